@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS board_snapshots (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    round_id    INTEGER NOT NULL,
+    start_slot  INTEGER NOT NULL,
+    end_slot    INTEGER NOT NULL,
+    created_at  INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_board_snapshots_round ON board_snapshots(round_id);
@@ -0,0 +1,3 @@
+-- SOL deployed on the square before this miner's deployment, captured at round close.
+-- Lets the top miner selection be verified later. NULL for rows written before this.
+ALTER TABLE deployments ADD COLUMN cumulative INTEGER;
@@ -0,0 +1,32 @@
+-- Round that was being finalized when the treasury snapshot was taken.
+ALTER TABLE treasury ADD COLUMN round_id INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_treasury_round_id ON treasury(round_id);
+
+-- Best-effort backfill: treasury rows are written right after their round at close,
+-- so link each one to the latest round inserted at most 60s before it.
+WITH events AS (
+  SELECT 0 AS is_treasury, id, julianday(created_at) AS t FROM rounds
+  UNION ALL
+  SELECT 1 AS is_treasury, id, julianday(created_at) AS t FROM treasury WHERE round_id IS NULL
+),
+running AS (
+  SELECT
+    is_treasury,
+    id,
+    t,
+    MAX(CASE WHEN is_treasury = 0 THEN id END) OVER w AS last_round_id,
+    MAX(CASE WHEN is_treasury = 0 THEN t END)  OVER w AS last_round_t
+  FROM events
+  WINDOW w AS (ORDER BY t, is_treasury ROWS UNBOUNDED PRECEDING)
+)
+UPDATE treasury
+SET round_id = m.last_round_id
+FROM (
+  SELECT id, last_round_id
+  FROM running
+  WHERE is_treasury = 1
+    AND last_round_id IS NOT NULL
+    AND (t - last_round_t) * 86400 <= 60
+) AS m
+WHERE m.id = treasury.id;
@@ -0,0 +1,10 @@
+-- Distinct miners that deployed in the round.
+ALTER TABLE rounds ADD COLUMN total_players INTEGER NOT NULL DEFAULT 0;
+
+UPDATE rounds
+SET total_players = (
+  SELECT COUNT(DISTINCT d.pubkey)
+  FROM deployments d
+  WHERE d.round_id = rounds.id
+    AND d.amount > 0
+);
@@ -0,0 +1,10 @@
+-- Admin fee taken from a winning deployment's refund: max(1, amount / 100).
+ALTER TABLE deployments ADD COLUMN admin_fee INTEGER NOT NULL DEFAULT 0;
+
+-- One-shot recompute for rows written before the column existed.
+UPDATE deployments
+SET admin_fee = MAX(1, deployments.amount / 100)
+FROM rounds r
+WHERE deployments.round_id = r.id
+  AND deployments.square_id = r.winning_square
+  AND deployments.amount > 0;
@@ -0,0 +1,25 @@
+-- Latest state of every miner account, replaced on each miners snapshot.
+-- deployed/cumulative are JSON arrays of 25 integers.
+CREATE TABLE IF NOT EXISTS miners_current (
+    pubkey               TEXT    PRIMARY KEY,
+    deployed             TEXT    NOT NULL,
+    total_deployed       INTEGER NOT NULL,
+    cumulative           TEXT    NOT NULL,
+    checkpoint_fee       INTEGER NOT NULL,
+    checkpoint_id        INTEGER NOT NULL,
+    last_claim_ore_at    INTEGER NOT NULL,
+    last_claim_sol_at    INTEGER NOT NULL,
+    rewards_sol          INTEGER NOT NULL,
+    rewards_ore          INTEGER NOT NULL,
+    refined_ore          INTEGER NOT NULL,
+    round_id             INTEGER NOT NULL,
+    lifetime_rewards_sol INTEGER NOT NULL,
+    lifetime_rewards_ore INTEGER NOT NULL,
+    updated_at           INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_miners_current_rewards_sol    ON miners_current(rewards_sol);
+CREATE INDEX IF NOT EXISTS idx_miners_current_rewards_ore    ON miners_current(rewards_ore);
+CREATE INDEX IF NOT EXISTS idx_miners_current_refined_ore    ON miners_current(refined_ore);
+CREATE INDEX IF NOT EXISTS idx_miners_current_total_deployed ON miners_current(total_deployed);
+CREATE INDEX IF NOT EXISTS idx_miners_current_round_id       ON miners_current(round_id);
@@ -0,0 +1,9 @@
+-- Single row recording how far the poller got, so a restart resumes at the right round boundary.
+CREATE TABLE IF NOT EXISTS poller_state (
+    id                 INTEGER PRIMARY KEY CHECK (id = 1),
+    -- round whose miners were last snapshot at round end
+    snapshot_round_id  INTEGER NOT NULL,
+    -- 1 once that round's deployments, round row and treasury were stored
+    snapshot_completed INTEGER NOT NULL,
+    updated_at         INTEGER NOT NULL
+);
@@ -0,0 +1,9 @@
+-- Net sol leaderboard ranks recorded periodically, compared against the current board by /leaderboard/movers.
+CREATE TABLE IF NOT EXISTS leaderboard_ranks (
+    -- rounds window as in ?window=, or 'all'
+    window_key TEXT    NOT NULL,
+    taken_at   INTEGER NOT NULL,
+    pubkey     TEXT    NOT NULL,
+    rank       INTEGER NOT NULL,
+    PRIMARY KEY (window_key, taken_at, pubkey)
+);
@@ -0,0 +1,3 @@
+-- 1 when the top miner reward was shared across the winning square (top_miner is SPLIT_ADDRESS).
+-- Existing rows are flagged at startup, the address lives in the ore program crate.
+ALTER TABLE rounds ADD COLUMN is_split INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,9 @@
+-- One row per miner and square in a round, so re-storing a round after a restart upserts
+-- instead of duplicating. Duplicates left by earlier restarts keep their newest row.
+DELETE FROM deployments
+WHERE id NOT IN (
+    SELECT MAX(id) FROM deployments GROUP BY round_id, pubkey, square_id
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_deployments_round_pubkey_square
+  ON deployments(round_id, pubkey, square_id);
@@ -1,11 +1,13 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::{HashMap, HashSet}, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc}, time::{Duration, Instant}};
 
+use async_graphql::SimpleObject;
 use ore_api::state::{Board, Miner, Round, Treasury};
 use serde::{Deserialize, Serialize};
+use solana_sdk::commitment_config::CommitmentConfig;
 use sqlx::{Pool, Sqlite};
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, watch, RwLock};
 
-use crate::GetDeploymentSquished;
+use crate::{database::{DbBoardSnapshot, DbTreasury, DeployPercentiles, LeaderboardMetric, LeaderboardWindow, MinerLeaderboardRow, RoundRow, UniqueMiners}, metrics::RpcMetrics, rpc::RpcRateLimiter, serde_util::u64_as_string, GetDeploymentSquished};
 
 #[derive(Clone, Debug, Serialize)]
 pub struct AppLiveDeployment {
@@ -41,14 +43,114 @@ pub struct AppState {
     pub staring_round: u64,
     pub rounds: Arc<RwLock<Vec<AppRound>>>,
     pub miners: Arc<RwLock<Vec<AppMiner>>>,
+    /// Unix timestamp of the last successful miners load, None until the first one.
+    pub miners_loaded_at: Arc<RwLock<Option<i64>>>,
     pub live_data_broadcaster: broadcast::Sender<LiveBroadcastData>,
+    /// Miners whose account changed in the last poll, for /ws subscribers.
+    pub miner_updates: broadcast::Sender<AppMiner>,
+    /// Id of the last round the poller stored, for /round/longpoll.
+    pub round_finalized: watch::Sender<u64>,
     pub live_round: Arc<RwLock<AppRound>>,
     pub live_deployments: Arc<RwLock<Vec<AppLiveDeployment>>>,
     pub db_pool: Pool<Sqlite>,
-    pub deployments_cache: Arc<RwLock<DeploymentsCache>>
+    /// Separate connections to the same database for streamed responses.
+    pub read_pool: Pool<Sqlite>,
+    pub db_2_pool: Option<Pool<Sqlite>>,
+    pub deployments_cache: Arc<RwLock<DeploymentsCache>>,
+    pub admin_token: Option<String>,
+    /// `READ_ONLY`: the poller keeps in-memory state live but nothing is written to the db.
+    pub read_only: bool,
+    /// False under `ENABLE_POLLER=false`: state comes from the db and live endpoints are off.
+    pub poller_enabled: bool,
+    /// Endpoints from `RPC_URLS` (or the single `RPC_URL`), without the scheme.
+    pub rpc_urls: Arc<Vec<String>>,
+    /// Index into `rpc_urls` of the endpoint the poller is currently using.
+    pub rpc_endpoint: Arc<AtomicUsize>,
+    pub slot_time_ms: u64,
+    pub commitment: CommitmentConfig,
+    /// Per-request timeout every rpc client is built with, from `RPC_TIMEOUT_SECS`.
+    pub rpc_timeout: Duration,
+    pub rpc_status: Arc<RwLock<RpcStatus>>,
+    pub rpc_metrics: Arc<RpcMetrics>,
+    pub rpc_limiter: Arc<RpcRateLimiter>,
+    /// Set while a gap backfill is running so the periodic scan and the admin route don't overlap.
+    pub gap_backfill_running: Arc<AtomicBool>,
+    /// Round ids whose accounts are closed on chain, skipped by later gap backfills.
+    pub unrecoverable_rounds: Arc<RwLock<HashSet<u64>>>,
+    /// Last /stats/unique-miners result and when it was computed.
+    pub unique_miners_cache: Arc<RwLock<Option<(Instant, UniqueMiners)>>>,
+    /// /stats/deploy-percentiles results keyed by the rounds window.
+    pub deploy_percentiles_cache: Arc<RwLock<HashMap<i64, (Instant, DeployPercentiles)>>>,
+    /// The default leaderboard pages, see `default_leaderboard_pages`.
+    pub leaderboard_cache: Arc<RwLock<LeaderboardCache>>,
+    /// Set once the default leaderboard pages have been computed, /ready waits for it.
+    pub leaderboard_warmed: Arc<AtomicBool>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Metric, window, min_rounds and limit of a cached leaderboard page.
+pub type LeaderboardCacheKey = (LeaderboardMetric, LeaderboardWindow, i64, i64);
+
+/// Cached leaderboard pages and when each was computed.
+pub type LeaderboardCache = HashMap<LeaderboardCacheKey, (Instant, Vec<MinerLeaderboardRow>)>;
+
+impl AppState {
+    /// The endpoint the poller is currently using.
+    pub fn rpc_url(&self) -> &str {
+        &self.rpc_urls[self.rpc_endpoint.load(Ordering::Relaxed) % self.rpc_urls.len()]
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// Empty state on top of `db_pool`, with defaults for everything main reads from env.
+    pub fn for_tests(db_pool: Pool<Sqlite>) -> Self {
+        AppState {
+            treasury: Arc::new(RwLock::new(AppTreasury::default())),
+            board: Arc::new(RwLock::new(AppBoard::default())),
+            staring_round: 0,
+            rounds: Arc::new(RwLock::new(vec![])),
+            miners: Arc::new(RwLock::new(vec![])),
+            miners_loaded_at: Arc::new(RwLock::new(None)),
+            live_data_broadcaster: broadcast::channel(16).0,
+            miner_updates: broadcast::channel(16).0,
+            round_finalized: watch::channel(0).0,
+            live_round: Arc::new(RwLock::new(AppRound::default())),
+            live_deployments: Arc::new(RwLock::new(vec![])),
+            read_pool: db_pool.clone(),
+            db_pool,
+            db_2_pool: None,
+            deployments_cache: Arc::new(RwLock::new(DeploymentsCache { item: HashMap::new() })),
+            admin_token: None,
+            read_only: false,
+            poller_enabled: true,
+            rpc_urls: Arc::new(vec!["localhost".to_string()]),
+            rpc_endpoint: Arc::new(AtomicUsize::new(0)),
+            slot_time_ms: 400,
+            commitment: CommitmentConfig::confirmed(),
+            rpc_timeout: Duration::from_secs(1),
+            rpc_status: Arc::new(RwLock::new(RpcStatus::Ok)),
+            rpc_metrics: Arc::new(RpcMetrics::default()),
+            rpc_limiter: Arc::new(RpcRateLimiter::from_env()),
+            gap_backfill_running: Arc::new(AtomicBool::new(false)),
+            unrecoverable_rounds: Arc::new(RwLock::new(HashSet::new())),
+            unique_miners_cache: Arc::new(RwLock::new(None)),
+            deploy_percentiles_cache: Arc::new(RwLock::new(HashMap::new())),
+            leaderboard_cache: Arc::new(RwLock::new(HashMap::new())),
+            leaderboard_warmed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+/// Health of the poller's rpc client as reported by /health.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RpcStatus {
+    Ok,
+    Reconnecting,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
 pub struct AppMiner {
     /// The authority of this miner account.
     pub authority: String,
@@ -56,12 +158,14 @@ pub struct AppMiner {
     /// The miner's prospects in the current round.
     pub deployed: [u64; 25],
     /// Total deployed (Sum of miners prospects)
+    #[serde(serialize_with = "u64_as_string")]
     pub total_deployed: u64,
 
     /// The cumulative amount of SOL deployed on each square prior to this miner's move.
     pub cumulative: [u64; 25],
 
     /// SOL witheld in reserve to pay for checkpointing.
+    #[serde(serialize_with = "u64_as_string")]
     pub checkpoint_fee: u64,
 
     /// The last round that this miner checkpointed.
@@ -74,21 +178,26 @@ pub struct AppMiner {
     pub last_claim_sol_at: i64,
 
     /// The amount of SOL this miner can claim.
+    #[serde(serialize_with = "u64_as_string")]
     pub rewards_sol: u64,
 
     /// The amount of ORE this miner can claim.
+    #[serde(serialize_with = "u64_as_string")]
     pub rewards_ore: u64,
 
     /// The amount of ORE this miner has earned from claim fees.
+    #[serde(serialize_with = "u64_as_string")]
     pub refined_ore: u64,
 
     /// The ID of the round this miner last played in.
     pub round_id: u64,
 
     /// The total amount of SOL this miner has mined across all blocks.
+    #[serde(serialize_with = "u64_as_string")]
     pub lifetime_rewards_sol: u64,
 
     /// The total amount of ORE this miner has mined across all blocks.
+    #[serde(serialize_with = "u64_as_string")]
     pub lifetime_rewards_ore: u64,
 }
 
@@ -117,12 +226,17 @@ impl From<Miner> for AppMiner {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AppTreasury {
+    #[serde(serialize_with = "u64_as_string")]
     pub balance: u64,
+    #[serde(serialize_with = "u64_as_string")]
     pub motherlode: u64,
+    #[serde(serialize_with = "u64_as_string")]
     pub total_staked: u64,
+    #[serde(serialize_with = "u64_as_string")]
     pub total_unclaimed: u64,
+    #[serde(serialize_with = "u64_as_string")]
     pub total_refined: u64,
 }
 
@@ -138,7 +252,19 @@ impl From<Treasury> for AppTreasury {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl From<DbTreasury> for AppTreasury {
+    fn from(t: DbTreasury) -> Self {
+        AppTreasury {
+            balance: t.balance as u64,
+            motherlode: t.motherlode as u64,
+            total_staked: t.total_staked as u64,
+            total_unclaimed: t.total_unclaimed as u64,
+            total_refined: t.total_refined as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AppRound {
     pub id: u64,
     pub deployed: [u64; 25],
@@ -154,6 +280,12 @@ pub struct AppRound {
     pub total_winnings: u64,
 }
 
+impl AppRound {
+    pub fn etag(&self) -> String {
+        format!("\"round-{}\"", self.id)
+    }
+}
+
 impl From<Round> for AppRound {
     fn from(r: Round) -> Self {
         AppRound {
@@ -173,19 +305,93 @@ impl From<Round> for AppRound {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl AppRound {
+    /// A stored round with the per-square `deployed` and `count` rebuilt from its deployments,
+    /// for instances that only read the database.
+    pub fn from_row(r: &RoundRow, deployed: [u64; 25], count: [u64; 25]) -> Self {
+        AppRound {
+            id: r.id as u64,
+            deployed,
+            count,
+            slot_hash: r.slot_hash.as_slice().try_into().unwrap_or([0; 32]),
+            expires_at: r.expires_at as u64,
+            motherlode: r.motherlode as u64,
+            rent_payer: r.rent_payer.clone(),
+            top_miner: r.top_miner.clone(),
+            top_miner_reward: r.top_miner_reward as u64,
+            total_deployed: r.total_deployed as u64,
+            total_vaulted: r.total_vaulted as u64,
+            total_winnings: r.total_winnings as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AppBoard {
     pub round_id: u64,
     pub start_slot: u64,
     pub end_slot: u64,
+    /// Estimated round length from start_slot to end_slot.
+    pub duration_secs: u64,
+    /// Estimated unix timestamp when the round ends. None until the round has an end slot.
+    pub ends_at: Option<i64>,
+    /// Slots left until end_slot as of the last poll.
+    pub slots_remaining: i64,
+    /// Latest slot observed by the poller.
+    pub current_slot: u64,
+    /// Latest block height observed by the poller, if the rpc returned one.
+    pub block_height: Option<u64>,
+    /// The round is more than `ROUND_STALL_SLOTS` past its end slot without the round id
+    /// advancing, e.g. the game is paused or the rpc is lagging.
+    pub stalled: bool,
 }
 
-impl From<Board> for AppBoard {
-    fn from(b: Board) -> Self {
+impl AppBoard {
+    /// Changes with every poll, since slots_remaining, ends_at and block_height are
+    /// derived from the slot the board was read at.
+    pub fn etag(&self) -> String {
+        format!("\"board-{}-{}-{}-{}-{}-{}\"", self.round_id, self.start_slot, self.end_slot, self.current_slot, self.block_height.unwrap_or(0), self.stalled)
+    }
+
+    pub fn new(b: Board, current_slot: u64, slot_time_ms: u64) -> Self {
+        // end_slot is u64::MAX until the first deploy of the round
+        let has_end = b.end_slot != u64::MAX;
+        let (duration_secs, ends_at, slots_remaining) = if has_end {
+            let slots_remaining = b.end_slot as i64 - current_slot as i64;
+            let duration_secs = b.end_slot.saturating_sub(b.start_slot) * slot_time_ms / 1000;
+            let ends_at = chrono::Utc::now().timestamp() + (slots_remaining.max(0) * slot_time_ms as i64) / 1000;
+            (duration_secs, Some(ends_at), slots_remaining)
+        } else {
+            (0, None, 0)
+        };
         AppBoard {
             round_id: b.round_id,
             start_slot: b.start_slot,
             end_slot: b.end_slot,
+            duration_secs,
+            ends_at,
+            slots_remaining,
+            current_slot,
+            block_height: None,
+            stalled: false,
+        }
+    }
+
+    /// Board as last recorded by the poller, for instances that don't poll themselves.
+    /// Slot-relative fields are left empty since the current slot is unknown.
+    pub fn from_snapshot(b: &DbBoardSnapshot, slot_time_ms: u64) -> Self {
+        let (start_slot, end_slot) = (b.start_slot as u64, b.end_slot as u64);
+        let duration_secs = if end_slot != u64::MAX {
+            end_slot.saturating_sub(start_slot) * slot_time_ms / 1000
+        } else {
+            0
+        };
+        AppBoard {
+            round_id: b.round_id as u64,
+            start_slot,
+            end_slot,
+            duration_secs,
+            ..Default::default()
         }
     }
 }
@@ -0,0 +1,137 @@
+use std::future::Future;
+
+use solana_client::{client_error::Result as ClientResult, nonblocking::rpc_client::RpcClient, rpc_config::RpcProgramAccountsConfig, rpc_response::RpcResult};
+use solana_sdk::{account::Account, commitment_config::CommitmentConfig};
+use steel::Pubkey;
+
+/// The chain reads the poller depends on, so they can be served by something other than
+/// a live `RpcClient` (e.g. canned Treasury/Board/Round/Miner bytes).
+pub trait ChainSource {
+    fn get_account_data(&self, address: &Pubkey) -> impl Future<Output = ClientResult<Vec<u8>>> + Send;
+
+    /// Unlike `get_account_data`, a closed account is `Ok` with a `None` value.
+    fn get_account_with_commitment(
+        &self,
+        address: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> impl Future<Output = RpcResult<Option<Account>>> + Send;
+
+    fn get_slot(&self) -> impl Future<Output = ClientResult<u64>> + Send;
+
+    fn get_block_height(&self) -> impl Future<Output = ClientResult<u64>> + Send;
+
+    fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> impl Future<Output = ClientResult<Vec<(Pubkey, Account)>>> + Send;
+}
+
+impl ChainSource for RpcClient {
+    fn get_account_data(&self, address: &Pubkey) -> impl Future<Output = ClientResult<Vec<u8>>> + Send {
+        RpcClient::get_account_data(self, address)
+    }
+
+    fn get_account_with_commitment(
+        &self,
+        address: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> impl Future<Output = RpcResult<Option<Account>>> + Send {
+        RpcClient::get_account_with_commitment(self, address, commitment)
+    }
+
+    fn get_slot(&self) -> impl Future<Output = ClientResult<u64>> + Send {
+        RpcClient::get_slot(self)
+    }
+
+    fn get_block_height(&self) -> impl Future<Output = ClientResult<u64>> + Send {
+        RpcClient::get_block_height(self)
+    }
+
+    fn get_program_accounts_with_config(
+        &self,
+        program_id: &Pubkey,
+        config: RpcProgramAccountsConfig,
+    ) -> impl Future<Output = ClientResult<Vec<(Pubkey, Account)>>> + Send {
+        RpcClient::get_program_accounts_with_config(self, program_id, config)
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use std::{collections::HashMap, future::Future};
+
+    use bytemuck::Pod;
+    use solana_client::{client_error::{ClientError, ClientErrorKind, Result as ClientResult}, rpc_config::RpcProgramAccountsConfig, rpc_response::{Response, RpcResponseContext, RpcResult}};
+    use solana_sdk::{account::Account, commitment_config::CommitmentConfig};
+    use steel::{Discriminator, Pubkey};
+
+    use super::ChainSource;
+
+    /// Serves accounts from memory, written the way steel lays them out on chain. Anything
+    /// not inserted reads as a missing account.
+    #[derive(Default)]
+    pub struct MockChainSource {
+        pub accounts: HashMap<Pubkey, Vec<u8>>,
+        pub slot: u64,
+        pub block_height: u64,
+    }
+
+    impl MockChainSource {
+        /// Stores `account` behind its 8-byte discriminator.
+        pub fn insert<A: Discriminator + Pod>(&mut self, address: Pubkey, account: &A) {
+            let mut data = vec![0; 8];
+            data[0] = A::discriminator();
+            data.extend_from_slice(bytemuck::bytes_of(account));
+            self.accounts.insert(address, data);
+        }
+
+        fn account(&self, address: &Pubkey) -> Option<Account> {
+            self.accounts.get(address).map(|data| Account {
+                lamports: 1,
+                data: data.clone(),
+                owner: ore_api::ID,
+                executable: false,
+                rent_epoch: 0,
+            })
+        }
+    }
+
+    fn missing_account(address: &Pubkey) -> ClientError {
+        ClientErrorKind::Custom(format!("AccountNotFound: pubkey={address}")).into()
+    }
+
+    impl ChainSource for MockChainSource {
+        fn get_account_data(&self, address: &Pubkey) -> impl Future<Output = ClientResult<Vec<u8>>> + Send {
+            std::future::ready(self.accounts.get(address).cloned().ok_or_else(|| missing_account(address)))
+        }
+
+        fn get_account_with_commitment(
+            &self,
+            address: &Pubkey,
+            _commitment: CommitmentConfig,
+        ) -> impl Future<Output = RpcResult<Option<Account>>> + Send {
+            std::future::ready(Ok(Response {
+                context: RpcResponseContext::new(self.slot),
+                value: self.account(address),
+            }))
+        }
+
+        fn get_slot(&self) -> impl Future<Output = ClientResult<u64>> + Send {
+            std::future::ready(Ok(self.slot))
+        }
+
+        fn get_block_height(&self) -> impl Future<Output = ClientResult<u64>> + Send {
+            std::future::ready(Ok(self.block_height))
+        }
+
+        fn get_program_accounts_with_config(
+            &self,
+            _program_id: &Pubkey,
+            _config: RpcProgramAccountsConfig,
+        ) -> impl Future<Output = ClientResult<Vec<(Pubkey, Account)>>> + Send {
+            let accounts = self.accounts.keys().filter_map(|k| Some((*k, self.account(k)?))).collect();
+            std::future::ready(Ok(accounts))
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::{env, str::FromStr, sync::OnceLock};
+
+use ore_api::{consts::{BOARD, ROUND, TREASURY}, state::round_pda};
+use steel::Pubkey;
+
+use crate::{entropy_api::ORE_VAR_ADDRESS, BOARD_ADDRESS};
+
+static ADDRESSES: OnceLock<Addresses> = OnceLock::new();
+
+/// Program and account addresses the server reads. Mainnet unless `PROGRAM_ID` is set,
+/// in which case the board, treasury and round pdas are derived from it instead.
+#[derive(Debug, Clone)]
+pub struct Addresses {
+    pub program_id: Pubkey,
+    pub board: Pubkey,
+    pub treasury: Pubkey,
+    /// Entropy var sampled for the round rng, `ORE_VAR_ADDRESS` overrides it.
+    pub ore_var: Pubkey,
+    /// `CLUSTER` if set, otherwise mainnet for the mainnet program and custom for any other.
+    pub cluster: String,
+}
+
+impl Addresses {
+    fn mainnet() -> Self {
+        Addresses {
+            program_id: ore_api::id(),
+            board: BOARD_ADDRESS,
+            treasury: ore_api::consts::TREASURY_ADDRESS,
+            ore_var: ORE_VAR_ADDRESS,
+            cluster: "mainnet".to_string(),
+        }
+    }
+
+    fn from_env() -> anyhow::Result<Self> {
+        let mut addresses = Addresses::mainnet();
+        if let Ok(program_id) = env::var("PROGRAM_ID") {
+            let program_id = Pubkey::from_str(program_id.trim())
+                .map_err(|e| anyhow::anyhow!("Invalid PROGRAM_ID {:?}: {}", program_id, e))?;
+            if program_id != addresses.program_id {
+                addresses.program_id = program_id;
+                addresses.board = Pubkey::find_program_address(&[BOARD], &program_id).0;
+                addresses.treasury = Pubkey::find_program_address(&[TREASURY], &program_id).0;
+                addresses.cluster = "custom".to_string();
+            }
+        }
+        if let Ok(ore_var) = env::var("ORE_VAR_ADDRESS") {
+            addresses.ore_var = Pubkey::from_str(ore_var.trim())
+                .map_err(|e| anyhow::anyhow!("Invalid ORE_VAR_ADDRESS {:?}: {}", ore_var, e))?;
+        }
+        if let Some(cluster) = env::var("CLUSTER").ok().map(|c| c.trim().to_lowercase()).filter(|c| !c.is_empty()) {
+            addresses.cluster = cluster;
+        }
+        Ok(addresses)
+    }
+
+    /// Address of the round account for `round_id`.
+    pub fn round(&self, round_id: u64) -> Pubkey {
+        if self.program_id == ore_api::id() {
+            round_pda(round_id).0
+        } else {
+            Pubkey::find_program_address(&[ROUND, &round_id.to_le_bytes()], &self.program_id).0
+        }
+    }
+}
+
+/// Resolves the addresses from env, call once at startup before anything reads them.
+/// Errors if they were already set, by an earlier call or an `addresses()` read that
+/// fell back to mainnet, since the env would otherwise be silently ignored.
+pub fn init_addresses() -> anyhow::Result<&'static Addresses> {
+    let resolved = Addresses::from_env()?;
+    ADDRESSES.set(resolved)
+        .map_err(|_| anyhow::anyhow!("Addresses already initialized for program {}", addresses().program_id))?;
+    Ok(addresses())
+}
+
+/// The active addresses, mainnet if `init_addresses` hasn't run.
+pub fn addresses() -> &'static Addresses {
+    ADDRESSES.get_or_init(Addresses::mainnet)
+}
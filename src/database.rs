@@ -1,11 +1,13 @@
-use std::{str::FromStr, time::Duration};
+use std::{collections::HashMap, str::FromStr, time::Duration};
 
-use ore_api::state::{Miner, Round, Treasury};
+use async_graphql::SimpleObject;
+use ore_api::{consts::SPLIT_ADDRESS, state::{Board, Miner, Round, Treasury}};
 use serde::{Deserialize, Serialize};
 use sqlx::{prelude::FromRow, sqlite::SqliteConnectOptions, Pool, QueryBuilder, Sqlite};
 use tokio::time::Instant;
+use tokio_stream::{Stream, StreamExt};
 
-use crate::{app_state::AppMiner};
+use crate::{app_state::AppMiner, serde_util::u64_as_string};
 
 #[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
 pub struct CreateMinerSnapshot {
@@ -49,6 +51,14 @@ pub struct CreateTreasury {
     pub total_unclaimed: i64,
     pub total_refined: i64,
     pub created_at: String, // RFC3339
+    /// Round being finalized when this snapshot was taken, if known.
+    pub round_id: Option<i64>,
+}
+
+impl CreateTreasury {
+    pub fn at_round(t: Treasury, round_id: u64) -> Self {
+        CreateTreasury { round_id: Some(round_id as i64), ..t.into() }
+    }
 }
 
 impl From<Treasury> for CreateTreasury {
@@ -60,6 +70,7 @@ impl From<Treasury> for CreateTreasury {
             total_unclaimed: r.total_unclaimed as i64,
             total_refined: r.total_refined as i64,
             created_at: chrono::Utc::now().to_rfc3339(),
+            round_id: None,
         }
     }
 }
@@ -73,6 +84,35 @@ pub struct DbTreasury {
     pub total_unclaimed: i64,
     pub total_refined: i64,
     pub created_at: String, // RFC3339
+    pub round_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct CreateBoardSnapshot {
+    pub round_id: i64,
+    pub start_slot: i64,
+    pub end_slot: i64,
+    pub created_at: i64,
+}
+
+impl From<Board> for CreateBoardSnapshot {
+    fn from(b: Board) -> Self {
+        CreateBoardSnapshot {
+            round_id: b.round_id as i64,
+            start_slot: b.start_slot as i64,
+            end_slot: b.end_slot as i64,
+            created_at: chrono::Utc::now().timestamp(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct DbBoardSnapshot {
+    pub id: i64,
+    pub round_id: i64,
+    pub start_slot: i64,
+    pub end_slot: i64,
+    pub created_at: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
@@ -84,23 +124,68 @@ pub struct CreateDeployment {
     pub sol_earned: i64,
     pub ore_earned: i64,
     pub unclaimed_ore: i64,
+    /// The miner's `cumulative` on this square at round close.
+    pub cumulative: i64,
+    pub admin_fee: i64,
     pub created_at: String, // RFC3339
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+/// Serializes byte blobs (e.g. slot hashes) as lowercase hex strings.
+pub mod hex_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 {
+            return Err(D::Error::custom("hex string has odd length"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                let pair = hex.get(i..i + 2).ok_or_else(|| D::Error::custom("invalid hex string"))?;
+                u8::from_str_radix(pair, 16).map_err(D::Error::custom)
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow, SimpleObject)]
+#[graphql(complex)]
 pub struct RoundRow {
     pub id: i64,
+    #[serde(with = "hex_bytes")]
+    #[graphql(skip)]
     pub slot_hash: Vec<u8>,
     pub winning_square: i64,
     pub expires_at: i64,
+    #[serde(serialize_with = "u64_as_string")]
     pub motherlode: i64,
     pub rent_payer: String,
     pub top_miner: String,
+    #[serde(serialize_with = "u64_as_string")]
     pub top_miner_reward: i64,
+    #[serde(serialize_with = "u64_as_string")]
     pub total_deployed: i64,
+    #[serde(serialize_with = "u64_as_string")]
     pub total_vaulted: i64,
+    #[serde(serialize_with = "u64_as_string")]
     pub total_winnings: i64,
     pub created_at: String, // RFC3339
+    /// Distinct miners that deployed in the round, 0 if unknown.
+    pub total_players: i64,
+    /// The top miner reward was shared pro-rata across the winning square.
+    pub is_split: bool,
+}
+
+impl RoundRow {
+    pub fn with_players(r: Round, total_players: usize) -> Self {
+        RoundRow { total_players: total_players as i64, ..r.into() }
+    }
 }
 
 impl From<Round> for RoundRow {
@@ -119,6 +204,8 @@ impl From<Round> for RoundRow {
                 total_vaulted: r.total_vaulted as i64,
                 total_winnings: r.total_winnings as i64,
                 created_at: chrono::Utc::now().to_rfc3339(),
+                total_players: 0,
+                is_split: r.top_miner == SPLIT_ADDRESS,
             }
         } else {
             RoundRow {
@@ -134,17 +221,26 @@ impl From<Round> for RoundRow {
                 total_vaulted: r.total_vaulted as i64,
                 total_winnings: r.total_winnings as i64,
                 created_at: chrono::Utc::now().to_rfc3339(),
+                total_players: 0,
+                is_split: r.top_miner == SPLIT_ADDRESS,
             }
         }
     }
 }
 
-pub async fn insert_treasury(pool: &Pool<Sqlite>, r: &CreateTreasury) -> Result<(), sqlx::Error> {
+pub async fn insert_treasury(pool: &Pool<Sqlite>, db_2: &Option<Pool<Sqlite>>, r: &CreateTreasury) -> Result<(), sqlx::Error> {
+    retry_busy("treasury", || insert_treasury_into(pool, r)).await?;
+    let row = r.clone();
+    replicate(db_2, "treasury", |p| async move { insert_treasury_into(&p, &row).await });
+    Ok(())
+}
+
+async fn insert_treasury_into(pool: &Pool<Sqlite>, r: &CreateTreasury) -> Result<(), sqlx::Error> {
     sqlx::query(
         r#"
         INSERT INTO treasury (
-            balance, motherlode, total_staked, total_unclaimed, total_refined, created_at
-        ) VALUES (?, ?, ?, ?, ?, ?)
+            balance, motherlode, total_staked, total_unclaimed, total_refined, created_at, round_id
+        ) VALUES (?, ?, ?, ?, ?, ?, ?)
         "#
     )
     .bind(r.balance)
@@ -153,6 +249,7 @@ pub async fn insert_treasury(pool: &Pool<Sqlite>, r: &CreateTreasury) -> Result<
     .bind(r.total_unclaimed)
     .bind(r.total_refined)
     .bind(&r.created_at)
+    .bind(r.round_id)
     .execute(pool)
     .await?;
 
@@ -175,25 +272,145 @@ pub async fn get_treasuries(pool: &Pool<Sqlite>, limit: i64, offset: i64) -> Res
     Ok(treasuries)
 }
 
-pub async fn insert_round(pool: &Pool<Sqlite>, r: &RoundRow) -> Result<(), sqlx::Error> {
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct TreasuryDelta {
+    pub id: i64,
+    pub round_id: Option<i64>,
+    pub created_at: String,
+    pub balance: i64,
+    pub motherlode: i64,
+    pub total_staked: i64,
+    pub total_unclaimed: i64,
+    pub total_refined: i64,
+}
+
+/// Change of each treasury field from the previous snapshot for the latest `limit`
+/// snapshots, newest first. The very first snapshot has zero deltas.
+pub async fn get_treasury_deltas(pool: &Pool<Sqlite>, limit: i64) -> Result<Vec<TreasuryDelta>, sqlx::Error> {
+    let deltas = sqlx::query_as::<_, TreasuryDelta>(
+        r#"
+        SELECT * FROM (
+          SELECT
+            id,
+            round_id,
+            created_at,
+            balance         - LAG(balance, 1, balance) OVER w                 AS balance,
+            motherlode      - LAG(motherlode, 1, motherlode) OVER w           AS motherlode,
+            total_staked    - LAG(total_staked, 1, total_staked) OVER w       AS total_staked,
+            total_unclaimed - LAG(total_unclaimed, 1, total_unclaimed) OVER w AS total_unclaimed,
+            total_refined   - LAG(total_refined, 1, total_refined) OVER w     AS total_refined
+          FROM treasury
+          -- the window plus the snapshot before it, so its first row has a real predecessor
+          WHERE id >= COALESCE((SELECT id FROM treasury ORDER BY id DESC LIMIT 1 OFFSET ?), 0)
+          WINDOW w AS (ORDER BY id)
+        )
+        ORDER BY id DESC
+        LIMIT ?
+        "#
+    )
+    .bind(limit)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(deltas)
+}
+
+/// The treasury snapshot taken when the given round was finalized.
+pub async fn get_treasury_at_round(pool: &Pool<Sqlite>, round_id: i64) -> Result<Option<DbTreasury>, sqlx::Error> {
+    let treasury = sqlx::query_as::<_, DbTreasury>(
+        r#"
+        SELECT * FROM treasury
+        WHERE round_id = ?
+        ORDER BY id DESC
+        LIMIT 1
+        "#
+    )
+    .bind(round_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(treasury)
+}
+
+pub async fn insert_board_snapshot(pool: &Pool<Sqlite>, db_2: &Option<Pool<Sqlite>>, b: &CreateBoardSnapshot) -> Result<(), sqlx::Error> {
+    retry_busy("board snapshot", || insert_board_snapshot_into(pool, b)).await?;
+    let row = b.clone();
+    replicate(db_2, "board snapshot", |p| async move { insert_board_snapshot_into(&p, &row).await });
+    Ok(())
+}
+
+async fn insert_board_snapshot_into(pool: &Pool<Sqlite>, b: &CreateBoardSnapshot) -> Result<(), sqlx::Error> {
     sqlx::query(
+        r#"
+        INSERT INTO board_snapshots (
+            round_id, start_slot, end_slot, created_at
+        ) VALUES (?, ?, ?, ?)
+        "#
+    )
+    .bind(b.round_id)
+    .bind(b.start_slot)
+    .bind(b.end_slot)
+    .bind(b.created_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_board_snapshots(pool: &Pool<Sqlite>, limit: i64, offset: i64) -> Result<Vec<DbBoardSnapshot>, sqlx::Error> {
+    let boards = sqlx::query_as::<_, DbBoardSnapshot>(
+        r#"
+        SELECT * FROM board_snapshots
+        ORDER BY id DESC
+        LIMIT ? OFFSET ?
+        "#
+    )
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(boards)
+}
+
+pub async fn insert_round(pool: &Pool<Sqlite>, db_2: &Option<Pool<Sqlite>>, r: &RoundRow) -> Result<(), sqlx::Error> {
+    retry_busy("round", || insert_round_into(pool, r)).await?;
+    let row = r.clone();
+    replicate(db_2, "round", |p| async move { insert_round_into(&p, &row).await });
+    Ok(())
+}
+
+async fn insert_round_into(pool: &Pool<Sqlite>, r: &RoundRow) -> Result<(), sqlx::Error> {
+    let result = sqlx::query(
         r#"
         INSERT INTO rounds (
             id, slot_hash, winning_square, expires_at, motherlode, rent_payer, top_miner,
-            top_miner_reward, total_deployed, total_vaulted, total_winnings, created_at
-        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            top_miner_reward, total_deployed, total_vaulted, total_winnings, created_at, total_players,
+            is_split
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         ON CONFLICT(id) DO UPDATE SET
             slot_hash        = excluded.slot_hash,
             winning_square   = excluded.winning_square,
             expires_at       = excluded.expires_at,
             motherlode       = excluded.motherlode,
             rent_payer       = excluded.rent_payer,
-            top_miner        = excluded.top_miner,
+            -- the on-chain top_miner stays default until someone checkpoints, keep the finalizer's
+            top_miner        = CASE WHEN rounds.top_miner = '11111111111111111111111111111111'
+                                    THEN excluded.top_miner ELSE rounds.top_miner END,
             top_miner_reward = excluded.top_miner_reward,
             total_deployed   = excluded.total_deployed,
             total_vaulted    = excluded.total_vaulted,
             total_winnings   = excluded.total_winnings,
-            created_at       = excluded.created_at
+            -- refetches and backfills run long after the round, keep when it was first stored
+            created_at       = rounds.created_at,
+            -- backfilled rounds don't know their players, keep what the finalizer stored
+            total_players    = MAX(rounds.total_players, excluded.total_players),
+            is_split         = CASE WHEN rounds.top_miner = '11111111111111111111111111111111'
+                                    THEN excluded.is_split ELSE rounds.is_split END
+        -- never replace a final slot_hash with an unset (zeros) or failed reset (0xFF) one
+        WHERE excluded.slot_hash NOT IN (zeroblob(32), x'FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF')
+           OR rounds.slot_hash IN (zeroblob(32), x'FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF')
         "#
     )
     .bind(r.id)
@@ -208,164 +425,819 @@ pub async fn insert_round(pool: &Pool<Sqlite>, r: &RoundRow) -> Result<(), sqlx:
     .bind(r.total_vaulted)
     .bind(r.total_winnings)
     .bind(&r.created_at)
+    .bind(r.total_players)
+    .bind(r.is_split)
     .execute(pool)
     .await?;
 
+    if result.rows_affected() == 0 {
+        tracing::warn!("Kept stored round {}, refused to overwrite its slot_hash with an unset one", r.id);
+    }
+
     Ok(())
 }
 
-pub async fn get_round_by_id(pool: &Pool<Sqlite>, round_id: i64) -> Result<Vec<RoundRow>, sqlx::Error> {
-    let rounds = sqlx::query_as::<_, RoundRow>(
+/// Flags stored rounds won by SPLIT_ADDRESS that predate the is_split column.
+pub async fn backfill_round_split_flags(pool: &Pool<Sqlite>) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("UPDATE rounds SET is_split = 1 WHERE is_split = 0 AND top_miner = ?")
+        .bind(SPLIT_ADDRESS.to_string())
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct RoundPlayers {
+    pub round_id: i64,
+    pub total_players: i64,
+    pub created_at: String, // RFC3339
+}
+
+/// Player counts of the last `rounds` rounds, oldest first.
+pub async fn get_players_per_round(pool: &Pool<Sqlite>, rounds: i64) -> Result<Vec<RoundPlayers>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, RoundPlayers>(
         r#"
-        SELECT * FROM rounds
-        WHERE id = ?
+        SELECT round_id, total_players, created_at FROM (
+          SELECT id AS round_id, total_players, created_at
+          FROM rounds
+          ORDER BY id DESC
+          LIMIT ?
+        )
+        ORDER BY round_id ASC
         "#
     )
-    .bind(round_id)
+    .bind(rounds)
     .fetch_all(pool)
     .await?;
 
-    return Ok(rounds)
+    Ok(rows)
 }
 
-pub async fn get_rounds(pool: &Pool<Sqlite>, limit: i64, offset: i64, ml: Option<bool>) -> Result<Vec<RoundRow>, sqlx::Error> {
-    if let Some(ml) = ml {
-        if ml {
-            let rounds = sqlx::query_as::<_, RoundRow>(
-                r#"
-                SELECT * FROM rounds
-                WHERE motherlode > 0
-                ORDER BY id DESC
-                LIMIT ? OFFSET ?
-                "#
-            )
-            .bind(limit)
-            .bind(offset)
-            .fetch_all(pool)
-            .await?;
-
-            return Ok(rounds)
-        }
-    }
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct DailyStats {
+    /// UTC day, YYYY-MM-DD.
+    pub day: String,
+    pub rounds: i64,
+    pub total_sol_deployed: i64,
+    pub total_ore_earned: i64,
+    pub active_miners: i64,
+    pub motherlodes: i64,
+}
 
-    let rounds = sqlx::query_as::<_, RoundRow>(
+/// Per UTC day aggregates over the last `days` days including today, oldest first.
+/// created_at is RFC3339 text; strftime/julianday parse it and normalize any offset to UTC.
+pub async fn get_daily_stats(pool: &Pool<Sqlite>, days: i64) -> Result<Vec<DailyStats>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, DailyStats>(
         r#"
-        SELECT * FROM rounds
-        ORDER BY id DESC
-        LIMIT ? OFFSET ?
+        WITH r AS (
+          SELECT id, strftime('%Y-%m-%d', created_at) AS day, total_deployed, motherlode
+          FROM rounds
+          WHERE julianday(created_at) >= julianday('now', 'start of day', ?)
+        ),
+        d AS (
+          SELECT
+            r.day,
+            SUM(dep.ore_earned)        AS ore,
+            COUNT(DISTINCT dep.pubkey) AS miners
+          FROM r
+          JOIN deployments dep ON dep.round_id = r.id
+          GROUP BY r.day
+        )
+        SELECT
+          r.day,
+          COUNT(*)                  AS rounds,
+          SUM(r.total_deployed)     AS total_sol_deployed,
+          COALESCE(d.ore, 0)        AS total_ore_earned,
+          COALESCE(d.miners, 0)     AS active_miners,
+          SUM(r.motherlode > 0)     AS motherlodes
+        FROM r
+        LEFT JOIN d ON d.day = r.day
+        GROUP BY r.day
+        ORDER BY r.day ASC
         "#
     )
-    .bind(limit)
-    .bind(offset)
+    .bind(format!("-{} days", days - 1))
     .fetch_all(pool)
     .await?;
 
-    Ok(rounds)
+    Ok(rows)
 }
 
-pub async fn get_rounds_via_cursor(pool: &Pool<Sqlite>, limit: i64, cursor: i64, ml: Option<bool>) -> Result<Vec<RoundRow>, sqlx::Error> {
-    if let Some(ml) = ml {
-        if ml {
-            let rounds = sqlx::query_as::<_, RoundRow>(
-                r#"
-                SELECT * FROM rounds
-                WHERE motherlode > 0
-                AND id < ?
-                ORDER BY id DESC
-                LIMIT ?
-                "#
-            )
-            .bind(cursor)
-            .bind(limit)
-            .fetch_all(pool)
-            .await?;
+#[derive(Serialize, Deserialize, Debug, Clone, Default, FromRow)]
+pub struct HourlyActivity {
+    /// UTC hour of day, 0-23.
+    pub hour: i64,
+    pub deployments: i64,
+    pub total_deployed: i64,
+}
 
-            return Ok(rounds)
+/// Deployments of rounds stored in the last `days` days, bucketed by the UTC hour of day
+/// the round was stored. Always 24 entries, hours with no deployments are zero.
+pub async fn get_hourly_activity(pool: &Pool<Sqlite>, days: i64) -> Result<Vec<HourlyActivity>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, HourlyActivity>(
+        r#"
+        WITH r AS (
+          SELECT id, CAST(strftime('%H', created_at) AS INTEGER) AS hour
+          FROM rounds
+          WHERE julianday(created_at) >= julianday('now', ?)
+        )
+        SELECT
+          r.hour,
+          COUNT(*)                     AS deployments,
+          COALESCE(SUM(dep.amount), 0) AS total_deployed
+        FROM r
+        JOIN deployments dep ON dep.round_id = r.id
+        GROUP BY r.hour
+        "#
+    )
+    .bind(format!("-{} days", days))
+    .fetch_all(pool)
+    .await?;
+
+    let mut hours: Vec<HourlyActivity> = (0..24).map(|hour| HourlyActivity { hour, ..Default::default() }).collect();
+    for row in rows {
+        if let Some(h) = usize::try_from(row.hour).ok().and_then(|h| hours.get_mut(h)) {
+            *h = row;
         }
     }
+    Ok(hours)
+}
 
-    let rounds = sqlx::query_as::<_, RoundRow>(
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct HourlyRounds {
+    /// UTC hour as `YYYY-MM-DD HH`.
+    pub hour: String,
+    pub rounds: i64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RoundRate {
+    /// Rounds stored in the last 60 minutes.
+    pub last_hour: i64,
+    /// Oldest first, one entry per hour including empty ones.
+    pub hours: Vec<HourlyRounds>,
+}
+
+/// Rounds stored per UTC hour over the last `hours` hours (the current one included),
+/// plus a rolling count for the last 60 minutes.
+pub async fn get_round_rate(pool: &Pool<Sqlite>, hours: i64) -> Result<RoundRate, sqlx::Error> {
+    let rows = sqlx::query_as::<_, HourlyRounds>(
         r#"
-        SELECT * FROM rounds
-        WHERE id < ?
-        ORDER BY id DESC
-        LIMIT ?
+        SELECT
+          strftime('%Y-%m-%d %H', created_at) AS hour,
+          COUNT(*)                            AS rounds
+        FROM rounds
+        WHERE julianday(created_at) >= julianday('now', 'start of day', '+' || strftime('%H', 'now') || ' hours', ?)
+        GROUP BY hour
         "#
     )
-    .bind(cursor)
-    .bind(limit)
+    .bind(format!("-{} hours", hours - 1))
     .fetch_all(pool)
     .await?;
 
-    Ok(rounds)
+    let (last_hour,): (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FROM rounds
+        WHERE julianday(created_at) >= julianday('now', '-1 hour')
+        "#
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let counts: HashMap<String, i64> = rows.into_iter().map(|r| (r.hour, r.rounds)).collect();
+    let now = chrono::Utc::now();
+    let hours = (0..hours)
+        .rev()
+        .map(|ago| {
+            let hour = (now - chrono::Duration::hours(ago)).format("%Y-%m-%d %H").to_string();
+            let rounds = counts.get(&hour).copied().unwrap_or(0);
+            HourlyRounds { hour, rounds }
+        })
+        .collect();
+
+    Ok(RoundRate { last_hour, hours })
 }
 
-pub async fn get_miner_rounds(pool: &Pool<Sqlite>, pubkey: String, limit: i64, offset: i64) -> Result<Vec<RoundRow>, sqlx::Error> {
-    let rounds = sqlx::query_as::<_, RoundRow>(
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct RentPayerRow {
+    pub rent_payer: String,
+    pub rounds_funded: i64,
+    /// Rounds opened by this payer that hit the motherlode.
+    pub motherlodes: i64,
+}
+
+pub async fn get_top_rent_payers(pool: &Pool<Sqlite>, limit: i64, offset: i64) -> Result<Vec<RentPayerRow>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, RentPayerRow>(
         r#"
-        SELECT * FROM rounds r
-        WHERE EXISTS (
-          SELECT 1 FROM deployments d
-          WHERE d.round_id = r.id
-            AND d.pubkey   = ?
-        )
-        ORDER BY r.created_at DESC
+        SELECT
+          rent_payer,
+          COUNT(*)            AS rounds_funded,
+          SUM(motherlode > 0) AS motherlodes
+        FROM rounds
+        GROUP BY rent_payer
+        ORDER BY rounds_funded DESC, rent_payer
         LIMIT ? OFFSET ?
         "#
     )
-    .bind(pubkey)
     .bind(limit)
     .bind(offset)
     .fetch_all(pool)
     .await?;
 
-    Ok(rounds)
+    Ok(rows)
 }
 
-pub async fn get_miner_rounds_via_cursor(pool: &Pool<Sqlite>, pubkey: String, limit: i64, cursor: i64) -> Result<Vec<RoundRow>, sqlx::Error> {
-    let rounds = sqlx::query_as::<_, RoundRow>(
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct AdminFeeTotal {
+    pub rounds: i64,
+    pub from_round_id: Option<i64>,
+    pub to_round_id: Option<i64>,
+    pub total_admin_fee: i64,
+}
+
+/// Admin fees summed over the last `rounds` rounds.
+pub async fn get_admin_fee_total(pool: &Pool<Sqlite>, rounds: i64) -> Result<AdminFeeTotal, sqlx::Error> {
+    let row = sqlx::query_as::<_, AdminFeeTotal>(
         r#"
-        SELECT * FROM rounds r
-        WHERE EXISTS (
-          SELECT 1 FROM deployments d
-          WHERE d.round_id = r.id
-            AND d.pubkey   = ?
+        WITH last_n AS (
+          SELECT id FROM rounds ORDER BY id DESC LIMIT ?
         )
-        AND r.id < ?
-        ORDER BY r.id DESC
-        LIMIT ?
+        SELECT
+          (SELECT COUNT(*) FROM last_n) AS rounds,
+          (SELECT MIN(id) FROM last_n)  AS from_round_id,
+          (SELECT MAX(id) FROM last_n)  AS to_round_id,
+          COALESCE((
+            SELECT SUM(d.admin_fee)
+            FROM deployments d
+            JOIN last_n r ON r.id = d.round_id
+          ), 0) AS total_admin_fee
         "#
     )
-    .bind(pubkey)
-    .bind(cursor)
-    .bind(limit)
-    .fetch_all(pool)
+    .bind(rounds)
+    .fetch_one(pool)
     .await?;
 
-    Ok(rounds)
+    Ok(row)
 }
 
-pub async fn insert_deployment(pool: &Pool<Sqlite>, d: &CreateDeployment) -> Result<(), sqlx::Error> {
-    sqlx::query(
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SquarePopularity {
+    pub square_id: i64,
+    pub deployments: i64,
+    pub total_deployed: i64,
+    pub wins: i64,
+}
+
+/// Deployment count, SOL deployed and wins per square over the last `rounds` rounds.
+/// Always 25 entries, indexed by square.
+pub async fn get_square_popularity(pool: &Pool<Sqlite>, rounds: i64) -> Result<Vec<SquarePopularity>, sqlx::Error> {
+    let deployed = sqlx::query_as::<_, (i64, i64, i64)>(
         r#"
-        INSERT INTO deployments (
-            round_id, pubkey, square_id, amount, sol_earned, ore_earned, unclaimed_ore, created_at
-        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
-        ON CONFLICT(round_id, pubkey, square_id) DO UPDATE SET
-            amount        = excluded.amount,
-            sol_earned    = excluded.sol_earned,
-            ore_earned    = excluded.ore_earned,
-            unclaimed_ore = excluded.unclaimed_ore,
-            created_at    = excluded.created_at
+        WITH last_n AS (
+          SELECT id FROM rounds ORDER BY id DESC LIMIT ?
+        )
+        SELECT d.square_id, COUNT(*), COALESCE(SUM(d.amount), 0)
+        FROM deployments d
+        JOIN last_n r ON r.id = d.round_id
+        GROUP BY d.square_id
         "#
     )
-    .bind(d.round_id)
+    .bind(rounds)
+    .fetch_all(pool)
+    .await?;
+
+    let wins = sqlx::query_as::<_, (i64, i64)>(
+        r#"
+        WITH last_n AS (
+          SELECT winning_square FROM rounds ORDER BY id DESC LIMIT ?
+        )
+        SELECT winning_square, COUNT(*)
+        FROM last_n
+        WHERE winning_square BETWEEN 0 AND 24
+        GROUP BY winning_square
+        "#
+    )
+    .bind(rounds)
+    .fetch_all(pool)
+    .await?;
+
+    let mut squares: Vec<SquarePopularity> = (0..25)
+        .map(|square_id| SquarePopularity { square_id, ..Default::default() })
+        .collect();
+    for (square_id, deployments, total_deployed) in deployed {
+        if let Some(s) = squares.get_mut(square_id as usize) {
+            s.deployments = deployments;
+            s.total_deployed = total_deployed;
+        }
+    }
+    for (square_id, count) in wins {
+        if let Some(s) = squares.get_mut(square_id as usize) {
+            s.wins = count;
+        }
+    }
+
+    Ok(squares)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoundSquareDeployed {
+    pub round_id: i64,
+    pub deployed: [u64; 25],
+}
+
+/// SOL deployed on each square in each of the last `rounds` stored rounds, oldest
+/// first. Rounds without deployments are included with an all-zero board.
+pub async fn get_square_heatmap(pool: &Pool<Sqlite>, rounds: i64) -> Result<Vec<RoundSquareDeployed>, sqlx::Error> {
+    let cells = sqlx::query_as::<_, (i64, Option<i64>, i64)>(
+        r#"
+        WITH last_n AS (
+          SELECT id FROM rounds ORDER BY id DESC LIMIT ?
+        )
+        SELECT r.id, d.square_id, COALESCE(SUM(d.amount), 0)
+        FROM last_n r
+        LEFT JOIN deployments d ON d.round_id = r.id
+        GROUP BY r.id, d.square_id
+        ORDER BY r.id ASC
+        "#
+    )
+    .bind(rounds)
+    .fetch_all(pool)
+    .await?;
+
+    let mut heatmap: Vec<RoundSquareDeployed> = Vec::new();
+    for (round_id, square_id, amount) in cells {
+        if heatmap.last().is_none_or(|r| r.round_id != round_id) {
+            heatmap.push(RoundSquareDeployed { round_id, deployed: [0; 25] });
+        }
+        let Some(square_id) = square_id.filter(|s| (0..25).contains(s)) else {
+            continue;
+        };
+        if let Some(row) = heatmap.last_mut() {
+            row.deployed[square_id as usize] = amount as u64;
+        }
+    }
+
+    Ok(heatmap)
+}
+
+/// SOL deployed and number of deployments on each square of a stored round.
+pub async fn get_round_squares(pool: &Pool<Sqlite>, round_id: i64) -> Result<([u64; 25], [u64; 25]), sqlx::Error> {
+    let squares = sqlx::query_as::<_, (i64, i64, i64)>(
+        r#"
+        SELECT square_id, SUM(amount), COUNT(*)
+        FROM deployments
+        WHERE round_id = ?
+        GROUP BY square_id
+        "#
+    )
+    .bind(round_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut deployed = [0; 25];
+    let mut count = [0; 25];
+    for (square_id, amount, n) in squares {
+        if (0..25).contains(&square_id) {
+            deployed[square_id as usize] = amount as u64;
+            count[square_id as usize] = n as u64;
+        }
+    }
+    Ok((deployed, count))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct NetFlow {
+    pub rounds: i64,
+    pub from_round_id: Option<i64>,
+    pub to_round_id: Option<i64>,
+    pub total_deployed: i64,
+    pub total_winnings: i64,
+    /// total_deployed minus total_winnings.
+    pub net_flow: i64,
+    pub total_motherlode: i64,
+    pub total_admin_fee: i64,
+    /// ORE credited to deployments, top miner rewards plus motherlode shares.
+    pub total_ore_emitted: i64,
+}
+
+/// Game-wide flows over the last `rounds` rounds, or all rounds when None.
+pub async fn get_net_flow(pool: &Pool<Sqlite>, rounds: Option<i64>) -> Result<NetFlow, sqlx::Error> {
+    let row = sqlx::query_as::<_, NetFlow>(
+        r#"
+        WITH last_n AS (
+          SELECT id, total_deployed, total_winnings, motherlode FROM rounds ORDER BY id DESC LIMIT ?
+        ),
+        r AS (
+          SELECT
+            COUNT(*) AS rounds,
+            MIN(id) AS from_round_id,
+            MAX(id) AS to_round_id,
+            COALESCE(SUM(total_deployed), 0) AS total_deployed,
+            COALESCE(SUM(total_winnings), 0) AS total_winnings,
+            COALESCE(SUM(motherlode), 0) AS total_motherlode
+          FROM last_n
+        ),
+        d AS (
+          SELECT
+            COALESCE(SUM(d.admin_fee), 0) AS total_admin_fee,
+            COALESCE(SUM(d.ore_earned), 0) AS total_ore_emitted
+          FROM deployments d
+          JOIN last_n n ON n.id = d.round_id
+        )
+        SELECT
+          r.rounds, r.from_round_id, r.to_round_id, r.total_deployed, r.total_winnings,
+          r.total_deployed - r.total_winnings AS net_flow,
+          r.total_motherlode, d.total_admin_fee, d.total_ore_emitted
+        FROM r, d
+        "#
+    )
+    // a negative LIMIT is no limit in sqlite
+    .bind(rounds.unwrap_or(-1))
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct DeployPercentiles {
+    pub deployments: i64,
+    pub mean: f64,
+    pub p50: i64,
+    pub p90: i64,
+    pub p99: i64,
+}
+
+/// Distribution of deployment amounts over the last `rounds` rounds. Nearest-rank
+/// percentiles: the value at row ceil(p * n) of the sorted amounts.
+pub async fn get_deploy_percentiles(pool: &Pool<Sqlite>, rounds: i64) -> Result<DeployPercentiles, sqlx::Error> {
+    let row = sqlx::query_as::<_, DeployPercentiles>(
+        r#"
+        WITH last_n AS (
+          SELECT id FROM rounds ORDER BY id DESC LIMIT ?
+        ),
+        amounts AS (
+          SELECT
+            d.amount,
+            ROW_NUMBER() OVER (ORDER BY d.amount) AS rn,
+            COUNT(*) OVER () AS n
+          FROM deployments d
+          JOIN last_n r ON r.id = d.round_id
+        )
+        SELECT
+          COUNT(*) AS deployments,
+          COALESCE(AVG(amount), 0.0) AS mean,
+          -- (n * p + 99) / 100 is ceil(p% of n) in integer math
+          COALESCE(MAX(CASE WHEN rn = (n * 50 + 99) / 100 THEN amount END), 0) AS p50,
+          COALESCE(MAX(CASE WHEN rn = (n * 90 + 99) / 100 THEN amount END), 0) AS p90,
+          COALESCE(MAX(CASE WHEN rn = (n * 99 + 99) / 100 THEN amount END), 0) AS p99
+        FROM amounts
+        "#
+    )
+    .bind(rounds)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct RoundActiveMiners {
+    pub round_id: i64,
+    pub active_miners: i64,
+}
+
+/// Distinct deployers in each of the last `rounds` rounds, oldest first.
+pub async fn get_active_miners_per_round(pool: &Pool<Sqlite>, rounds: i64) -> Result<Vec<RoundActiveMiners>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, RoundActiveMiners>(
+        r#"
+        WITH last_n AS (
+          SELECT id FROM rounds ORDER BY id DESC LIMIT ?
+        )
+        SELECT
+          r.id AS round_id,
+          (
+            SELECT COUNT(DISTINCT d.pubkey)
+            FROM deployments d
+            WHERE d.round_id = r.id
+          ) AS active_miners
+        FROM last_n r
+        ORDER BY r.id ASC
+        "#
+    )
+    .bind(rounds)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct InactiveMiner {
+    pub pubkey: String,
+    pub last_round: i64,
+    pub rounds_inactive: i64,
+    pub net_sol_change: i64,
+}
+
+/// Miners whose last played round is more than `rounds` behind `current_round`,
+/// longest inactive first.
+pub async fn get_inactive_miners(
+    pool: &Pool<Sqlite>,
+    current_round: i64,
+    rounds: i64,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<InactiveMiner>, sqlx::Error> {
+    sqlx::query_as::<_, InactiveMiner>(
+        r#"
+        SELECT
+          s.pubkey,
+          s.last_round,
+          ?1 - s.last_round AS rounds_inactive,
+          t.net_sol_change
+        FROM (
+          SELECT pubkey, MAX(round_id) AS last_round
+          FROM miner_round_stats
+          GROUP BY pubkey
+        ) s
+        JOIN miner_totals t ON t.pubkey = s.pubkey
+        WHERE s.last_round < ?1 - ?2
+        ORDER BY rounds_inactive DESC, s.pubkey ASC
+        LIMIT ?3 OFFSET ?4
+        "#
+    )
+    .bind(current_round)
+    .bind(rounds)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct NewMiner {
+    pub pubkey: String,
+    pub first_round: i64,
+    pub rounds_played: i64,
+    pub total_ore_earned: i64,
+    pub net_sol_change: i64,
+}
+
+/// Miners whose first deployment is within the last `rounds` stored rounds, newest
+/// first. Totals come from miner_totals, the same source as /miners/inactive.
+pub async fn get_new_miners(
+    pool: &Pool<Sqlite>,
+    rounds: i64,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<NewMiner>, sqlx::Error> {
+    sqlx::query_as::<_, NewMiner>(
+        r#"
+        SELECT
+          s.pubkey,
+          s.first_round,
+          t.rounds_played,
+          t.total_ore_earned,
+          t.net_sol_change
+        FROM (
+          SELECT pubkey, MIN(round_id) AS first_round
+          FROM miner_round_stats
+          GROUP BY pubkey
+        ) s
+        JOIN miner_totals t ON t.pubkey = s.pubkey
+        WHERE s.first_round > (SELECT COALESCE(MAX(id), 0) FROM rounds) - ?1
+        ORDER BY s.first_round DESC, s.pubkey ASC
+        LIMIT ?2 OFFSET ?3
+        "#
+    )
+    .bind(rounds)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct UniqueMiners {
+    /// Distinct pubkeys that ever deployed.
+    pub deployments: i64,
+    /// Distinct pubkeys that ever appeared in a miner snapshot.
+    pub snapshots: i64,
+    pub first_round: Option<i64>,
+    pub last_round: Option<i64>,
+}
+
+/// Full scans of deployments and miner_snapshots, callers should cache the result.
+pub async fn get_unique_miners(pool: &Pool<Sqlite>) -> Result<UniqueMiners, sqlx::Error> {
+    let row = sqlx::query_as::<_, UniqueMiners>(
+        r#"
+        SELECT
+          (SELECT COUNT(DISTINCT pubkey) FROM deployments)     AS deployments,
+          (SELECT COUNT(DISTINCT pubkey) FROM miner_snapshots) AS snapshots,
+          (SELECT MIN(round_id) FROM deployments)              AS first_round,
+          (SELECT MAX(round_id) FROM deployments)              AS last_round
+        "#
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row)
+}
+
+pub async fn get_max_round_id(pool: &Pool<Sqlite>) -> Result<Option<i64>, sqlx::Error> {
+    let max_id: Option<i64> = sqlx::query_scalar(
+        r#"
+        SELECT MAX(id) FROM rounds
+        "#
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(max_id)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct RoundGap {
+    pub start_id: i64,
+    pub end_id: i64,
+}
+
+/// Missing id ranges (inclusive) between MIN(id) and MAX(id) of rounds.
+pub async fn get_round_gaps(pool: &Pool<Sqlite>) -> Result<Vec<RoundGap>, sqlx::Error> {
+    let gaps = sqlx::query_as::<_, RoundGap>(
+        r#"
+        SELECT id + 1 AS start_id, next_id - 1 AS end_id
+        FROM (
+            SELECT id, LEAD(id) OVER (ORDER BY id) AS next_id
+            FROM rounds
+        )
+        WHERE next_id - id > 1
+        ORDER BY start_id
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(gaps)
+}
+
+pub async fn get_round_by_id(pool: &Pool<Sqlite>, round_id: i64) -> Result<Vec<RoundRow>, sqlx::Error> {
+    let rounds = sqlx::query_as::<_, RoundRow>(
+        r#"
+        SELECT * FROM rounds
+        WHERE id = ?
+        "#
+    )
+    .bind(round_id)
+    .fetch_all(pool)
+    .await?;
+
+    return Ok(rounds)
+}
+
+pub async fn get_rounds(pool: &Pool<Sqlite>, limit: i64, offset: i64, ml: Option<bool>) -> Result<Vec<RoundRow>, sqlx::Error> {
+    if let Some(ml) = ml {
+        if ml {
+            let rounds = sqlx::query_as::<_, RoundRow>(
+                r#"
+                SELECT * FROM rounds
+                WHERE motherlode > 0
+                ORDER BY id DESC
+                LIMIT ? OFFSET ?
+                "#
+            )
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(pool)
+            .await?;
+
+            return Ok(rounds)
+        }
+    }
+
+    let rounds = sqlx::query_as::<_, RoundRow>(
+        r#"
+        SELECT * FROM rounds
+        ORDER BY id DESC
+        LIMIT ? OFFSET ?
+        "#
+    )
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rounds)
+}
+
+pub async fn get_rounds_via_cursor(pool: &Pool<Sqlite>, limit: i64, cursor: i64, ml: Option<bool>) -> Result<Vec<RoundRow>, sqlx::Error> {
+    if let Some(ml) = ml {
+        if ml {
+            let rounds = sqlx::query_as::<_, RoundRow>(
+                r#"
+                SELECT * FROM rounds
+                WHERE motherlode > 0
+                AND id < ?
+                ORDER BY id DESC
+                LIMIT ?
+                "#
+            )
+            .bind(cursor)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?;
+
+            return Ok(rounds)
+        }
+    }
+
+    let rounds = sqlx::query_as::<_, RoundRow>(
+        r#"
+        SELECT * FROM rounds
+        WHERE id < ?
+        ORDER BY id DESC
+        LIMIT ?
+        "#
+    )
+    .bind(cursor)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rounds)
+}
+
+pub async fn get_miner_rounds(pool: &Pool<Sqlite>, pubkey: String, limit: i64, offset: i64) -> Result<Vec<RoundRow>, sqlx::Error> {
+    let rounds = sqlx::query_as::<_, RoundRow>(
+        r#"
+        SELECT * FROM rounds r
+        WHERE EXISTS (
+          SELECT 1 FROM deployments d
+          WHERE d.round_id = r.id
+            AND d.pubkey   = ?
+        )
+        ORDER BY r.created_at DESC
+        LIMIT ? OFFSET ?
+        "#
+    )
+    .bind(pubkey)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rounds)
+}
+
+pub async fn get_miner_rounds_via_cursor(pool: &Pool<Sqlite>, pubkey: String, limit: i64, cursor: i64) -> Result<Vec<RoundRow>, sqlx::Error> {
+    let rounds = sqlx::query_as::<_, RoundRow>(
+        r#"
+        SELECT * FROM rounds r
+        WHERE EXISTS (
+          SELECT 1 FROM deployments d
+          WHERE d.round_id = r.id
+            AND d.pubkey   = ?
+        )
+        AND r.id < ?
+        ORDER BY r.id DESC
+        LIMIT ?
+        "#
+    )
+    .bind(pubkey)
+    .bind(cursor)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rounds)
+}
+
+pub async fn insert_deployment(pool: &Pool<Sqlite>, d: &CreateDeployment) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO deployments (
+            round_id, pubkey, square_id, amount, sol_earned, ore_earned, unclaimed_ore, cumulative, admin_fee, created_at
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(round_id, pubkey, square_id) DO UPDATE SET
+            amount        = excluded.amount,
+            sol_earned    = excluded.sol_earned,
+            ore_earned    = excluded.ore_earned,
+            unclaimed_ore = excluded.unclaimed_ore,
+            cumulative    = excluded.cumulative,
+            admin_fee     = excluded.admin_fee,
+            created_at    = excluded.created_at
+        "#
+    )
+    .bind(d.round_id)
     .bind(&d.pubkey)
     .bind(d.square_id)
     .bind(d.amount)
     .bind(d.sol_earned)
     .bind(d.ore_earned)
     .bind(d.unclaimed_ore)
+    .bind(d.cumulative)
+    .bind(d.admin_fee)
     .bind(&d.created_at)
     .execute(pool)
     .await?;
@@ -376,16 +1248,58 @@ pub async fn insert_deployment(pool: &Pool<Sqlite>, d: &CreateDeployment) -> Res
 
 pub async fn insert_deployments(
     pool: &Pool<Sqlite>,
+    db_2: &Option<Pool<Sqlite>>,
     rows: &[CreateDeployment],
 ) -> Result<(), sqlx::Error> {
-    const CHUNK_SIZE: usize = 100;
+    retry_busy("deployments", || insert_deployments_into(pool, rows)).await?;
+    let rows = rows.to_vec();
+    replicate(db_2, "deployments", |p| async move { insert_deployments_into(&p, &rows).await });
+    Ok(())
+}
 
+/// SQLite's default bind parameter limit (SQLITE_MAX_VARIABLE_NUMBER) since 3.32.
+const SQLITE_MAX_VARIABLE_NUMBER: usize = 32766;
+
+/// Rows per multi-row INSERT. Every row binds one parameter per column, so a chunk
+/// binds `chunk_size * columns` parameters and must stay under SQLITE_MAX_VARIABLE_NUMBER.
+/// Reads `env_key`, falls back to `default`, and clamps to the largest safe size.
+fn insert_chunk_size(env_key: &str, default: usize, columns: usize) -> usize {
+    let max_safe = SQLITE_MAX_VARIABLE_NUMBER / columns;
+    let size = std::env::var(env_key)
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(default);
+    if size > max_safe {
+        tracing::warn!("{} of {} exceeds sqlite bind limit, using {}", env_key, size, max_safe);
+    }
+    size.clamp(1, max_safe)
+}
+
+/// Columns bound per deployment row in `insert_deployments_into`.
+const DEPLOYMENT_COLUMNS: usize = 10;
+
+/// Columns bound per snapshot row in `insert_miner_snapshots_into`.
+const SNAPSHOT_COLUMNS: usize = 6;
+
+async fn insert_deployments_into(
+    pool: &Pool<Sqlite>,
+    rows: &[CreateDeployment],
+) -> Result<(), sqlx::Error> {
+    let chunk_size = insert_chunk_size("DEPLOYMENT_INSERT_CHUNK_SIZE", 100, DEPLOYMENT_COLUMNS);
+    insert_deployment_chunks(pool, rows, chunk_size).await
+}
+
+async fn insert_deployment_chunks(
+    pool: &Pool<Sqlite>,
+    rows: &[CreateDeployment],
+    chunk_size: usize,
+) -> Result<(), sqlx::Error> {
     let mut tx = pool.begin().await?;
 
-    for chunk in rows.chunks(CHUNK_SIZE) {
+    for chunk in rows.chunks(chunk_size) {
         let mut qb = QueryBuilder::<Sqlite>::new(
             "INSERT INTO deployments (
-                round_id, pubkey, square_id, amount, sol_earned, ore_earned, unclaimed_ore, created_at
+                round_id, pubkey, square_id, amount, sol_earned, ore_earned, unclaimed_ore, cumulative, admin_fee, created_at
             ) ",
         );
 
@@ -397,9 +1311,22 @@ pub async fn insert_deployments(
                 .push_bind(d.sol_earned)
                 .push_bind(d.ore_earned)
                 .push_bind(d.unclaimed_ore)
+                .push_bind(d.cumulative)
+                .push_bind(d.admin_fee)
                 .push_bind(&d.created_at);
         });
 
+        qb.push(
+            " ON CONFLICT(round_id, pubkey, square_id) DO UPDATE SET
+                amount        = excluded.amount,
+                sol_earned    = excluded.sol_earned,
+                ore_earned    = excluded.ore_earned,
+                unclaimed_ore = excluded.unclaimed_ore,
+                cumulative    = excluded.cumulative,
+                admin_fee     = excluded.admin_fee,
+                created_at    = excluded.created_at",
+        );
+
         qb.build().execute(&mut *tx).await?;
     }
 
@@ -418,6 +1345,75 @@ pub struct GetDeployment {
     pub ore_earned: i64,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct SquareDeployment {
+    pub pubkey: String,
+    pub amount: i64,
+    /// None for deployments stored before cumulative was recorded.
+    pub cumulative: Option<i64>,
+}
+
+/// Every deployment on one square of a round, in deployment order.
+pub async fn get_square_deployments(
+    pool: &Pool<Sqlite>,
+    round_id: i64,
+    square_id: i64,
+) -> Result<Vec<SquareDeployment>, sqlx::Error> {
+    let deployments = sqlx::query_as::<_, SquareDeployment>(
+        r#"
+        SELECT pubkey, amount, cumulative
+        FROM deployments
+        WHERE round_id = ? AND square_id = ?
+        ORDER BY cumulative
+        "#
+    )
+    .bind(round_id)
+    .bind(square_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(deployments)
+}
+
+/// Aggregates of a round's stored deployments, split by the winning square.
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct RoundDeploymentSums {
+    pub deployments: i64,
+    pub total_amount: i64,
+    pub winning_amount: i64,
+    pub winning_count: i64,
+    pub winning_sol_earned: i64,
+    pub winning_admin_fee: i64,
+    pub losing_sol_earned: i64,
+}
+
+pub async fn get_round_deployment_sums(
+    pool: &Pool<Sqlite>,
+    round_id: i64,
+    winning_square: i64,
+) -> Result<RoundDeploymentSums, sqlx::Error> {
+    let sums = sqlx::query_as::<_, RoundDeploymentSums>(
+        r#"
+        SELECT
+            COUNT(*) AS deployments,
+            COALESCE(SUM(amount), 0) AS total_amount,
+            COALESCE(SUM(CASE WHEN square_id = ?1 THEN amount END), 0) AS winning_amount,
+            COUNT(CASE WHEN square_id = ?1 THEN 1 END) AS winning_count,
+            COALESCE(SUM(CASE WHEN square_id = ?1 THEN sol_earned END), 0) AS winning_sol_earned,
+            COALESCE(SUM(CASE WHEN square_id = ?1 THEN admin_fee END), 0) AS winning_admin_fee,
+            COALESCE(SUM(CASE WHEN square_id <> ?1 THEN sol_earned END), 0) AS losing_sol_earned
+        FROM deployments
+        WHERE round_id = ?2
+        "#
+    )
+    .bind(winning_square)
+    .bind(round_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(sums)
+}
+
 pub async fn get_deployments_by_round(
     pool: &Pool<Sqlite>,
     round_id: i64,
@@ -439,17 +1435,59 @@ pub async fn get_deployments_by_round(
     Ok(deployments)
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct RoundWinner {
+    pub pubkey: String,
+    pub ore_earned: i64,
+    pub sol_earned: i64,
+}
+
+/// Miners that earned ORE in the round, summed over their deployments, most ORE first.
+pub async fn get_round_winners(
+    pool: &Pool<Sqlite>,
+    round_id: i64,
+) -> Result<Vec<RoundWinner>, sqlx::Error> {
+    sqlx::query_as::<_, RoundWinner>(
+        r#"
+        SELECT
+            pubkey,
+            SUM(ore_earned) AS ore_earned,
+            SUM(sol_earned) AS sol_earned
+        FROM deployments
+        WHERE round_id = ?
+        GROUP BY pubkey
+        HAVING SUM(ore_earned) > 0
+        ORDER BY ore_earned DESC, pubkey ASC
+        "#
+    )
+    .bind(round_id)
+    .fetch_all(pool)
+    .await
+}
+
 pub async fn insert_miner_snapshots(
+    pool: &Pool<Sqlite>,
+    db_2: &Option<Pool<Sqlite>>,
+    rows: &[CreateMinerSnapshot],
+) -> Result<(), sqlx::Error> {
+    retry_busy("miner snapshots", || insert_miner_snapshots_into(pool, rows)).await?;
+    let rows = rows.to_vec();
+    replicate(db_2, "miner snapshots", |p| async move { insert_miner_snapshots_into(&p, &rows).await });
+    Ok(())
+}
+
+async fn insert_miner_snapshots_into(
     pool: &Pool<Sqlite>,
     rows: &[CreateMinerSnapshot],
 ) -> Result<(), sqlx::Error> {
     let n = Instant::now();
     tracing::info!("Inserting snapshots ({} rows)", rows.len());
-    const CHUNK_SIZE: usize = 150;
+    let chunk_size = insert_chunk_size("SNAPSHOT_INSERT_CHUNK_SIZE", 150, SNAPSHOT_COLUMNS);
 
+    // one transaction for all chunks so the whole snapshot is a single WAL commit
     let mut tx = pool.begin().await?;
 
-    for chunk in rows.chunks(CHUNK_SIZE) {
+    for chunk in rows.chunks(chunk_size) {
         let mut qb = QueryBuilder::<Sqlite>::new(
             r#"
             INSERT INTO miner_snapshots (
@@ -476,31 +1514,432 @@ pub async fn insert_miner_snapshots(
 }
 
 
-pub async fn get_miner_snapshots(
+#[derive(Debug, Clone, FromRow)]
+pub struct DbCurrentMiner {
+    pub pubkey: String,
+    pub deployed: String,
+    pub total_deployed: i64,
+    pub cumulative: String,
+    pub checkpoint_fee: i64,
+    pub checkpoint_id: i64,
+    pub last_claim_ore_at: i64,
+    pub last_claim_sol_at: i64,
+    pub rewards_sol: i64,
+    pub rewards_ore: i64,
+    pub refined_ore: i64,
+    pub round_id: i64,
+    pub lifetime_rewards_sol: i64,
+    pub lifetime_rewards_ore: i64,
+    pub updated_at: i64,
+}
+
+impl From<DbCurrentMiner> for AppMiner {
+    fn from(m: DbCurrentMiner) -> Self {
+        AppMiner {
+            authority: m.pubkey,
+            deployed: serde_json::from_str(&m.deployed).unwrap_or([0; 25]),
+            total_deployed: m.total_deployed as u64,
+            cumulative: serde_json::from_str(&m.cumulative).unwrap_or([0; 25]),
+            checkpoint_fee: m.checkpoint_fee as u64,
+            checkpoint_id: m.checkpoint_id as u64,
+            last_claim_ore_at: m.last_claim_ore_at,
+            last_claim_sol_at: m.last_claim_sol_at,
+            rewards_sol: m.rewards_sol as u64,
+            rewards_ore: m.rewards_ore as u64,
+            refined_ore: m.refined_ore as u64,
+            round_id: m.round_id as u64,
+            lifetime_rewards_sol: m.lifetime_rewards_sol as u64,
+            lifetime_rewards_ore: m.lifetime_rewards_ore as u64,
+        }
+    }
+}
+
+/// Columns bound per row in `upsert_current_miners`.
+const CURRENT_MINER_COLUMNS: usize = 15;
+
+/// Replaces miners_current with the given snapshot. Miners missing from it are
+/// removed so the table mirrors the in-memory list.
+pub async fn upsert_current_miners(pool: &Pool<Sqlite>, miners: &[AppMiner]) -> Result<(), sqlx::Error> {
+    let chunk_size = insert_chunk_size("MINERS_CURRENT_CHUNK_SIZE", 500, CURRENT_MINER_COLUMNS);
+    let now = chrono::Utc::now().timestamp();
+
+    let mut tx = pool.begin().await?;
+
+    for chunk in miners.chunks(chunk_size) {
+        let mut qb = QueryBuilder::<Sqlite>::new(
+            r#"
+            INSERT INTO miners_current (
+                pubkey, deployed, total_deployed, cumulative, checkpoint_fee, checkpoint_id,
+                last_claim_ore_at, last_claim_sol_at, rewards_sol, rewards_ore, refined_ore,
+                round_id, lifetime_rewards_sol, lifetime_rewards_ore, updated_at
+            )
+            "#,
+        );
+
+        qb.push_values(chunk, |mut b, m| {
+            b.push_bind(m.authority.clone())
+                .push_bind(serde_json::to_string(&m.deployed).unwrap_or_default())
+                .push_bind(m.total_deployed as i64)
+                .push_bind(serde_json::to_string(&m.cumulative).unwrap_or_default())
+                .push_bind(m.checkpoint_fee as i64)
+                .push_bind(m.checkpoint_id as i64)
+                .push_bind(m.last_claim_ore_at)
+                .push_bind(m.last_claim_sol_at)
+                .push_bind(m.rewards_sol as i64)
+                .push_bind(m.rewards_ore as i64)
+                .push_bind(m.refined_ore as i64)
+                .push_bind(m.round_id as i64)
+                .push_bind(m.lifetime_rewards_sol as i64)
+                .push_bind(m.lifetime_rewards_ore as i64)
+                .push_bind(now);
+        });
+
+        qb.push(
+            r#"
+            ON CONFLICT(pubkey) DO UPDATE SET
+                deployed             = excluded.deployed,
+                total_deployed       = excluded.total_deployed,
+                cumulative           = excluded.cumulative,
+                checkpoint_fee       = excluded.checkpoint_fee,
+                checkpoint_id        = excluded.checkpoint_id,
+                last_claim_ore_at    = excluded.last_claim_ore_at,
+                last_claim_sol_at    = excluded.last_claim_sol_at,
+                rewards_sol          = excluded.rewards_sol,
+                rewards_ore          = excluded.rewards_ore,
+                refined_ore          = excluded.refined_ore,
+                round_id             = excluded.round_id,
+                lifetime_rewards_sol = excluded.lifetime_rewards_sol,
+                lifetime_rewards_ore = excluded.lifetime_rewards_ore,
+                updated_at           = excluded.updated_at
+            "#,
+        );
+
+        qb.build().execute(&mut *tx).await?;
+    }
+
+    sqlx::query("DELETE FROM miners_current WHERE updated_at < ?")
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Sort orders accepted by `get_current_miners`, all descending.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurrentMinerOrder {
+    UnclaimedSol,
+    UnclaimedOre,
+    RefinedOre,
+    TotalDeployed,
+    RoundId,
+}
+
+impl CurrentMinerOrder {
+    fn column(&self) -> &'static str {
+        match self {
+            CurrentMinerOrder::UnclaimedSol => "rewards_sol",
+            CurrentMinerOrder::UnclaimedOre => "rewards_ore",
+            CurrentMinerOrder::RefinedOre => "refined_ore",
+            CurrentMinerOrder::TotalDeployed => "total_deployed",
+            CurrentMinerOrder::RoundId => "round_id",
+        }
+    }
+}
+
+/// Optional lower bounds for `get_current_miners`, in base units.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CurrentMinerFilter {
+    pub min_rewards_ore: Option<i64>,
+    pub min_rewards_sol: Option<i64>,
+    /// Only miners whose last played round is this one.
+    pub round_id: Option<i64>,
+}
+
+pub async fn get_current_miners(
+    pool: &Pool<Sqlite>,
+    filter: CurrentMinerFilter,
+    order: Option<CurrentMinerOrder>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<AppMiner>, sqlx::Error> {
+    let mut qb = QueryBuilder::<Sqlite>::new("SELECT * FROM miners_current WHERE 1 = 1");
+    if let Some(min) = filter.min_rewards_ore {
+        qb.push(" AND rewards_ore >= ");
+        qb.push_bind(min);
+    }
+    if let Some(min) = filter.min_rewards_sol {
+        qb.push(" AND rewards_sol >= ");
+        qb.push_bind(min);
+    }
+    if let Some(round_id) = filter.round_id {
+        qb.push(" AND round_id = ");
+        qb.push_bind(round_id);
+    }
+    match order {
+        Some(o) => qb.push(format!(" ORDER BY {} DESC, pubkey", o.column())),
+        None => qb.push(" ORDER BY rowid"),
+    };
+    qb.push(" LIMIT ");
+    qb.push_bind(limit);
+    qb.push(" OFFSET ");
+    qb.push_bind(offset);
+
+    let rows = qb.build_query_as::<DbCurrentMiner>()
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(AppMiner::from).collect())
+}
+
+pub async fn get_miner_snapshots(
+    pool: &Pool<Sqlite>,
+    pubkey: String,
+    limit: i64,
+    offset: i64,
+    range: Option<(i64, i64)>,
+) -> Result<Vec<DbMinerSnapshot>, sqlx::Error> {
+    if let Some((from, to)) = range {
+        let miner_data = sqlx::query_as::<_, DbMinerSnapshot>(
+            r#"
+            SELECT
+                id, pubkey, unclaimed_ore, refined_ore, lifetime_sol, lifetime_ore, created_at
+            FROM miner_snapshots
+            WHERE pubkey = ?
+              AND created_at BETWEEN ? AND ?
+            ORDER BY id DESC
+            LIMIT ? OFFSET ?
+            "#
+        )
+        .bind(pubkey)
+        .bind(from)
+        .bind(to)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+
+        return Ok(miner_data)
+    }
+
+    let miner_data = sqlx::query_as::<_, DbMinerSnapshot>(
+        r#"
+        SELECT
+            id, pubkey, unclaimed_ore, refined_ore, lifetime_sol, lifetime_ore, created_at
+        FROM miner_snapshots
+        WHERE pubkey = ?
+        ORDER BY id DESC
+        LIMIT ? OFFSET ?
+        "#
+    )
+    .bind(pubkey)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(miner_data)
+}
+
+/// Keyset variant of `get_miner_snapshots`: snapshots with id below `cursor`, newest first.
+pub async fn get_miner_snapshots_via_cursor(
     pool: &Pool<Sqlite>,
     pubkey: String,
     limit: i64,
-    offset: i64,
+    cursor: i64,
+    range: Option<(i64, i64)>,
 ) -> Result<Vec<DbMinerSnapshot>, sqlx::Error> {
+    let (from, to) = range.unwrap_or((i64::MIN, i64::MAX));
     let miner_data = sqlx::query_as::<_, DbMinerSnapshot>(
         r#"
         SELECT
             id, pubkey, unclaimed_ore, refined_ore, lifetime_sol, lifetime_ore, created_at
         FROM miner_snapshots
         WHERE pubkey = ?
+          AND id < ?
+          AND created_at BETWEEN ? AND ?
         ORDER BY id DESC
-        LIMIT ? OFFSET ?
+        LIMIT ?
         "#
     )
     .bind(pubkey)
+    .bind(cursor)
+    .bind(from)
+    .bind(to)
     .bind(limit)
-    .bind(offset)
     .fetch_all(pool)
     .await?;
 
     Ok(miner_data)
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, FromRow)]
+pub struct SnapshotSeriesPoint {
+    pub bucket_ts: i64,
+    pub unclaimed_ore: i64,
+    pub refined_ore: i64,
+    pub lifetime_sol: i64,
+    pub lifetime_ore: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SeriesBucket {
+    Hour,
+    Day,
+}
+
+/// One point per bucket, taken from the last snapshot in that bucket.
+pub async fn get_miner_snapshot_series(
+    pool: &Pool<Sqlite>,
+    pubkey: String,
+    bucket: SeriesBucket,
+) -> Result<Vec<SnapshotSeriesPoint>, sqlx::Error> {
+    let bucket_fmt = match bucket {
+        SeriesBucket::Hour => "%Y-%m-%d %H:00:00",
+        SeriesBucket::Day => "%Y-%m-%d 00:00:00",
+    };
+
+    // sqlite returns the bare columns from the row that matched MAX(created_at)
+    let points = sqlx::query_as::<_, SnapshotSeriesPoint>(
+        r#"
+        SELECT
+            CAST(strftime('%s', strftime(?, created_at, 'unixepoch')) AS INTEGER) AS bucket_ts,
+            MAX(created_at) AS last_created_at,
+            unclaimed_ore, refined_ore, lifetime_sol, lifetime_ore
+        FROM miner_snapshots
+        WHERE pubkey = ?
+        GROUP BY bucket_ts
+        ORDER BY bucket_ts
+        "#
+    )
+    .bind(bucket_fmt)
+    .bind(pubkey)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(points)
+}
+
+/// Where the poller left off, see the poller_state migration.
+#[derive(Debug, Clone, Copy, FromRow)]
+pub struct PollerState {
+    pub snapshot_round_id: i64,
+    pub snapshot_completed: bool,
+}
+
+pub async fn get_poller_state(pool: &Pool<Sqlite>) -> Result<Option<PollerState>, sqlx::Error> {
+    sqlx::query_as::<_, PollerState>("SELECT snapshot_round_id, snapshot_completed FROM poller_state WHERE id = 1")
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn save_poller_state(pool: &Pool<Sqlite>, state: &PollerState) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO poller_state (id, snapshot_round_id, snapshot_completed, updated_at)
+        VALUES (1, ?, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            snapshot_round_id  = excluded.snapshot_round_id,
+            snapshot_completed = excluded.snapshot_completed,
+            updated_at         = excluded.updated_at
+        "#
+    )
+    .bind(state.snapshot_round_id)
+    .bind(state.snapshot_completed)
+    .bind(chrono::Utc::now().timestamp())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Lets SQLite refresh planner statistics for tables that changed enough to need it.
+pub async fn optimize(pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+    sqlx::query("PRAGMA optimize").execute(pool).await?;
+    Ok(())
+}
+
+/// Result row of `PRAGMA wal_checkpoint`.
+#[derive(Debug, FromRow)]
+pub struct WalCheckpoint {
+    /// 1 if the checkpoint could not finish because of a reader or writer.
+    pub busy: i64,
+    /// Frames in the wal file, -1 if the database isn't in wal mode.
+    pub log: i64,
+    /// Frames moved back into the database file.
+    pub checkpointed: i64,
+}
+
+/// Checkpoints and truncates the wal. TRUNCATE holds the write lock while it waits on
+/// readers, so the wait is capped at `busy_timeout_ms` on this connection instead of the
+/// pool's long busy timeout; a busy result just means the next run tries again.
+pub async fn wal_checkpoint(pool: &Pool<Sqlite>, busy_timeout_ms: u64) -> Result<WalCheckpoint, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    let (previous,): (i64,) = sqlx::query_as("PRAGMA busy_timeout").fetch_one(&mut *conn).await?;
+
+    sqlx::query(&format!("PRAGMA busy_timeout = {}", busy_timeout_ms)).execute(&mut *conn).await?;
+    let result = sqlx::query_as::<_, WalCheckpoint>("PRAGMA wal_checkpoint(TRUNCATE)")
+        .fetch_one(&mut *conn)
+        .await;
+    sqlx::query(&format!("PRAGMA busy_timeout = {}", previous)).execute(&mut *conn).await?;
+
+    result
+}
+
+/// Writes a consistent copy of the database to `path`, which must not exist yet.
+/// Safe while the server is writing, unlike copying the file with a wal attached.
+pub async fn vacuum_into(pool: &Pool<Sqlite>, path: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("VACUUM INTO ?").bind(path).execute(pool).await?;
+    Ok(())
+}
+
+/// Deletes up to `batch` snapshots older than `cutoff`. With `keep_daily` the last
+/// snapshot of each day per miner is kept so long-term history survives at day resolution.
+pub async fn prune_miner_snapshots(
+    pool: &Pool<Sqlite>,
+    cutoff: i64,
+    keep_daily: bool,
+    batch: i64,
+) -> Result<u64, sqlx::Error> {
+    let result = if keep_daily {
+        sqlx::query(
+            r#"
+            DELETE FROM miner_snapshots
+            WHERE id IN (
+                SELECT id FROM miner_snapshots
+                WHERE created_at < ?
+                  AND id NOT IN (
+                    SELECT MAX(id) FROM miner_snapshots
+                    WHERE created_at < ?
+                    GROUP BY pubkey, created_at / 86400
+                  )
+                LIMIT ?
+            )
+            "#
+        )
+        .bind(cutoff)
+        .bind(cutoff)
+        .bind(batch)
+        .execute(pool)
+        .await?
+    } else {
+        sqlx::query(
+            r#"
+            DELETE FROM miner_snapshots
+            WHERE id IN (
+                SELECT id FROM miner_snapshots
+                WHERE created_at < ?
+                LIMIT ?
+            )
+            "#
+        )
+        .bind(cutoff)
+        .bind(batch)
+        .execute(pool)
+        .await?
+    };
+
+    Ok(result.rows_affected())
+}
 
 pub async fn get_miner_stats(
     pool: &sqlx::SqlitePool,
@@ -530,6 +1969,87 @@ pub async fn get_miner_stats(
     Ok(row)
 }
 
+/// A miner's wins against what covering their squares would give by chance.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct MinerLuck {
+    pub rounds_played: i64,
+    pub actual_wins: i64,
+    pub expected_wins: f64,
+    /// actual / expected, None until there is something expected.
+    #[sqlx(skip)]
+    pub luck_ratio: Option<f64>,
+}
+
+/// Expected wins assume the winning square is uniform, so a round where the miner
+/// covered k squares is won with probability k/25 regardless of how much went on
+/// each. Rounds without a valid winning square are left out.
+pub async fn get_miner_luck(pool: &Pool<Sqlite>, pubkey: String) -> Result<MinerLuck, sqlx::Error> {
+    let mut luck = sqlx::query_as::<_, MinerLuck>(
+        r#"
+        SELECT
+            COUNT(*)                         AS rounds_played,
+            COALESCE(SUM(won), 0)            AS actual_wins,
+            COALESCE(SUM(squares), 0) / 25.0 AS expected_wins
+        FROM (
+            SELECT
+                COUNT(DISTINCT d.square_id)            AS squares,
+                MAX(d.square_id = r.winning_square)    AS won
+            FROM deployments d
+            JOIN rounds r ON r.id = d.round_id
+            WHERE d.pubkey = ?
+              AND d.amount > 0
+              AND r.winning_square < 25
+            GROUP BY d.round_id
+        )
+        "#
+    )
+    .bind(pubkey)
+    .fetch_one(pool)
+    .await?;
+
+    if luck.expected_wins > 0.0 {
+        luck.luck_ratio = Some(luck.actual_wins as f64 / luck.expected_wins);
+    }
+    Ok(luck)
+}
+
+pub async fn get_miner_stats_many(
+    pool: &sqlx::SqlitePool,
+    pubkeys: &[String],
+) -> anyhow::Result<Vec<MinerTotalsRow>> {
+    if pubkeys.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut qb = QueryBuilder::<Sqlite>::new(r#"
+        SELECT
+          pubkey,
+          rounds_played,
+          rounds_won,
+          total_sol_deployed,
+          total_sol_earned,
+          total_ore_earned,
+          net_sol_change,
+          CASE
+            WHEN net_sol_change > 0 THEN 'up'
+            WHEN net_sol_change < 0 THEN 'down'
+            ELSE 'flat'
+          END AS sol_balance_direction
+        FROM miner_totals
+        WHERE pubkey IN ("#);
+    let mut separated = qb.separated(", ");
+    for pubkey in pubkeys {
+        separated.push_bind(pubkey);
+    }
+    separated.push_unseparated(")");
+
+    let rows = qb.build_query_as::<MinerTotalsRow>()
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows)
+}
+
 pub async fn finalize_round_idempotent(pool: &sqlx::SqlitePool, round_id: i64) -> anyhow::Result<()> {
     let mut tx = pool.begin().await?;
 
@@ -611,6 +2131,49 @@ pub async fn finalize_round_idempotent(pool: &sqlx::SqlitePool, round_id: i64) -
     Ok(())
 }
 
+/// miner_totals rows for every pubkey that deployed in (or has stats for) the round.
+pub async fn get_miner_totals_for_round(
+    pool: &sqlx::SqlitePool,
+    round_id: i64,
+) -> anyhow::Result<Vec<MinerTotalsRow>> {
+    let rows = sqlx::query_as::<_, MinerTotalsRow>(r#"
+        SELECT
+          pubkey,
+          rounds_played,
+          rounds_won,
+          total_sol_deployed,
+          total_sol_earned,
+          total_ore_earned,
+          net_sol_change,
+          CASE
+            WHEN net_sol_change > 0 THEN 'up'
+            WHEN net_sol_change < 0 THEN 'down'
+            ELSE 'flat'
+          END AS sol_balance_direction
+        FROM miner_totals
+        WHERE pubkey IN (
+          SELECT pubkey FROM deployments WHERE round_id = ?
+          UNION
+          SELECT pubkey FROM miner_round_stats WHERE round_id = ?
+        )
+    "#)
+    .bind(round_id)
+    .bind(round_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+pub async fn get_all_time_miner_count(pool: &sqlx::SqlitePool) -> anyhow::Result<i64> {
+    let count: i64 = sqlx::query_scalar(r#"
+        SELECT COUNT(*) FROM miner_totals
+    "#)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
 
 pub async fn get_available_pubkeys(pool: &Pool<Sqlite>, limit: String) -> Result<Vec<String>, sqlx::Error> {
     Ok(vec![])
@@ -640,7 +2203,43 @@ pub async fn get_snapshot_24h_ago(
     Ok(rows.into_iter().next())
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+/// Same ±15 minute matching as `get_snapshot_24h_ago`, for many pubkeys in one query.
+pub async fn get_snapshots_24h_ago(
+    pool: &Pool<Sqlite>,
+    pubkeys: &[String],
+) -> Result<Vec<DbMinerSnapshot>, sqlx::Error> {
+    if pubkeys.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut qb = QueryBuilder::<Sqlite>::new(r#"
+        WITH target(ts) AS (SELECT strftime('%s','now','-24 hours')),
+        ranked AS (
+          SELECT
+            id, pubkey, unclaimed_ore, refined_ore, lifetime_sol, lifetime_ore, created_at,
+            ROW_NUMBER() OVER (PARTITION BY pubkey ORDER BY ABS(created_at - ts)) AS rn
+          FROM miner_snapshots, target
+          WHERE created_at BETWEEN (ts - 900) AND (ts + 900)
+            AND pubkey IN ("#);
+    let mut separated = qb.separated(", ");
+    for pubkey in pubkeys {
+        separated.push_bind(pubkey);
+    }
+    separated.push_unseparated(r#")
+        )
+        SELECT id, pubkey, unclaimed_ore, refined_ore, lifetime_sol, lifetime_ore, created_at
+        FROM ranked
+        WHERE rn = 1
+    "#);
+
+    let rows = qb.build_query_as::<DbMinerSnapshot>()
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow, SimpleObject)]
 pub struct MinerTotalsRow {
     pub pubkey: String,
     pub rounds_played: i64,
@@ -652,265 +2251,203 @@ pub struct MinerTotalsRow {
     pub sol_balance_direction: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow, SimpleObject)]
 pub struct MinerLeaderboardRow {
     pub rank: i64,
     pub pubkey: String,
     pub rounds_played: i64,
     pub rounds_won: i64,
+    #[serde(serialize_with = "u64_as_string")]
     pub total_sol_deployed: i64,
+    #[serde(serialize_with = "u64_as_string")]
     pub total_sol_earned: i64,
+    #[serde(serialize_with = "u64_as_string")]
     pub total_ore_earned: i64,
+    #[serde(serialize_with = "u64_as_string")]
     pub net_sol_change: i64,
     pub sol_balance_direction: String,
+    /// The miner's current refined ORE, lifetime regardless of the window.
+    #[serde(serialize_with = "u64_as_string")]
+    pub refined_ore: i64,
+    /// ORE kept. All-time this is total_ore_earned plus refined_ore: refining only ever
+    /// adds to a miner's ORE (it's paid out of other miners' claim fees). Refined ORE
+    /// isn't tracked per round, so a round window only counts total_ore_earned.
+    #[serde(serialize_with = "u64_as_string")]
+    pub net_ore: i64,
 }
 
-pub async fn get_miner_totals_all_time(
-    pool: &sqlx::SqlitePool,
-    limit: i64,
-    offset: i64,
-) -> anyhow::Result<Vec<MinerTotalsRow>> {
-    let rows = sqlx::query_as::<_, MinerTotalsRow>(r#"
-        WITH per_miner_round AS (
-          SELECT
-            d.pubkey,
-            d.round_id,
-            SUM(d.amount)      AS total_deployed,
-            SUM(d.sol_earned)  AS total_sol_earned,
-            SUM(d.ore_earned)  AS total_ore_earned,
-            MAX(CASE WHEN d.square_id = r.winning_square THEN 1 ELSE 0 END) AS won_round,
-            (SUM(d.sol_earned) - SUM(d.amount)) AS net_sol_round
-          FROM deployments d
-          JOIN rounds r ON r.id = d.round_id
-          GROUP BY d.pubkey, d.round_id
-        )
-        SELECT
-          pubkey,
-          COUNT(*)                                  AS rounds_played,
-          SUM(won_round)                            AS rounds_won,
-          SUM(total_deployed)                       AS total_sol_deployed,
-          SUM(total_sol_earned)                     AS total_sol_earned,
-          SUM(total_ore_earned)                     AS total_ore_earned,
-          SUM(net_sol_round)                        AS net_sol_change,
-          CASE
-            WHEN SUM(net_sol_round) > 0 THEN 'up'
-            WHEN SUM(net_sol_round) < 0 THEN 'down'
-            ELSE 'flat'
-          END AS sol_balance_direction
-        FROM per_miner_round
-        GROUP BY pubkey
-        HAVING COUNT(*) >= 100
-        ORDER BY net_sol_change DESC
-        LIMIT ? OFFSET ?;
-    "#)
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(pool)
-    .await?;
-
-    Ok(rows)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LeaderboardMetric {
+    NetSol,
+    Ore,
+    NetOre,
 }
 
-pub async fn get_leaderboard_last_n_rounds(
-    pool: &sqlx::SqlitePool,
-    n_rounds: i64,
-    limit: i64,
-    offset: i64,
-) -> anyhow::Result<Vec<MinerLeaderboardRow>> {
-    let rows = sqlx::query_as::<_, MinerLeaderboardRow>(r#"
-        WITH last_60_rounds AS (
-          SELECT id
-          FROM rounds
-          ORDER BY id DESC
-          LIMIT ?
-        ),
-        per_miner_round AS (
-          SELECT
-            d.pubkey,
-            d.round_id,
-            SUM(d.amount)      AS total_deployed,
-            SUM(d.sol_earned)  AS total_sol_earned,
-            SUM(d.ore_earned)  AS total_ore_earned,
-            MAX(CASE WHEN d.square_id = r.winning_square THEN 1 ELSE 0 END) AS won_round,
-            (SUM(d.sol_earned) - SUM(d.amount)) AS net_sol_round
-          FROM deployments d
-          JOIN rounds r ON r.id = d.round_id
-          WHERE d.round_id IN (SELECT id FROM last_60_rounds)
-          GROUP BY d.pubkey, d.round_id
-        ),
-        miner_aggs AS (
-          SELECT
-            pubkey,
-            COUNT(*)              AS rounds_played,
-            SUM(won_round)        AS rounds_won,
-            SUM(total_deployed)   AS total_sol_deployed,
-            SUM(total_sol_earned) AS total_sol_earned,
-            SUM(total_ore_earned) AS total_ore_earned,
-            SUM(net_sol_round)    AS net_sol_change
-          FROM per_miner_round
-          GROUP BY pubkey
-        )
-        SELECT
-          ROW_NUMBER() OVER (ORDER BY net_sol_change DESC) AS rank,
-          pubkey,
-          rounds_played,
-          rounds_won,
-          total_sol_deployed,
-          total_sol_earned,
-          total_ore_earned,
-          net_sol_change,
-          CASE
-            WHEN net_sol_change > 0 THEN 'up'
-            WHEN net_sol_change < 0 THEN 'down'
-            ELSE 'flat'
-          END AS sol_balance_direction
-        FROM miner_aggs
-        ORDER BY rank
-        LIMIT ? OFFSET ?;
-    "#)
-    .bind(n_rounds)
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(pool)
-    .await?;
-
-    Ok(rows)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LeaderboardWindow {
+    AllTime,
+    LastRounds(i64),
 }
 
-
-#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
-pub struct MinerOreLeaderboardRow {
-    pub rank: i64,                // ranked by total_ore_earned DESC
-    pub pubkey: String,
-    pub rounds_played: i64,
-    pub rounds_won: i64,
-    pub total_sol_deployed: i64,
-    pub total_sol_earned: i64,
-    pub total_ore_earned: i64,
-    pub net_sol_change: i64,      // still useful context even though we sort by ore
+impl LeaderboardWindow {
+    /// How the window is written in `?window=` and stored in leaderboard_ranks.
+    pub fn key(&self) -> String {
+        match self {
+            LeaderboardWindow::AllTime => "all".to_string(),
+            LeaderboardWindow::LastRounds(n) => n.to_string(),
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SolBalanceDirection {
+    Up,
+    Down,
+    Flat,
+}
 
-pub async fn get_ore_leaderboard_all_time(
-    pool: &sqlx::SqlitePool,
-    limit: i64,
-    offset: i64,
-) -> anyhow::Result<Vec<MinerOreLeaderboardRow>> {
-    let rows = sqlx::query_as::<_, MinerOreLeaderboardRow>(r#"
-        WITH per_miner_round AS (
-          SELECT
-            d.pubkey,
-            d.round_id,
-            SUM(d.amount)      AS total_deployed,
-            SUM(d.sol_earned)  AS total_sol_earned,
-            SUM(d.ore_earned)  AS total_ore_earned,
-            MAX(CASE WHEN d.square_id = r.winning_square THEN 1 ELSE 0 END) AS won_round,
-            (SUM(d.sol_earned) - SUM(d.amount)) AS net_sol_round
-          FROM deployments d
-          JOIN rounds r ON r.id = d.round_id
-          GROUP BY d.pubkey, d.round_id
-        ),
-        miner_aggs AS (
-          SELECT
-            pubkey,
-            COUNT(*)                  AS rounds_played,
-            SUM(won_round)            AS rounds_won,
-            SUM(total_deployed)       AS total_sol_deployed,
-            SUM(total_sol_earned)     AS total_sol_earned,
-            SUM(total_ore_earned)     AS total_ore_earned,
-            SUM(net_sol_round)        AS net_sol_change
-          FROM per_miner_round
-          GROUP BY pubkey
-          HAVING COUNT(*) >= 100
-        )
-        SELECT
-          ROW_NUMBER() OVER (ORDER BY total_ore_earned DESC, total_sol_earned DESC) AS rank,
-          pubkey,
-          rounds_played,
-          rounds_won,
-          total_sol_deployed,
-          total_sol_earned,
-          total_ore_earned,
-          net_sol_change
-        FROM miner_aggs
-        ORDER BY rank
-        LIMIT ? OFFSET ?;
-    "#)
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(pool)
-    .await?;
-    Ok(rows)
+impl SolBalanceDirection {
+    fn condition(&self) -> &'static str {
+        match self {
+            SolBalanceDirection::Up => " > 0",
+            SolBalanceDirection::Down => " < 0",
+            SolBalanceDirection::Flat => " = 0",
+        }
+    }
 }
 
-pub async fn get_ore_leaderboard_last_n_rounds(
-    pool: &sqlx::SqlitePool,
-    n_rounds: i64,
-    limit: i64,
-    offset: i64,
-) -> anyhow::Result<Vec<MinerOreLeaderboardRow>> {
-    let rows = sqlx::query_as::<_, MinerOreLeaderboardRow>(r#"
-        WITH last_n_rounds AS (
-          SELECT id
-          FROM rounds
-          ORDER BY id DESC
-          LIMIT ?
-        ),
-        per_miner_round AS (
+/// Pushes the `agg` CTE (one row per miner) that all leaderboard queries select from.
+/// All-time reads the pre-aggregated miner_totals, a round window aggregates miner_round_stats.
+/// Both are then joined to miners_current for refined_ore, which only counts towards net_ore all-time.
+fn push_leaderboard_agg(
+    qb: &mut QueryBuilder<'_, Sqlite>,
+    window: LeaderboardWindow,
+    min_rounds: i64,
+    direction: Option<SolBalanceDirection>,
+) {
+    let net_ore = match window {
+        LeaderboardWindow::AllTime => "b.total_ore_earned + COALESCE(mc.refined_ore, 0)",
+        LeaderboardWindow::LastRounds(_) => "b.total_ore_earned",
+    };
+    match window {
+        LeaderboardWindow::AllTime => {
+            qb.push(r#"
+        WITH base AS (
           SELECT
-            d.pubkey,
-            d.round_id,
-            SUM(d.amount)      AS total_deployed,
-            SUM(d.sol_earned)  AS total_sol_earned,
-            SUM(d.ore_earned)  AS total_ore_earned,
-            MAX(CASE WHEN d.square_id = r.winning_square THEN 1 ELSE 0 END) AS won_round,
-            (SUM(d.sol_earned) - SUM(d.amount)) AS net_sol_round
-          FROM deployments d
-          JOIN rounds r ON r.id = d.round_id
-          WHERE d.round_id IN (SELECT id FROM last_n_rounds)
-          GROUP BY d.pubkey, d.round_id
+            pubkey,
+            rounds_played,
+            rounds_won,
+            total_sol_deployed,
+            total_sol_earned,
+            total_ore_earned,
+            net_sol_change
+          FROM miner_totals
+          WHERE rounds_played >= "#);
+            qb.push_bind(min_rounds);
+            if let Some(d) = direction {
+                qb.push("\n          AND net_sol_change");
+                qb.push(d.condition());
+            }
+            qb.push("\n        )");
+        },
+        LeaderboardWindow::LastRounds(n_rounds) => {
+            qb.push(r#"
+        WITH last_n AS (
+          SELECT id FROM rounds ORDER BY id DESC LIMIT "#);
+            qb.push_bind(n_rounds.max(1));
+            qb.push(r#"
         ),
-        miner_aggs AS (
+        base AS (
           SELECT
-            pubkey,
+            s.pubkey,
             COUNT(*)                  AS rounds_played,
-            SUM(won_round)            AS rounds_won,
-            SUM(total_deployed)       AS total_sol_deployed,
-            SUM(total_sol_earned)     AS total_sol_earned,
-            SUM(total_ore_earned)     AS total_ore_earned,
-            SUM(net_sol_round)        AS net_sol_change
-          FROM per_miner_round
-          GROUP BY pubkey
-        )
-        SELECT
-          ROW_NUMBER() OVER (ORDER BY total_ore_earned DESC, total_sol_earned DESC) AS rank,
-          pubkey,
-          rounds_played,
-          rounds_won,
-          total_sol_deployed,
-          total_sol_earned,
-          total_ore_earned,
-          net_sol_change
-        FROM miner_aggs
-        ORDER BY rank
-        LIMIT ? OFFSET ?;
-    "#)
-    .bind(n_rounds.max(1))
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(pool)
-    .await?;
-    Ok(rows)
+            SUM(s.won_round)          AS rounds_won,
+            SUM(s.total_sol_deployed) AS total_sol_deployed,
+            SUM(s.total_sol_earned)   AS total_sol_earned,
+            SUM(s.total_ore_earned)   AS total_ore_earned,
+            SUM(s.net_sol_round)      AS net_sol_change
+          FROM miner_round_stats s
+          JOIN last_n r ON r.id = s.round_id
+          GROUP BY s.pubkey
+          HAVING COUNT(*) >= "#);
+            qb.push_bind(min_rounds);
+            if let Some(d) = direction {
+                qb.push("\n            AND SUM(s.net_sol_round)");
+                qb.push(d.condition());
+            }
+            qb.push("\n        )");
+        },
+    }
+    qb.push(r#",
+        agg AS (
+          SELECT
+            b.*,
+            COALESCE(mc.refined_ore, 0) AS refined_ore,
+            "#);
+    qb.push(net_ore);
+    qb.push(r#" AS net_ore
+          FROM base b
+          LEFT JOIN miners_current mc ON mc.pubkey = b.pubkey
+        )"#);
+}
+
+impl LeaderboardMetric {
+    fn order_by(&self) -> &'static str {
+        match self {
+            LeaderboardMetric::NetSol => "net_sol_change DESC",
+            LeaderboardMetric::Ore => "total_ore_earned DESC, total_sol_earned DESC",
+            LeaderboardMetric::NetOre => "net_ore DESC",
+        }
+    }
+
+    /// Condition for a row `a` ranking strictly above the target row `t`.
+    fn ranks_above(&self) -> &'static str {
+        match self {
+            LeaderboardMetric::NetSol => "a.net_sol_change > t.net_sol_change",
+            LeaderboardMetric::Ore => "a.total_ore_earned > t.total_ore_earned OR (a.total_ore_earned = t.total_ore_earned AND a.total_sol_earned > t.total_sol_earned)",
+            LeaderboardMetric::NetOre => "a.net_ore > t.net_ore",
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GiniMetric {
+    SolDeployed,
+    SolEarned,
+}
 
-pub async fn get_miner_totals_all_time_v2(
-    pool: &sqlx::SqlitePool,
+/// Per-miner totals of `metric` in the window, ascending, from the leaderboard `agg` CTE.
+pub async fn get_sorted_miner_totals(
+    pool: &Pool<Sqlite>,
+    window: LeaderboardWindow,
+    metric: GiniMetric,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let column = match metric {
+        GiniMetric::SolDeployed => "total_sol_deployed",
+        GiniMetric::SolEarned => "total_sol_earned",
+    };
+    let mut qb = QueryBuilder::<Sqlite>::new("");
+    push_leaderboard_agg(&mut qb, window, 1, None);
+    qb.push(format!("\n        SELECT {column} FROM agg ORDER BY {column}"));
+
+    let rows: Vec<(i64,)> = qb.build_query_as().fetch_all(pool).await?;
+    Ok(rows.into_iter().map(|(v,)| v).collect())
+}
+
+fn leaderboard_query(
+    metric: LeaderboardMetric,
+    window: LeaderboardWindow,
+    min_rounds: i64,
+    direction: Option<SolBalanceDirection>,
     limit: i64,
     offset: i64,
-) -> anyhow::Result<Vec<MinerTotalsRow>> {
-    let rows = sqlx::query_as::<_, MinerTotalsRow>(r#"
+) -> QueryBuilder<'static, Sqlite> {
+    let mut qb = QueryBuilder::<Sqlite>::new("");
+    push_leaderboard_agg(&mut qb, window, min_rounds, direction);
+
+    qb.push(format!(r#"
         SELECT
+          ROW_NUMBER() OVER (ORDER BY {}) AS rank,
           pubkey,
           rounds_played,
           rounds_won,
@@ -922,187 +2459,559 @@ pub async fn get_miner_totals_all_time_v2(
             WHEN net_sol_change > 0 THEN 'up'
             WHEN net_sol_change < 0 THEN 'down'
             ELSE 'flat'
-          END AS sol_balance_direction
-        FROM miner_totals
-        WHERE rounds_played >= 100
-        ORDER BY net_sol_change DESC
-        LIMIT ? OFFSET ?;
-    "#)
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(pool)
-    .await?;
-    Ok(rows)
+          END AS sol_balance_direction,
+          refined_ore,
+          net_ore
+        FROM agg
+        ORDER BY rank
+        LIMIT "#, metric.order_by()));
+    qb.push_bind(limit);
+    qb.push(" OFFSET ");
+    qb.push_bind(offset);
+    qb
 }
 
-pub async fn get_ore_leaderboard_all_time_v2(
+/// Single leaderboard query backing every leaderboard route.
+pub async fn get_leaderboard(
     pool: &sqlx::SqlitePool,
+    metric: LeaderboardMetric,
+    window: LeaderboardWindow,
+    min_rounds: i64,
+    direction: Option<SolBalanceDirection>,
     limit: i64,
     offset: i64,
-) -> anyhow::Result<Vec<MinerOreLeaderboardRow>> {
-    let rows = sqlx::query_as::<_, MinerOreLeaderboardRow>(r#"
-        SELECT
-          ROW_NUMBER() OVER (ORDER BY total_ore_earned DESC, total_sol_earned DESC) AS rank,
-          pubkey,
-          rounds_played,
-          rounds_won,
-          total_sol_deployed,
-          total_sol_earned,
-          total_ore_earned,
-          net_sol_change
-        FROM miner_totals
-        WHERE rounds_played >= 100
-        ORDER BY rank
-        LIMIT ? OFFSET ?;
-    "#)
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(pool)
-    .await?;
+) -> anyhow::Result<Vec<MinerLeaderboardRow>> {
+    let mut qb = leaderboard_query(metric, window, min_rounds, direction, limit, offset);
+    let rows = qb.build_query_as::<MinerLeaderboardRow>()
+        .fetch_all(pool)
+        .await?;
     Ok(rows)
 }
 
+/// Stores one leaderboard's ranks as of `taken_at`, for later comparison by movers.
+pub async fn insert_leaderboard_ranks(
+    pool: &Pool<Sqlite>,
+    window: LeaderboardWindow,
+    taken_at: i64,
+    rows: &[MinerLeaderboardRow],
+) -> Result<(), sqlx::Error> {
+    let chunk_size = insert_chunk_size("LEADERBOARD_RANKS_CHUNK_SIZE", 1000, 4);
+    let key = window.key();
 
-pub async fn get_leaderboard_last_n_rounds_v2(
-    pool: &sqlx::SqlitePool,
-    n_rounds: i64,
+    let mut tx = pool.begin().await?;
+    for chunk in rows.chunks(chunk_size) {
+        let mut qb = QueryBuilder::<Sqlite>::new("INSERT OR REPLACE INTO leaderboard_ranks (window_key, taken_at, pubkey, rank) ");
+        qb.push_values(chunk, |mut b, r| {
+            b.push_bind(key.clone())
+                .push_bind(taken_at)
+                .push_bind(r.pubkey.clone())
+                .push_bind(r.rank);
+        });
+        qb.build().execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct LeaderboardRank {
+    pub taken_at: i64,
+    pub pubkey: String,
+    pub rank: i64,
+}
+
+/// Ranks from the newest copy of the window taken at or before `at`, empty if none.
+pub async fn get_leaderboard_ranks_before(
+    pool: &Pool<Sqlite>,
+    window: LeaderboardWindow,
+    at: i64,
+) -> Result<Vec<LeaderboardRank>, sqlx::Error> {
+    let key = window.key();
+    sqlx::query_as::<_, LeaderboardRank>(
+        r#"
+        SELECT taken_at, pubkey, rank
+        FROM leaderboard_ranks
+        WHERE window_key = ?1
+          AND taken_at = (
+            SELECT MAX(taken_at) FROM leaderboard_ranks
+            WHERE window_key = ?1 AND taken_at <= ?2
+          )
+        "#
+    )
+    .bind(key)
+    .bind(at)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn prune_leaderboard_ranks(pool: &Pool<Sqlite>, before: i64) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM leaderboard_ranks WHERE taken_at < ?")
+        .bind(before)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Same as `get_leaderboard` but yields rows as sqlite produces them. Meant for
+/// `AppState::read_pool`, the connection is held until the stream is dropped.
+pub fn stream_leaderboard(
+    pool: Pool<Sqlite>,
+    metric: LeaderboardMetric,
+    window: LeaderboardWindow,
+    min_rounds: i64,
+    direction: Option<SolBalanceDirection>,
     limit: i64,
     offset: i64,
-) -> anyhow::Result<Vec<MinerLeaderboardRow>> {
-    let rows = sqlx::query_as::<_, MinerLeaderboardRow>(r#"
-        WITH last_n AS (
-          SELECT id FROM rounds ORDER BY id DESC LIMIT ?
-        ),
-        agg AS (
-          SELECT
-            s.pubkey,
-            COUNT(*)                         AS rounds_played,
-            SUM(s.won_round)                 AS rounds_won,
-            SUM(s.total_sol_deployed)        AS total_sol_deployed,
-            SUM(s.total_sol_earned)          AS total_sol_earned,
-            SUM(s.total_ore_earned)          AS total_ore_earned,
-            SUM(s.net_sol_round)             AS net_sol_change
-          FROM miner_round_stats s
-          JOIN last_n r ON r.id = s.round_id
-          GROUP BY s.pubkey
+) -> impl Stream<Item = Result<MinerLeaderboardRow, sqlx::Error>> + Send + 'static {
+    async_stream::try_stream! {
+        let mut qb = leaderboard_query(metric, window, min_rounds, direction, limit, offset);
+        let mut rows = qb.build_query_as::<MinerLeaderboardRow>().fetch(&pool);
+        while let Some(row) = rows.next().await {
+            yield row?;
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, FromRow)]
+pub struct MinerRoundHistoryRow {
+    pub round_id: i64,
+    pub deployed: i64,
+    pub sol_earned: i64,
+    pub ore_earned: i64,
+    pub won_round: i64,
+    pub net_sol_round: i64,
+    pub created_at: String,
+}
+
+/// Every finalized round the miner played, oldest first, yielded as sqlite produces them.
+/// Meant for `AppState::read_pool`, the connection is held until the stream is dropped.
+pub fn stream_miner_round_history(
+    pool: Pool<Sqlite>,
+    pubkey: String,
+) -> impl Stream<Item = Result<MinerRoundHistoryRow, sqlx::Error>> + Send + 'static {
+    async_stream::try_stream! {
+        let mut rows = sqlx::query_as::<_, MinerRoundHistoryRow>(
+            r#"
+            SELECT
+              s.round_id,
+              s.total_sol_deployed AS deployed,
+              s.total_sol_earned   AS sol_earned,
+              s.total_ore_earned   AS ore_earned,
+              s.won_round,
+              s.net_sol_round,
+              r.created_at
+            FROM miner_round_stats s
+            JOIN rounds r ON r.id = s.round_id
+            WHERE s.pubkey = ?
+            ORDER BY s.round_id ASC
+            "#
+        )
+        .bind(pubkey)
+        .fetch(&pool);
+        while let Some(row) = rows.next().await {
+            yield row?;
+        }
+    }
+}
+
+/// The miner's leaderboard row with its 1-based rank, counted as the number of
+/// miners strictly above it plus one. None if the miner isn't on the board.
+pub async fn get_leaderboard_rank(
+    pool: &sqlx::SqlitePool,
+    pubkey: String,
+    metric: LeaderboardMetric,
+    window: LeaderboardWindow,
+    min_rounds: i64,
+) -> anyhow::Result<Option<MinerLeaderboardRow>> {
+    let mut qb = QueryBuilder::<Sqlite>::new("");
+    push_leaderboard_agg(&mut qb, window, min_rounds, None);
+
+    qb.push(r#",
+        target AS (
+          SELECT * FROM agg WHERE pubkey = "#);
+    qb.push_bind(pubkey);
+    qb.push(format!(r#"
         )
         SELECT
-          ROW_NUMBER() OVER (ORDER BY net_sol_change DESC) AS rank,
-          pubkey,
-          rounds_played,
-          rounds_won,
-          total_sol_deployed,
-          total_sol_earned,
-          total_ore_earned,
-          net_sol_change,
+          (SELECT COUNT(*) FROM agg a WHERE {}) + 1 AS rank,
+          t.pubkey,
+          t.rounds_played,
+          t.rounds_won,
+          t.total_sol_deployed,
+          t.total_sol_earned,
+          t.total_ore_earned,
+          t.net_sol_change,
           CASE
-            WHEN net_sol_change > 0 THEN 'up'
-            WHEN net_sol_change < 0 THEN 'down'
+            WHEN t.net_sol_change > 0 THEN 'up'
+            WHEN t.net_sol_change < 0 THEN 'down'
             ELSE 'flat'
-          END AS sol_balance_direction
-        FROM agg
-        ORDER BY rank
-        LIMIT ? OFFSET ?;
-    "#)
-    .bind(n_rounds.max(1))
-    .bind(limit)
-    .bind(offset)
-    .fetch_all(pool)
-    .await?;
-    Ok(rows)
+          END AS sol_balance_direction,
+          t.refined_ore,
+          t.net_ore
+        FROM target t
+    "#, metric.ranks_above()));
+
+    let row = qb.build_query_as::<MinerLeaderboardRow>()
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
 }
 
-pub async fn get_ore_leaderboard_last_n_rounds_v2(
-    pool: &sqlx::SqlitePool,
-    n_rounds: i64,
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct RefinementLeaderboardRow {
+    pub rank: i64,
+    pub pubkey: String,
+    pub refined_ore: i64,
+    pub unclaimed_ore: i64,
+    /// When the snapshot the values come from was taken.
+    pub created_at: i64,
+}
+
+/// Miners ranked by refined ORE as of their latest snapshot.
+pub async fn get_refinement_leaderboard(
+    pool: &Pool<Sqlite>,
     limit: i64,
     offset: i64,
-) -> anyhow::Result<Vec<MinerOreLeaderboardRow>> {
-    let rows = sqlx::query_as::<_, MinerOreLeaderboardRow>(r#"
-        WITH last_n AS (
-          SELECT id FROM rounds ORDER BY id DESC LIMIT ?
-        ),
-        agg AS (
+) -> Result<Vec<RefinementLeaderboardRow>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, RefinementLeaderboardRow>(
+        r#"
+        WITH latest AS (
           SELECT
-            s.pubkey,
-            COUNT(*)                  AS rounds_played,
-            SUM(s.won_round)          AS rounds_won,
-            SUM(s.total_sol_deployed) AS total_sol_deployed,
-            SUM(s.total_sol_earned)   AS total_sol_earned,
-            SUM(s.total_ore_earned)   AS total_ore_earned,
-            SUM(s.net_sol_round)      AS net_sol_change
-          FROM miner_round_stats s
-          JOIN last_n r ON r.id = s.round_id
-          GROUP BY s.pubkey
+            pubkey,
+            refined_ore,
+            unclaimed_ore,
+            created_at,
+            ROW_NUMBER() OVER (PARTITION BY pubkey ORDER BY id DESC) AS rn
+          FROM miner_snapshots
         )
         SELECT
-          ROW_NUMBER() OVER (ORDER BY total_ore_earned DESC, total_sol_earned DESC) AS rank,
+          ROW_NUMBER() OVER (ORDER BY refined_ore DESC, pubkey) AS rank,
           pubkey,
-          rounds_played,
-          rounds_won,
-          total_sol_deployed,
-          total_sol_earned,
-          total_ore_earned,
-          net_sol_change
-        FROM agg
+          refined_ore,
+          unclaimed_ore,
+          created_at
+        FROM latest
+        WHERE rn = 1
         ORDER BY rank
-        LIMIT ? OFFSET ?;
-    "#)
-    .bind(n_rounds.max(1))
+        LIMIT ? OFFSET ?
+        "#
+    )
     .bind(limit)
     .bind(offset)
     .fetch_all(pool)
     .await?;
+
     Ok(rows)
 }
 
+pub async fn process_secondary_database(db_url: String) -> Option<Pool<Sqlite>> {
+    tracing::info!("connecting to db_2");
+    let db_2_pool = if let Some(p) = get_db_2_pool(db_url.to_string()).await {
+        p
+    } else {
+        tracing::error!("failed to established connection to db_2");
+        return None
+    };
+    tracing::info!("successfully established connection to db_2");
+    tracing::info!("Running migrations for db_2");
+    match sqlx::migrate!("./migrations").run(&db_2_pool).await {
+        Ok(_) => {
+            tracing::info!("Successfully ran migrations for db_2");
+            Some(db_2_pool)
+        },
+        Err(e) => {
+            tracing::error!("Error running migrations for db_2.\nE: {:?}", e);
+            None
+        },
+    }
+}
+
+/// SQLITE_BUSY (5) and SQLITE_LOCKED (6), including their extended codes.
+fn is_busy(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Database(db) => db
+            .code()
+            .and_then(|c| c.parse::<i32>().ok())
+            .map(|c| matches!(c & 0xff, 5 | 6))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
 
-pub async fn process_secondary_database(db_url: String) {
-    tokio::spawn(async move {
-        tracing::info!("connecting to db_2");
-        let db_2_pool = get_db_2_pool(db_url.to_string()).await;
-        if let None = db_2_pool {
-            tracing::info!("failed to established connection to db_2");
+/// Retries a write that failed with a transient lock error, backing off between
+/// attempts. Any other error, or the last busy error, is returned as is.
+async fn retry_busy<F, Fut>(name: &'static str, mut f: F) -> Result<(), sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<(), sqlx::Error>>,
+{
+    const MAX_ATTEMPTS: u32 = 4;
+    let mut delay = Duration::from_millis(250);
+
+    let mut attempt = 1;
+    loop {
+        match f().await {
+            Err(e) if attempt < MAX_ATTEMPTS && is_busy(&e) => {
+                tracing::warn!(
+                    "Database busy writing {} (attempt {}/{}), retrying in {} ms",
+                    name, attempt, MAX_ATTEMPTS, delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            r => return r,
         }
-        tracing::info!("successfully established connection to db_2");
-        tracing::info!("Running migrations for db_2");
-        if let Some(db_2_pool) = db_2_pool {
-            match sqlx::migrate!("./migrations").run(&db_2_pool).await {
-                Ok(_) => {
-                    tracing::info!("Successfully ran migrations for db_2");
-                },
-                Err(e) => {
-                    tracing::info!("Error running migrations for db_2.\nE: {:?}", e);
-                },
+    }
+}
+
+/// Best-effort write to the secondary database. Runs in its own task so a slow
+/// or broken db_2 never blocks the primary write path.
+fn replicate<F, Fut>(db_2: &Option<Pool<Sqlite>>, name: &'static str, f: F)
+where
+    F: FnOnce(Pool<Sqlite>) -> Fut,
+    Fut: std::future::Future<Output = Result<(), sqlx::Error>> + Send + 'static,
+{
+    if let Some(db_2) = db_2 {
+        let fut = f(db_2.clone());
+        tokio::spawn(async move {
+            if let Err(e) = fut.await {
+                tracing::error!("Failed to replicate {} to db_2: {:?}", name, e);
             }
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PoolConfig {
+    pub min_connections: u32,
+    pub max_connections: u32,
+    pub acquire_timeout_secs: u64,
+    pub busy_timeout_secs: u64,
+}
+
+impl PoolConfig {
+    /// Single writer connection, waits a long time rather than failing a write.
+    pub const PRIMARY: PoolConfig = PoolConfig {
+        min_connections: 0,
+        max_connections: 1,
+        acquire_timeout_secs: 1000,
+        busy_timeout_secs: 400,
+    };
+
+    /// Readers for responses that stream rows straight from sqlite, so a slow client
+    /// holds one of these instead of the primary's only connection.
+    pub const READER: PoolConfig = PoolConfig {
+        min_connections: 0,
+        max_connections: 4,
+        acquire_timeout_secs: 10,
+        busy_timeout_secs: 15,
+    };
+
+    pub const SECONDARY: PoolConfig = PoolConfig {
+        min_connections: 2,
+        max_connections: 10,
+        acquire_timeout_secs: 10,
+        busy_timeout_secs: 15,
+    };
+
+    /// Reads `{prefix}_MAX_CONNECTIONS`, `{prefix}_MIN_CONNECTIONS`, `{prefix}_ACQUIRE_TIMEOUT_SECS`
+    /// and `{prefix}_BUSY_TIMEOUT_SECS`, falling back to `defaults`.
+    pub fn from_env(prefix: &str, defaults: PoolConfig) -> PoolConfig {
+        fn var<T: FromStr>(prefix: &str, key: &str, default: T) -> T {
+            std::env::var(format!("{prefix}_{key}"))
+                .ok()
+                .and_then(|v| v.parse::<T>().ok())
+                .unwrap_or(default)
         }
-        
-    });
+        PoolConfig {
+            min_connections: var(prefix, "MIN_CONNECTIONS", defaults.min_connections),
+            max_connections: var(prefix, "MAX_CONNECTIONS", defaults.max_connections).max(1),
+            acquire_timeout_secs: var(prefix, "ACQUIRE_TIMEOUT_SECS", defaults.acquire_timeout_secs),
+            busy_timeout_secs: var(prefix, "BUSY_TIMEOUT_SECS", defaults.busy_timeout_secs),
+        }
+    }
+}
+
+/// Shared connect options so the primary and secondary databases stay configured the same.
+pub async fn connect_pool(db_url: &str, config: &PoolConfig) -> Result<Pool<Sqlite>, sqlx::Error> {
+    let connect_ops = connect_options(db_url, config)?
+        .create_if_missing(true)
+        .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
+    pool_options(config).connect_with(connect_ops).await
+}
+
+/// For `READ_ONLY`: the file must already exist and its journal mode is left as is,
+/// since creating the file or switching it to WAL are both writes.
+pub async fn connect_read_only_pool(db_url: &str, config: &PoolConfig) -> Result<Pool<Sqlite>, sqlx::Error> {
+    let connect_ops = connect_options(db_url, config)?.read_only(true);
+    pool_options(config).connect_with(connect_ops).await
+}
+
+fn connect_options(db_url: &str, config: &PoolConfig) -> Result<SqliteConnectOptions, sqlx::Error> {
+    Ok(SqliteConnectOptions::from_str(db_url)?
+        .pragma("cache_size", "-200000") // Set cache to ~200MB (200,000KB)
+        .pragma("temp_store", "memory") // Store temporary data in memory
+        .synchronous(sqlx::sqlite::SqliteSynchronous::Normal)
+        .busy_timeout(Duration::from_secs(config.busy_timeout_secs))
+        .foreign_keys(true))
+}
+
+fn pool_options(config: &PoolConfig) -> sqlx::sqlite::SqlitePoolOptions {
+    sqlx::sqlite::SqlitePoolOptions::new()
+        .min_connections(config.min_connections.min(config.max_connections))
+        .max_connections(config.max_connections)
+        .acquire_timeout(Duration::from_secs(config.acquire_timeout_secs))
+}
+
+/// Fresh in-memory database with every migration applied. Kept to a single connection
+/// that never idles out, since each connection to `:memory:` is its own database.
+#[cfg(test)]
+pub async fn memory_pool() -> Pool<Sqlite> {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .min_connections(1)
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(SqliteConnectOptions::from_str("sqlite::memory:").unwrap().foreign_keys(true))
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    pool
 }
 
 async fn get_db_2_pool(db_url: String) -> Option<Pool<Sqlite>> {
-    let db_2_connect_ops = match SqliteConnectOptions::from_str(&db_url) {
-        Ok(d) => {
-            d.create_if_missing(true)
-            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
-            .pragma("cache_size", "-200000") // Set cache to ~200MB (200,000KB)
-            .pragma("temp_store", "memory") // Store temporary data in memory
-            .synchronous(sqlx::sqlite::SqliteSynchronous::Normal)
-            .busy_timeout(Duration::from_secs(15))
-            .foreign_keys(true)
-        },
+    match connect_pool(&db_url, &PoolConfig::from_env("DB_2", PoolConfig::SECONDARY)).await {
+        Ok(s) => Some(s),
         Err(e) => {
-            return None
+            tracing::error!("Failed to connect to db_2: {:?}", e);
+            None
         }
-    };
+    }
+}
 
-    match sqlx::sqlite::SqlitePoolOptions::new()
-        .min_connections(2)
-        .max_connections(10)
-        .acquire_timeout(Duration::from_secs(10))
-        .connect_with(db_2_connect_ops)
-        .await {
-            Ok(s) => {return Some(s)}
-            Err(e) => {return None}
+#[cfg(test)]
+mod tests {
+    use bytemuck::Zeroable;
+
+    use super::*;
+
+    fn deployment(round_id: i64, i: usize) -> CreateDeployment {
+        CreateDeployment {
+            round_id,
+            pubkey: format!("miner{}", i),
+            square_id: (i % 25) as i64,
+            amount: 1_000_000,
+            sol_earned: 0,
+            ore_earned: 0,
+            unclaimed_ore: 0,
+            cumulative: 0,
+            admin_fee: 10_000,
+            created_at: "2025-12-01T00:00:00Z".to_string(),
         }
+    }
+
+    #[tokio::test]
+    async fn read_only_pool_rejects_writes() {
+        let path = std::env::temp_dir().join(format!("ore-stats-read-only-{}.db", std::process::id()));
+        let db_url = format!("sqlite://{}", path.display());
+        let pool = connect_pool(&db_url, &PoolConfig::PRIMARY).await.unwrap();
+        sqlx::query("CREATE TABLE t (x INTEGER)").execute(&pool).await.unwrap();
+        pool.close().await;
+
+        let pool = connect_read_only_pool(&db_url, &PoolConfig::PRIMARY).await.unwrap();
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM t").fetch_one(&pool).await.unwrap();
+        assert_eq!(count, 0);
+        assert!(sqlx::query("INSERT INTO t VALUES (1)").execute(&pool).await.is_err());
+        pool.close().await;
+        for suffix in ["", "-wal", "-shm"] {
+            std::fs::remove_file(format!("{}{}", path.display(), suffix)).ok();
+        }
+    }
+
+    #[tokio::test]
+    async fn hourly_activity_buckets_deployments_by_round() {
+        use bytemuck::Zeroable;
+
+        let pool = memory_pool().await;
+        let mut round = Round::zeroed();
+        round.id = 1;
+        insert_round(&pool, &None, &RoundRow::from(round)).await.unwrap();
+        let hour: i64 = sqlx::query_scalar("SELECT CAST(strftime('%H', created_at) AS INTEGER) FROM rounds")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let rows: Vec<CreateDeployment> = (0..3).map(|i| deployment(1, i)).collect();
+        insert_deployment_chunks(&pool, &rows, 100).await.unwrap();
+
+        let hours = get_hourly_activity(&pool, 1).await.unwrap();
+        assert_eq!(hours.len(), 24);
+        assert_eq!(hours[hour as usize].deployments, 3);
+        assert_eq!(hours[hour as usize].total_deployed, 3_000_000);
+        assert_eq!(hours.iter().map(|h| h.deployments).sum::<i64>(), 3);
+    }
+
+    #[test]
+    fn insert_chunk_size_stays_under_bind_limit() {
+        let size = insert_chunk_size("TEST_UNSET_INSERT_CHUNK_SIZE", usize::MAX, DEPLOYMENT_COLUMNS);
+        assert!(size * DEPLOYMENT_COLUMNS <= SQLITE_MAX_VARIABLE_NUMBER);
+        assert_eq!(insert_chunk_size("TEST_UNSET_INSERT_CHUNK_SIZE", 0, DEPLOYMENT_COLUMNS), 1);
+        assert_eq!(insert_chunk_size("TEST_UNSET_INSERT_CHUNK_SIZE", 100, DEPLOYMENT_COLUMNS), 100);
+    }
+
+    #[tokio::test]
+    async fn inserts_thousands_of_deployments_at_the_largest_chunk_size() {
+        let pool = memory_pool().await;
+        let rows: Vec<CreateDeployment> = (0..5_000).map(|i| deployment(1, i)).collect();
+        let chunk_size = insert_chunk_size("TEST_UNSET_INSERT_CHUNK_SIZE", usize::MAX, DEPLOYMENT_COLUMNS);
+        assert!(chunk_size < rows.len());
+
+        insert_deployment_chunks(&pool, &rows, chunk_size).await.unwrap();
+
+        let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM deployments WHERE round_id = 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, rows.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn storing_a_round_twice_does_not_duplicate_deployments() {
+        let pool = memory_pool().await;
+        let rows: Vec<CreateDeployment> = (0..50).map(|i| deployment(1, i)).collect();
+        insert_deployment_chunks(&pool, &rows, 20).await.unwrap();
+
+        let mut again = rows.clone();
+        again[0].sol_earned = 42;
+        insert_deployment_chunks(&pool, &again, 20).await.unwrap();
+
+        let (stored, sol_earned): (i64, i64) = sqlx::query_as("SELECT COUNT(*), SUM(sol_earned) FROM deployments WHERE round_id = 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, rows.len() as i64);
+        assert_eq!(sol_earned, 42);
+    }
+
+    #[tokio::test]
+    async fn restoring_a_round_keeps_created_at_and_the_finalized_top_miner() {
+        let pool = memory_pool().await;
+        let mut round = Round::zeroed();
+        round.id = 3;
+        round.slot_hash[0] = 7;
+        let default_top_miner = RoundRow::from(round);
+
+        let finalized = RoundRow {
+            top_miner: "miner1".to_string(),
+            created_at: "2025-12-01T00:00:00+00:00".to_string(),
+            ..default_top_miner.clone()
+        };
+        insert_round_into(&pool, &finalized).await.unwrap();
+        insert_round_into(&pool, &default_top_miner).await.unwrap();
+
+        let stored = get_round_by_id(&pool, 3).await.unwrap().remove(0);
+        assert_eq!(stored.created_at, finalized.created_at);
+        assert_eq!(stored.top_miner, "miner1");
+    }
 }
@@ -0,0 +1,151 @@
+use async_graphql::{ComplexObject, Context, EmptyMutation, EmptySubscription, Object, Result, Schema};
+use axum::response::Html;
+
+use crate::{
+    app_state::{AppMiner, AppState},
+    database::{self, CurrentMinerFilter, MinerLeaderboardRow, MinerTotalsRow, RoundRow},
+    leaderboard_min_rounds, normalize_pagination, parse_leaderboard_metric, parse_leaderboard_window,
+};
+
+pub type ApiSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Complexity budget of one query. A list field costs its limit times the cost of each
+/// item, so a full page of miners or rounds fits but per-item lookups don't.
+const MAX_COMPLEXITY: usize = 50_000;
+
+/// Cost of a nested field that runs its own database query for every parent item. With
+/// `MAX_COMPLEXITY` it allows about a hundred of them per query.
+const LOOKUP_COMPLEXITY: usize = 500;
+
+/// Most rounds a nested `AppMiner.rounds` returns per miner.
+const NESTED_ROUNDS_MAX: i64 = 25;
+
+/// Read-only schema over the same database functions as the REST routes. Depth and
+/// complexity are capped, with list fields weighted by their limit, so one query can't
+/// fan out into thousands of lookups.
+pub fn schema(state: AppState) -> ApiSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(state)
+        .limit_depth(6)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// Complexity of a list field: the page size it will return times the cost of one item.
+fn list_complexity(limit: Option<i64>, default: i64, max: i64, child_complexity: usize) -> usize {
+    limit.unwrap_or(default).clamp(1, max) as usize * child_complexity
+}
+
+pub async fn graphiql() -> Html<String> {
+    Html(async_graphql::http::GraphiQLSource::build().endpoint("/v1/graphql").finish())
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Current miners, same filters and caps as /miners.
+    #[graphql(complexity = "list_complexity(limit, 2500, 2500, child_complexity)")]
+    async fn miners(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        min_rewards_ore: Option<i64>,
+        min_rewards_sol: Option<i64>,
+        round_id: Option<i64>,
+    ) -> Result<Vec<AppMiner>> {
+        let state = ctx.data::<AppState>()?;
+        let (limit, offset) = normalize_pagination(limit, offset, 2500, 2500)?;
+        let filter = CurrentMinerFilter { min_rewards_ore, min_rewards_sol, round_id };
+        Ok(database::get_current_miners(&state.db_pool, filter, None, limit, offset).await?)
+    }
+
+    /// A single miner from the in-memory list, None if it isn't loaded.
+    async fn miner(&self, ctx: &Context<'_>, pubkey: String) -> Result<Option<AppMiner>> {
+        let state = ctx.data::<AppState>()?;
+        Ok(state.miners.read().await.iter().find(|m| m.authority == pubkey).cloned())
+    }
+
+    /// Stored rounds, newest first.
+    #[graphql(complexity = "list_complexity(limit, 100, 2000, child_complexity)")]
+    async fn rounds(&self, ctx: &Context<'_>, limit: Option<i64>, offset: Option<i64>) -> Result<Vec<RoundRow>> {
+        let state = ctx.data::<AppState>()?;
+        let (limit, offset) = normalize_pagination(limit, offset, 100, 2000)?;
+        Ok(database::get_rounds(&state.db_pool, limit, offset, None).await?)
+    }
+
+    async fn round(&self, ctx: &Context<'_>, id: i64) -> Result<Option<RoundRow>> {
+        let state = ctx.data::<AppState>()?;
+        Ok(database::get_round_by_id(&state.db_pool, id).await?.into_iter().next())
+    }
+
+    async fn miner_totals(&self, ctx: &Context<'_>, pubkey: String) -> Result<Option<MinerTotalsRow>> {
+        let state = ctx.data::<AppState>()?;
+        Ok(database::get_miner_stats(&state.db_pool, pubkey).await?)
+    }
+
+    /// Same `metric`/`window`/`min_rounds` as /leaderboard.
+    #[graphql(complexity = "list_complexity(limit, 100, 2000, child_complexity)")]
+    async fn leaderboard(
+        &self,
+        ctx: &Context<'_>,
+        metric: Option<String>,
+        window: Option<String>,
+        min_rounds: Option<i64>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<MinerLeaderboardRow>> {
+        let state = ctx.data::<AppState>()?;
+        let metric = parse_leaderboard_metric(metric.as_deref())?;
+        let window = parse_leaderboard_window(window.as_deref())?;
+        let min_rounds = leaderboard_min_rounds(window, min_rounds);
+        let (limit, offset) = normalize_pagination(limit, offset, 100, 2000)?;
+        Ok(database::get_leaderboard(&state.db_pool, metric, window, min_rounds, None, limit, offset).await?)
+    }
+}
+
+/// Lets a query join a miner to its totals and rounds without extra requests.
+#[ComplexObject]
+impl AppMiner {
+    #[graphql(complexity = "LOOKUP_COMPLEXITY + child_complexity")]
+    async fn totals(&self, ctx: &Context<'_>) -> Result<Option<MinerTotalsRow>> {
+        let state = ctx.data::<AppState>()?;
+        Ok(database::get_miner_stats(&state.db_pool, self.authority.clone()).await?)
+    }
+
+    /// Rounds the miner deployed in, newest first, at most `NESTED_ROUNDS_MAX` per miner.
+    #[graphql(complexity = "LOOKUP_COMPLEXITY + list_complexity(limit, 10, NESTED_ROUNDS_MAX, child_complexity)")]
+    async fn rounds(&self, ctx: &Context<'_>, limit: Option<i64>, offset: Option<i64>) -> Result<Vec<RoundRow>> {
+        let state = ctx.data::<AppState>()?;
+        let (limit, offset) = normalize_pagination(limit, offset, 10, NESTED_ROUNDS_MAX)?;
+        Ok(database::get_miner_rounds(&state.db_pool, self.authority.clone(), limit, offset).await?)
+    }
+}
+
+#[ComplexObject]
+impl RoundRow {
+    /// Hex encoded, as in the REST responses.
+    async fn slot_hash(&self) -> String {
+        self.slot_hash.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn complexity_counts_list_sizes() {
+        let schema = schema(AppState::for_tests(database::memory_pool().await));
+
+        let page = schema.execute("{ miners { authority roundId } rounds(limit: 2000) { id } }").await;
+        assert!(page.errors.is_empty(), "{:?}", page.errors);
+
+        let fan_out = schema.execute("{ miners(limit: 1000) { authority totals { roundsPlayed } } }").await;
+        assert!(fan_out.errors.iter().any(|e| e.message.contains("too complex")), "{:?}", fan_out.errors);
+
+        let nested = schema.execute("{ miners(limit: 5) { rounds(limit: 25) { id } } }").await;
+        assert!(nested.errors.is_empty(), "{:?}", nested.errors);
+    }
+}
@@ -1,27 +1,27 @@
-use std::{collections::HashMap, convert::Infallible, env, str::FromStr, sync::Arc, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use std::{collections::{HashMap, HashSet}, convert::Infallible, env, str::FromStr, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
 
 use anyhow::{anyhow, bail};
-use sqlx::{sqlite::SqliteConnectOptions, Pool, Sqlite};
+use sqlx::{Pool, Sqlite};
 use thiserror::Error;
-use axum::{body::Body, extract::{Path, Query, State}, http::{Request, Response, StatusCode}, middleware::{self, Next}, response::{sse, Sse}, routing::get, Json, Router};
+use axum::{body::Body, extract::{ws::{Message, WebSocket, WebSocketUpgrade}, FromRequestParts, OriginalUri, Path, Query, State}, http::{header, request::Parts, HeaderMap, HeaderValue, Request, Response, StatusCode, Uri}, middleware::{self, Next}, response::{sse, IntoResponse, Sse}, routing::{get, post}, Json, Router};
+use async_graphql_axum::GraphQL;
 use const_crypto::ed25519;
-use ore_api::{consts::{BOARD, ROUND, TREASURY_ADDRESS}, state::{round_pda, Board, Miner, Round, Treasury}};
+use ore_api::{consts::{BOARD, ROUND}, state::{Board, Miner, Round, Treasury}};
 use serde::{Deserialize, Serialize};
-use solana_account_decoder_client_types::UiAccountEncoding;
-use solana_client::{nonblocking::rpc_client::RpcClient, rpc_filter::RpcFilterType};
-use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
 use steel::{AccountDeserialize, Pubkey};
 use tokio::{signal, sync::{broadcast, RwLock}};
-use tokio_stream::Stream;
+use tokio_stream::{Stream, StreamExt};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
-use crate::{app_state::{AppBoard, AppLiveDeployment, AppMiner, AppRound, AppState, AppTreasury, LiveBroadcastData}, database::{get_deployments_by_round, process_secondary_database, DbMinerSnapshot, DbTreasury, GetDeployment, MinerLeaderboardRow, MinerOreLeaderboardRow, MinerTotalsRow, RoundRow}, rpc::{infer_refined_ore, update_data_system, watch_live_board}};
+use crate::{cluster::{addresses, init_addresses}, app_state::{AppBoard, AppLiveDeployment, AppMiner, AppRound, AppState, AppTreasury, LeaderboardCacheKey, LiveBroadcastData, RpcStatus}, database::{hex_bytes, get_deployments_by_round, AdminFeeTotal, CurrentMinerFilter, CurrentMinerOrder, DailyStats, DeployPercentiles, connect_pool, connect_read_only_pool, process_secondary_database, DbBoardSnapshot, PoolConfig, DbMinerSnapshot, DbTreasury, GetDeployment, GiniMetric, HourlyActivity, InactiveMiner, LeaderboardMetric, LeaderboardWindow, MinerLeaderboardRow, MinerLuck, NewMiner, RefinementLeaderboardRow, SolBalanceDirection, MinerTotalsRow, RentPayerRow, RoundActiveMiners, RoundGap, RoundPlayers, RoundRate, RoundRow, RoundSquareDeployed, RoundWinner, SeriesBucket, NetFlow, SnapshotSeriesPoint, SquarePopularity, TreasuryDelta, UniqueMiners}, rpc::{backfill_gaps, backfill_rounds, commitment_from_env, get_miner_accounts, observed_miner_account_size, parse_miner_accounts, recompute_winning_square, refetch_round, rpc_timeout_from_env, rpc_urls_from_env, update_data_system, RpcRateLimiter, watch_live_board, watch_round_gaps}, maintenance::{backup_database, backup_system, db_refresh_system, leaderboard_rank_system, leaderboard_warm_system, movers_retention_hours_from_env, movers_top_from_env, movers_windows_from_env, optimize_system, refresh_state_from_db, snapshot_retention_system, wal_checkpoint_system, Backup}, metrics::RpcMetrics, rewards::{compute_deployment_reward, RoundRewardParams}, serde_util::{bigint_mode, bigint_as_string, with_bigint_mode}};
 
 /// Program id for const pda derivations
 const PROGRAM_ID: [u8; 32] = unsafe { *(&ore_api::id() as *const Pubkey as *const [u8; 32]) };
 
 
-/// The address of the board account.
+/// The address of the board account on mainnet, see `cluster::addresses` for the active one.
 pub const BOARD_ADDRESS: Pubkey =
     Pubkey::new_from_array(ed25519::derive_program_address(&[BOARD], &PROGRAM_ID).0);
 
@@ -30,9 +30,16 @@ pub const ROUND_ADDRESS: Pubkey =
     Pubkey::new_from_array(ed25519::derive_program_address(&[ROUND], &PROGRAM_ID).0);
 
 pub mod app_state;
+pub mod chain;
+pub mod cluster;
 pub mod rpc;
 pub mod database;
 pub mod entropy_api;
+pub mod graphql;
+pub mod maintenance;
+pub mod metrics;
+pub mod rewards;
+pub mod serde_util;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -44,44 +51,239 @@ async fn main() -> anyhow::Result<()> {
         .with(env_filter)
         .init();
 
-    let db_url = env::var("DATABASE_URL").unwrap_or_else(|_| "data/app.db".to_string());
-    if let Some(parent) = std::path::Path::new(&db_url).parent() {
-        std::fs::create_dir_all(parent).ok();
+    // ENABLE_API=false / --indexer-only polls and maintains the db without binding a listener,
+    // ENABLE_POLLER=false / --api-only serves from the db without touching the rpc
+    let enable_api = env_flag("ENABLE_API", true) && !env::args().any(|a| a == "--indexer-only");
+    let enable_poller = env_flag("ENABLE_POLLER", true) && !env::args().any(|a| a == "--api-only");
+    if !enable_api && !enable_poller {
+        bail!("At least one of ENABLE_API and ENABLE_POLLER must be enabled");
     }
 
-    let db_connect_ops = SqliteConnectOptions::from_str(&db_url)?
-        .create_if_missing(true)
-        .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
-        .pragma("cache_size", "-200000") // Set cache to ~200MB (200,000KB)
-        .pragma("temp_store", "memory") // Store temporary data in memory
-        .synchronous(sqlx::sqlite::SqliteSynchronous::Normal)
-        .busy_timeout(Duration::from_secs(400))
-        .foreign_keys(true);
+    let db_url = env::var("DATABASE_URL").unwrap_or_else(|_| "data/app.db".to_string());
+    let read_only = env_flag("READ_ONLY", false);
+    let db_config = PoolConfig::from_env("DB", PoolConfig::PRIMARY);
+    let db_pool = if read_only {
+        connect_read_only_pool(&db_url, &db_config).await?
+    } else {
+        if let Some(parent) = std::path::Path::new(&db_url).parent() {
+            std::fs::create_dir_all(parent).ok();
+        }
+        connect_pool(&db_url, &db_config).await?
+    };
 
-    let db_pool = sqlx::sqlite::SqlitePoolOptions::new()
-        .max_connections(1)
-        .acquire_timeout(Duration::from_secs(1000))
-        .connect_with(db_connect_ops)
-        .await?;
+    if read_only {
+        tracing::warn!("READ_ONLY is set: database writes, migrations and maintenance tasks are disabled, serving reads from {}", db_url);
+    } else {
+        tracing::info!("Running optimize...");
+        sqlx::query("PRAGMA optimize").execute(&db_pool).await?;
+        tracing::info!("Optimize complete!");
 
-    tracing::info!("Running optimize...");
-    sqlx::query("PRAGMA optimize").execute(&db_pool).await?;
-    tracing::info!("Optimize complete!");
 
 
+        tracing::info!("Running migrations...");
 
-    tracing::info!("Running migrations...");
+        sqlx::migrate!("./migrations").run(&db_pool).await?;
 
-    sqlx::migrate!("./migrations").run(&db_pool).await?;
+        let flagged = database::backfill_round_split_flags(&db_pool).await?;
+        if flagged > 0 {
+            tracing::info!("Flagged {} stored split rounds", flagged);
+        }
 
-    tracing::info!("Database migrations complete.");
+        tracing::info!("Database migrations complete.");
+    }
+    let read_config = PoolConfig::from_env("READ_DB", PoolConfig::READER);
+    let read_pool = if read_only {
+        connect_read_only_pool(&db_url, &read_config).await?
+    } else {
+        connect_pool(&db_url, &read_config).await?
+    };
     tracing::info!("Database ready!");
 
-    let rpc_url = env::var("RPC_URL").expect("RPC_URL must be set");
-    let prefix = "https://".to_string();
-    let connection = RpcClient::new_with_commitment(prefix + &rpc_url, CommitmentConfig { commitment: CommitmentLevel::Confirmed });
+    // the secondary database only ever receives writes
+    let db_2_pool = match env::var("DATABASE_URL_2") {
+        Ok(db_2_url) if !read_only => process_secondary_database(db_2_url).await,
+        _ => None,
+    };
+
+    let rpc_urls = rpc_urls_from_env();
+    let rpc_url = rpc_urls[0].clone();
+    if rpc_urls.len() > 1 {
+        tracing::info!("Using {} RPC endpoints with failover", rpc_urls.len());
+    }
+    let commitment = commitment_from_env();
+    tracing::info!("Using RPC commitment: {:?}", commitment.commitment);
+    let rpc_timeout = rpc_timeout_from_env();
+    tracing::info!("Using RPC timeout: {}s", rpc_timeout.as_secs());
+    let connection = rpc::connect(&rpc_url, rpc_timeout, commitment);
+
+    let program = init_addresses()?;
+    tracing::info!("Using ORE program {} on {} (board {}, treasury {})", program.program_id, program.cluster, program.board, program.treasury);
+
+    let slot_time_ms = env::var("SLOT_TIME_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(400);
+
+    let rpc_metrics = Arc::new(RpcMetrics::default());
+    let initial = if enable_poller {
+        initial_state_from_chain(&connection, commitment, slot_time_ms, &rpc_metrics).await?
+    } else {
+        tracing::info!("Poller disabled, serving state from the database only");
+        InitialState::default()
+    };
+
+    let miners_loaded_at = if initial.miners.is_empty() {
+        if enable_poller {
+            tracing::warn!("No miners loaded at startup, waiting for first snapshot");
+        }
+        None
+    } else {
+        if !read_only
+            && let Err(e) = database::upsert_current_miners(&db_pool, &initial.miners).await
+        {
+            tracing::error!("Failed to upsert current miners: {:?}", e);
+        }
+        Some(chrono::Utc::now().timestamp())
+    };
+
+    let (live_broadcaster, _rx) = broadcast::channel(1000);
+    let (miner_updates, _) = broadcast::channel(4096);
+
+
+    let app_state = AppState {
+        treasury: Arc::new(RwLock::new(initial.treasury)),
+        staring_round: initial.board.round_id,
+        board: Arc::new(RwLock::new(initial.board)),
+        rounds: Arc::new(RwLock::new(vec![])),
+        miners: Arc::new(RwLock::new(initial.miners)),
+        miners_loaded_at: Arc::new(RwLock::new(miners_loaded_at)),
+        live_data_broadcaster: live_broadcaster,
+        miner_updates,
+        round_finalized: tokio::sync::watch::channel(0).0,
+        live_round: Arc::new(RwLock::new(initial.round)),
+        live_deployments: Arc::new(RwLock::new(vec![])),
+        deployments_cache: Arc::new(RwLock::new(app_state::DeploymentsCache { item: HashMap::new() })),
+        db_pool,
+        read_pool,
+        db_2_pool,
+        admin_token: env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+        read_only,
+        poller_enabled: enable_poller,
+        rpc_urls: Arc::new(rpc_urls),
+        rpc_endpoint: Arc::new(AtomicUsize::new(0)),
+        slot_time_ms,
+        commitment,
+        rpc_timeout,
+        rpc_status: Arc::new(RwLock::new(RpcStatus::Ok)),
+        rpc_metrics,
+        rpc_limiter: Arc::new(RpcRateLimiter::from_env()),
+        gap_backfill_running: Arc::new(AtomicBool::new(false)),
+        unrecoverable_rounds: Arc::new(RwLock::new(HashSet::new())),
+        unique_miners_cache: Arc::new(RwLock::new(None)),
+        deploy_percentiles_cache: Arc::new(RwLock::new(HashMap::new())),
+        leaderboard_cache: Arc::new(RwLock::new(HashMap::new())),
+        leaderboard_warmed: Arc::new(AtomicBool::new(false)),
+    };
+
+    if enable_api {
+        let s = app_state.clone();
+        leaderboard_warm_system(s).await;
+    }
+
+    if enable_poller && !read_only {
+        let s = app_state.clone();
+        backfill_rounds(s, app_state.staring_round).await;
+
+        let s = app_state.clone();
+        watch_round_gaps(s).await;
+    }
+
+    if !enable_poller {
+        refresh_state_from_db(&app_state).await?;
+        let s = app_state.clone();
+        db_refresh_system(s).await;
+    }
+
+    if !read_only {
+        let s = app_state.clone();
+        snapshot_retention_system(s).await;
+
+        let s = app_state.clone();
+        optimize_system(s).await;
+
+        let s = app_state.clone();
+        wal_checkpoint_system(s).await;
+
+        let s = app_state.clone();
+        leaderboard_rank_system(s).await;
+    }
+
+    let s = app_state.clone();
+    backup_system(s).await;
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let poller = if enable_poller {
+        let s = app_state.clone();
+        Some(update_data_system(connection, s, shutdown_rx).await)
+    } else {
+        None
+    };
+
+    if enable_api {
+        if enable_poller {
+            // only feeds the live/sse endpoints
+            let s = app_state.clone();
+            watch_live_board(s).await;
+        }
+
+        let app = router(app_state.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:8080")
+            .await?;
+
+        tracing::debug!("Listening on {}", listener.local_addr()?);
+
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                shutdown_signal().await;
+                let _ = shutdown_tx.send(true);
+            })
+            .await?;
+    } else {
+        tracing::info!("API disabled, running as indexer only");
+        shutdown_signal().await;
+        let _ = shutdown_tx.send(true);
+    }
+
+    // let the poller finish the cycle it's in so a round insert isn't cut off
+    if let Some(poller) = poller {
+        let timeout_secs = env::var("SHUTDOWN_TIMEOUT_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(30);
+        if tokio::time::timeout(Duration::from_secs(timeout_secs), poller).await.is_err() {
+            tracing::warn!("Poller did not stop within {} seconds, exiting anyway", timeout_secs);
+        }
+    }
+
+    Ok(())
+}
+
+
+/// State the server starts from, read from chain when polling and filled in from the
+/// db by `refresh_state_from_db` otherwise.
+#[derive(Default)]
+struct InitialState {
+    treasury: AppTreasury,
+    board: AppBoard,
+    round: AppRound,
+    miners: Vec<AppMiner>,
+}
 
-    let treasury = if let Ok(treasury) = connection.get_account_data(&TREASURY_ADDRESS).await {
+async fn initial_state_from_chain(
+    connection: &RpcClient,
+    commitment: CommitmentConfig,
+    slot_time_ms: u64,
+    rpc_metrics: &RpcMetrics,
+) -> anyhow::Result<InitialState> {
+    let treasury = if let Ok(treasury) = connection.get_account_data(&addresses().treasury).await {
         if let Ok(treasury) = Treasury::try_from_bytes(&treasury) {
             treasury.clone()
         } else {
@@ -94,7 +296,7 @@ async fn main() -> anyhow::Result<()> {
     // Sleep between RPC Calls
     tokio::time::sleep(Duration::from_secs(1)).await;
 
-    let board = if let Ok(board) = connection.get_account_data(&BOARD_ADDRESS).await {
+    let board = if let Ok(board) = connection.get_account_data(&addresses().board).await {
         if let Ok(board) = Board::try_from_bytes(&board) {
             board.clone()
         } else {
@@ -105,7 +307,9 @@ async fn main() -> anyhow::Result<()> {
     };
     tokio::time::sleep(Duration::from_secs(1)).await;
 
-    let round = if let Ok(round) = connection.get_account_data(&round_pda(board.round_id).0).await {
+    let current_slot = connection.get_slot().await.unwrap_or(board.start_slot);
+
+    let round = if let Ok(round) = connection.get_account_data(&addresses().round(board.round_id)).await {
         if let Ok(round) = Round::try_from_bytes(&round) {
             round.clone()
         } else {
@@ -117,69 +321,108 @@ async fn main() -> anyhow::Result<()> {
     tokio::time::sleep(Duration::from_secs(1)).await;
 
     let mut miners = vec![];
-    if let Ok(miners_data_raw) = connection.get_program_accounts_with_config(
-        &ore_api::id(),
-        solana_client::rpc_config::RpcProgramAccountsConfig { 
-            filters: Some(vec![RpcFilterType::DataSize(size_of::<Miner>() as u64 + 8)]),
-            account_config: solana_client::rpc_config::RpcAccountInfoConfig {
-                encoding: Some(UiAccountEncoding::Base64),
-                data_slice: None,
-                commitment: Some(CommitmentConfig { commitment: CommitmentLevel::Confirmed }),
-                min_context_slot: None,
-            },
-            with_context: None,
-            sort_results: None
-        } 
-    ).await {
-        for miner_data in miners_data_raw {
-            if let Ok(miner) = Miner::try_from_bytes(&miner_data.1.data) {
-                let mut miner = miner.clone();
-                miner.refined_ore = infer_refined_ore(&miner, &treasury);
-                miners.push(miner.clone().into());
-            }
-        }
+    if let Ok((miners_data_raw, partitions)) = get_miner_accounts(commitment, |config| {
+        connection.get_program_accounts_with_config(&addresses().program_id, config)
+    }).await {
+        rpc_metrics.set_miner_fetch_partitions(partitions);
+        let skipped;
+        (miners, skipped) = parse_miner_accounts(&miners_data_raw, &treasury);
+        rpc_metrics.set_skipped_miner_accounts(skipped);
     }
 
-    let (live_broadcaster, _rx) = broadcast::channel(1000);
-
-
-    let app_state = AppState {
-        treasury: Arc::new(RwLock::new(treasury.into())),
-        board: Arc::new(RwLock::new(board.into())),
-        staring_round: board.round_id,
-        rounds: Arc::new(RwLock::new(vec![])),
-        miners: Arc::new(RwLock::new(miners)),
-        live_data_broadcaster: live_broadcaster,
-        live_round: Arc::new(RwLock::new(AppRound::from(round))),
-        live_deployments: Arc::new(RwLock::new(vec![])),
-        deployments_cache: Arc::new(RwLock::new(app_state::DeploymentsCache { item: HashMap::new() })),
-        db_pool,
+    let expected_miner_size = size_of::<Miner>() + 8;
+    let miner_account_size = match observed_miner_account_size(connection, commitment).await {
+        Ok(Some(size)) if size != expected_miner_size => {
+            tracing::error!(
+                "Miner accounts on chain are {} bytes but this build expects {}, the ORE program layout has changed and miners will not load",
+                size,
+                expected_miner_size
+            );
+            size
+        }
+        Ok(Some(size)) => size,
+        Ok(None) => {
+            tracing::warn!("No Miner accounts found on chain to check the layout against");
+            0
+        }
+        Err(e) => {
+            tracing::warn!("Failed to read a Miner account to check the layout: {:?}", e);
+            0
+        }
     };
+    rpc_metrics.set_miner_account_size(miner_account_size as u64);
+
+    Ok(InitialState {
+        treasury: treasury.into(),
+        board: AppBoard::new(board, current_slot, slot_time_ms),
+        round: AppRound::from(round),
+        miners,
+    })
+}
 
-    let s = app_state.clone();
-    update_data_system(connection, s).await;
-
-    let s = app_state.clone();
-    watch_live_board(&rpc_url, s).await;
-
-    let state = app_state.clone();
+/// Every route, public under both `/v1` and the deprecated unprefixed paths.
+fn router(state: AppState) -> Router {
+    let admin = Router::new()
+        .route("/admin/finalize/{round_id}", post(admin_finalize_round))
+        .route("/admin/gaps", get(admin_get_gaps))
+        .route("/admin/backup", post(admin_backup))
+        .route("/admin/round/{round_id}/refetch", post(admin_refetch_round))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_admin_token));
+
+    // fed by the poller, 501 when it's disabled
+    let live = Router::new()
+        .route("/round/longpoll", get(get_round_longpoll))
+        .route("/sse", get(sse_handler))
+        .route("/sse/deployments", get(sse_deployments_handler))
+        .route("/sse/rounds", get(sse_rounds_handler))
+        .route("/ws", get(ws_handler))
+        .route("/live/round", get(get_live_round))
+        .route("/round/live/ev", get(get_live_round_ev))
+        .route("/simulate", post(post_simulate))
+        .route("/live/deployments", get(get_live_deployments))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_poller));
 
-    let app = Router::new()
+    let api = Router::new()
         .route("/", get(root))
         .route("/treasury", get(get_treasury))
         .route("/board", get(get_board))
+        .route("/boards", get(get_boards))
         .route("/round", get(get_round))
         .route("/round/{round_id}", get(get_round_by_id))
+        .route("/round/{round_id}/full", get(get_round_full))
+        .route("/round/{round_id}/winner-proof", get(get_round_winner_proof))
+        .route("/round/{round_id}/winners", get(get_round_winners))
+        .route("/round/{round_id}/recompute", get(get_round_recompute))
+        .route("/round/{round_id}/consistency", get(get_round_consistency))
+        .route("/ready", get(get_ready))
+        .route("/health", get(get_health))
+        .route("/version", get(get_version))
+        .route("/metrics", get(get_metrics))
         .route("/miners", get(get_miners))
+        .route("/miners/count", get(get_miners_count))
+        .route("/miners/compare", get(get_miners_compare))
+        .route("/miners/checkpoint-lag", get(get_miners_checkpoint_lag))
+        .route("/miners/inactive", get(get_miners_inactive))
+        .route("/miners/new", get(get_miners_new))
+        .route("/miners/low-checkpoint-fee", get(get_miners_low_checkpoint_fee))
+        .route("/miners/24h", post(post_miners_24h_delta))
         .route("/deployments", get(get_deployments_old))
         .route("/v2/deployments", get(get_deployments))
         .route("/rounds", get(get_rounds))
         .route("/v2/rounds", get(v2_get_rounds))
         .route("/treasuries", get(get_treasuries))
+        .route("/treasury/at/{round_id}", get(get_treasury_at_round))
+        .route("/treasury/deltas", get(get_treasury_deltas))
         .route("/search/pubkey/{letters}", get(get_available_pubkeys))
         .route("/miner/latest/{pubkey}", get(get_miner_latest))
         .route("/miner/snapshot/{pubkey}", get(get_miner_snapshot))
         .route("/miner/{pubkey}", get(get_miner_history))
+        .route("/miner/{pubkey}/24h", get(get_miner_24h_delta))
+        .route("/miner/{pubkey}/rank", get(get_miner_rank))
+        .route("/miner/{pubkey}/luck", get(get_miner_luck))
+        .route("/miner/{pubkey}/nearby", get(get_miner_nearby))
+        .route("/miner/{pubkey}/series", get(get_miner_series))
+        .route("/miner/{pubkey}/history.csv", get(get_miner_history_csv))
         .route("/miner/rounds/{pubkey}", get(get_miner_rounds))
         .route("/v2/miner/rounds/{pubkey}", get(get_miner_rounds_v2))
         .route("/miner/stats/{pubkey}", get(get_miner_stats))
@@ -187,30 +430,39 @@ async fn main() -> anyhow::Result<()> {
         .route("/miner/totals/ore", get(get_miner_totals_ore))
         .route("/leaderboard", get(get_leaderboard))
         .route("/leaderboard/ore", get(get_leaderboard_ore))
+        .route("/leaderboard/refinement", get(get_refinement_leaderboard))
+        .route("/leaderboard/movers", get(get_leaderboard_movers))
         .route("/leaderboard/latest-rounds", get(get_leaderboard_latest_rounds))
         .route("/leaderboard/latest-rounds/ore", get(get_leaderboard_latest_rounds_ore))
         .route("/leaderboard/all-time", get(get_leaderboard_all_time))
         .route("/leaderboard/all-time/ore", get(get_leaderboard_all_time_ore))
-        .route("/sse", get(sse_handler))
-        .route("/sse/deployments", get(sse_deployments_handler))
-        .route("/sse/rounds", get(sse_rounds_handler))
-        .route("/live/round", get(get_live_round))
-        .route("/live/deployments", get(get_live_deployments))
+        .route("/stats/players-per-round", get(get_players_per_round))
+        .route("/stats/active-miners", get(get_active_miners_per_round))
+        .route("/stats/daily", get(get_daily_stats))
+        .route("/stats/hourly-activity", get(get_hourly_activity))
+        .route("/stats/round-rate", get(get_round_rate))
+        .route("/stats/rent-payers", get(get_top_rent_payers))
+        .route("/stats/unique-miners", get(get_unique_miners))
+        .route("/stats/admin-fees", get(get_admin_fees))
+        .route("/stats/square-popularity", get(get_square_popularity))
+        .route("/stats/square-heatmap", get(get_square_heatmap))
+        .route("/stats/square-edge", get(get_square_edge))
+        .route("/stats/net-flow", get(get_net_flow))
+        .route("/stats/deploy-percentiles", get(get_deploy_percentiles))
+        .route("/stats/gini", get(get_gini))
+        .route("/graphql", get(graphql::graphiql).post_service(GraphQL::new(graphql::schema(state.clone()))))
+        .merge(live)
+        .merge(admin);
+
+    // Unprefixed routes are kept as deprecated aliases of /v1
+    Router::new()
+        .nest("/v1", api.clone())
+        .merge(api.layer(middleware::from_fn(mark_deprecated)))
+        .layer(middleware::from_fn(bigint_mode))
         .layer(middleware::from_fn(log_request_time))
-        .with_state(state);
-
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:8080")
-        .await?;
-
-    tracing::debug!("Listening on {}", listener.local_addr()?);
-
-    //axum::serve(listener, app).with_graceful_shutdown(shutdown_signal()).await?;
-    axum::serve(listener, app).await?;
-
-    Ok(())
+        .with_state(state)
 }
 
-
 async fn log_request_time(
     req: Request<Body>,
     next: Next,
@@ -241,51 +493,407 @@ async fn log_request_time(
     Ok(response)
 }
 
+async fn mark_deprecated(
+    req: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let mut response = next.run(req).await;
+    response.headers_mut().insert("deprecation", HeaderValue::from_static("true"));
+    response.headers_mut().append(header::LINK, HeaderValue::from_static("</v1>; rel=\"successor-version\""));
+    response
+}
+
+async fn require_poller(
+    State(state): State<AppState>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response<Body>, AppError> {
+    if !state.poller_enabled {
+        return Err(AppError::Disabled("live data is unavailable, the poller is disabled on this instance".into()));
+    }
+    Ok(next.run(req).await)
+}
+
+async fn require_admin_token(
+    State(state): State<AppState>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response<Body>, AppError> {
+    // Admin routes are disabled entirely when no token is configured
+    let token = state.admin_token.as_deref().ok_or(AppError::Unauthorized)?;
+
+    let provided = req
+        .headers()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    if provided != Some(token) {
+        return Err(AppError::Unauthorized);
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Path extractor for `{pubkey}` that rejects anything that isn't a base58 pubkey with a 400.
+pub struct MinerPubkey(pub String);
+
+impl<S: Send + Sync> FromRequestParts<S> for MinerPubkey {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(pubkey) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| AppError::BadRequest(e.body_text()))?;
+        Pubkey::from_str(&pubkey)
+            .map(|p| MinerPubkey(p.to_string()))
+            .map_err(|_| AppError::InvalidPubkey(pubkey))
+    }
+}
+
 async fn root() -> &'static str {
     "ORE"
 }
 
+/// Applies an endpoint's default and cap to the client's limit/offset.
+/// A limit above the cap is lowered to it, but an explicit limit below 1 or a
+/// negative offset is a 400 instead of being silently clamped.
+///
+/// Caps (default/max): miners 2500/2500, treasuries 2000/2000, treasury deltas 100/2000, miner history 1200/2000,
+/// rounds, boards and leaderboards (incl. refinement) 100/2000, miner rounds 10/100 (v2 100/100),
+/// stats `rounds` 100/10000, stats `days` 30/365, rent payers 25/500.
+fn normalize_pagination(limit: Option<i64>, offset: Option<i64>, default: i64, max: i64) -> Result<(i64, i64), AppError> {
+    let limit = match limit {
+        None => default,
+        Some(l) if l < 1 => return Err(AppError::BadRequest(format!("limit must be >= 1, got {l}"))),
+        Some(l) => l.min(max),
+    };
+    let offset = match offset {
+        None => 0,
+        Some(o) if o < 0 => return Err(AppError::BadRequest(format!("offset must be >= 0, got {o}"))),
+        Some(o) => o,
+    };
+    Ok((limit, offset))
+}
+
+/// RFC 5988 `Link` header with first/prev/next for offset pagination, pointing at the
+/// original request path with `limit`/`offset` replaced and other params kept. `next` is
+/// left out once a page comes back short; `returned` is None for streamed pages, where
+/// the count isn't known up front.
+fn pagination_links(uri: &Uri, limit: i64, offset: i64, returned: Option<usize>) -> String {
+    let page = |offset: i64| {
+        let mut params: Vec<String> = uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|kv| !kv.is_empty() && !kv.starts_with("limit=") && !kv.starts_with("offset="))
+            .map(str::to_string)
+            .collect();
+        params.push(format!("limit={limit}&offset={offset}"));
+        format!("<{}?{}>", uri.path(), params.join("&"))
+    };
+
+    let mut links = vec![format!("{}; rel=\"first\"", page(0))];
+    if offset > 0 {
+        links.push(format!("{}; rel=\"prev\"", page((offset - limit).max(0))));
+    }
+    if returned.is_none_or(|n| n as i64 >= limit) {
+        links.push(format!("{}; rel=\"next\"", page(offset + limit)));
+    }
+    links.join(", ")
+}
+
 #[derive(Debug, Deserialize)]
 struct MinersPagination {
     limit: Option<i64>,
     offset: Option<i64>,
     order_by: Option<String>,
+    /// Raw base units, or whole ORE with `format=decimal`.
+    min_unclaimed_ore: Option<String>,
+    /// Raw lamports, or whole SOL with `format=decimal`.
+    min_unclaimed_sol: Option<String>,
+    format: Option<String>,
+    /// Only miners deployed in the current round.
+    active: Option<bool>,
+}
+
+const ORE_DECIMALS: u32 = 11;
+const SOL_DECIMALS: u32 = 9;
+
+/// Parses a reward threshold query param into base units.
+fn parse_threshold(name: &str, value: Option<&str>, decimal: bool, decimals: u32) -> Result<Option<i64>, AppError> {
+    let Some(v) = value else {
+        return Ok(None);
+    };
+    let parsed = if decimal {
+        v.parse::<f64>()
+            .ok()
+            .filter(|f| f.is_finite() && *f >= 0.0)
+            .map(|f| (f * 10f64.powi(decimals as i32)).round() as i64)
+    } else {
+        v.parse::<i64>().ok().filter(|n| *n >= 0)
+    };
+    parsed
+        .map(Some)
+        .ok_or_else(|| AppError::BadRequest(format!("invalid {name}: {v}")))
 }
 
 async fn get_miners(
     State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
     Query(p): Query<MinersPagination>,
-) -> Result<Json<Vec<AppMiner>>, AppError> {
-    let limit = p.limit.unwrap_or(2500).max(1).min(2500) as usize;
-    let offset = p.offset.unwrap_or(0).max(0) as usize;
-    let miners = state.miners.clone();
-    let reader = miners.read().await;
-    let mut miners = reader.clone();
+) -> Result<([(header::HeaderName, String); 2], Json<Vec<AppMiner>>), AppError> {
+    let (limit, offset) = normalize_pagination(p.limit, p.offset, 2500, 2500)?;
+    let freshness = miners_freshness(&state).await;
+    let order = match p.order_by.as_deref() {
+        Some("unclaimed_sol") => Some(CurrentMinerOrder::UnclaimedSol),
+        Some("unclaimed_ore") => Some(CurrentMinerOrder::UnclaimedOre),
+        Some("refined_ore") => Some(CurrentMinerOrder::RefinedOre),
+        Some("total_deployed") => Some(CurrentMinerOrder::TotalDeployed),
+        Some("round_id") => Some(CurrentMinerOrder::RoundId),
+        _ => None,
+    };
+    let decimal = match p.format.as_deref() {
+        None | Some("raw") => false,
+        Some("decimal") => true,
+        Some(f) => return Err(AppError::BadRequest(format!("unknown format: {f}"))),
+    };
+    let filter = CurrentMinerFilter {
+        min_rewards_ore: parse_threshold("min_unclaimed_ore", p.min_unclaimed_ore.as_deref(), decimal, ORE_DECIMALS)?,
+        min_rewards_sol: parse_threshold("min_unclaimed_sol", p.min_unclaimed_sol.as_deref(), decimal, SOL_DECIMALS)?,
+        round_id: if p.active.unwrap_or(false) {
+            Some(state.board.read().await.round_id as i64)
+        } else {
+            None
+        },
+    };
+    let miners = database::get_current_miners(&state.db_pool, filter, order, limit, offset).await?;
+    let link = pagination_links(&uri, limit, offset, Some(miners.len()));
+    Ok(([(header::HeaderName::from_static("x-data-freshness"), freshness), (header::LINK, link)], Json(miners)))
+}
+
+#[derive(Debug, Deserialize)]
+struct MinersCountQuery {
+    all_time: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MinersCount {
+    pub count: usize,
+    pub all_time: Option<i64>,
+}
+
+async fn get_miners_count(
+    State(state): State<AppState>,
+    Query(q): Query<MinersCountQuery>,
+) -> Result<Json<MinersCount>, AppError> {
+    let count = state.miners.read().await.len();
+    let all_time = if q.all_time.unwrap_or(false) {
+        Some(database::get_all_time_miner_count(&state.db_pool).await?)
+    } else {
+        None
+    };
+    Ok(Json(MinersCount { count, all_time }))
+}
+
+#[derive(Debug, Deserialize)]
+struct CheckpointLagQuery {
+    threshold: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MinerCheckpointLag {
+    pub pubkey: String,
+    pub checkpoint_id: u64,
+    pub round_id: u64,
+    pub lag: u64,
+    pub checkpoint_fee: u64,
+}
+
+/// Loaded miners whose last checkpoint is more than `threshold` (default 10) rounds
+/// behind the current round, largest lag first.
+async fn get_miners_checkpoint_lag(
+    State(state): State<AppState>,
+    Query(q): Query<CheckpointLagQuery>,
+) -> Json<Vec<MinerCheckpointLag>> {
+    let threshold = q.threshold.unwrap_or(10);
+    let round_id = state.board.read().await.round_id;
+    let reader = state.miners.read().await;
+    let mut lagging: Vec<MinerCheckpointLag> = reader
+        .iter()
+        .filter_map(|m| {
+            let lag = round_id.saturating_sub(m.checkpoint_id);
+            (lag > threshold).then(|| MinerCheckpointLag {
+                pubkey: m.authority.clone(),
+                checkpoint_id: m.checkpoint_id,
+                round_id,
+                lag,
+                checkpoint_fee: m.checkpoint_fee,
+            })
+        })
+        .collect();
     drop(reader);
-    if miners.len() > 0 {
-        match p.order_by {
-            Some(v) => {
-                if v.eq("unclaimed_sol") {
-                    miners.sort_by(|a, b| b.rewards_sol.partial_cmp(&a.rewards_sol).unwrap());
-                } else if v.eq("unclaimed_ore") {
-                    miners.sort_by(|a, b| b.rewards_ore.partial_cmp(&a.rewards_ore).unwrap());
-                } else if v.eq("refined_ore") {
-                    miners.sort_by(|a, b| b.refined_ore.partial_cmp(&a.refined_ore).unwrap());
-                } else if v.eq("total_deployed") {
-                    miners.sort_by(|a, b| b.total_deployed.partial_cmp(&a.total_deployed).unwrap());
-                } else if v.eq("round_id") {
-                    miners.sort_by(|a, b| b.round_id.partial_cmp(&a.round_id).unwrap());
-                }
-            },
-            None => {
-                // No ordering
-            }
-        }
-        let start = offset.min(miners.len() - 2);
-        let end = start + limit.min(miners.len() - 1 - start);
-        return Ok(Json(miners[start..end].to_vec()));
+    lagging.sort_by_key(|m| std::cmp::Reverse(m.lag));
+    Json(lagging)
+}
+
+#[derive(Debug, Deserialize)]
+struct MinerLifecycleQuery {
+    rounds: Option<i64>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// Miners whose last played round is more than `rounds` (default 100) behind the
+/// current board round, longest inactive first.
+async fn get_miners_inactive(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(q): Query<MinerLifecycleQuery>,
+) -> Result<([(header::HeaderName, String); 1], Json<Vec<InactiveMiner>>), AppError> {
+    let (rounds, _) = normalize_pagination(q.rounds, None, 100, 100_000)?;
+    let (limit, offset) = normalize_pagination(q.limit, q.offset, 100, 2000)?;
+    let round_id = state.board.read().await.round_id as i64;
+    let rows = database::get_inactive_miners(&state.db_pool, round_id, rounds, limit, offset).await?;
+    let link = pagination_links(&uri, limit, offset, Some(rows.len()));
+    Ok(([(header::LINK, link)], Json(rows)))
+}
+
+/// Miners whose first deployment is within the last `rounds` (default 100) stored
+/// rounds, newest first.
+async fn get_miners_new(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(q): Query<MinerLifecycleQuery>,
+) -> Result<([(header::HeaderName, String); 1], Json<Vec<NewMiner>>), AppError> {
+    let (rounds, _) = normalize_pagination(q.rounds, None, 100, 100_000)?;
+    let (limit, offset) = normalize_pagination(q.limit, q.offset, 100, 2000)?;
+    let rows = database::get_new_miners(&state.db_pool, rounds, limit, offset).await?;
+    let link = pagination_links(&uri, limit, offset, Some(rows.len()));
+    Ok(([(header::LINK, link)], Json(rows)))
+}
+
+#[derive(Debug, Deserialize)]
+struct LowCheckpointFeeQuery {
+    min: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MinerCheckpointFee {
+    pub pubkey: String,
+    pub checkpoint_fee: u64,
+    pub checkpoint_id: u64,
+}
+
+/// Lamports, the default `min` for /miners/low-checkpoint-fee.
+const DEFAULT_MIN_CHECKPOINT_FEE: u64 = 10_000;
+
+/// Loaded miners whose reserved checkpoint fee is below `min` lamports, lowest first.
+async fn get_miners_low_checkpoint_fee(
+    State(state): State<AppState>,
+    Query(q): Query<LowCheckpointFeeQuery>,
+) -> Json<Vec<MinerCheckpointFee>> {
+    let min = q.min.unwrap_or(DEFAULT_MIN_CHECKPOINT_FEE);
+    let reader = state.miners.read().await;
+    let mut low: Vec<MinerCheckpointFee> = reader
+        .iter()
+        .filter(|m| m.checkpoint_fee < min)
+        .map(|m| MinerCheckpointFee {
+            pubkey: m.authority.clone(),
+            checkpoint_fee: m.checkpoint_fee,
+            checkpoint_id: m.checkpoint_id,
+        })
+        .collect();
+    drop(reader);
+    low.sort_by_key(|m| m.checkpoint_fee);
+    Json(low)
+}
+
+/// Age in seconds of the in-memory miners, or "loading" if they were never loaded.
+async fn miners_freshness(state: &AppState) -> String {
+    let loaded_at = *state.miners_loaded_at.read().await;
+    match loaded_at {
+        Some(ts) => (chrono::Utc::now().timestamp() - ts).max(0).to_string(),
+        None => "loading".to_string(),
     }
-    Ok(Json(miners))
+}
+
+async fn get_ready(
+    State(state): State<AppState>,
+) -> (StatusCode, &'static str) {
+    let warmed = state.leaderboard_warmed.load(Ordering::Relaxed);
+    if warmed && state.miners_loaded_at.read().await.is_some() {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "loading")
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Health {
+    program_id: String,
+    cluster: &'static str,
+    rpc: RpcStatus,
+    /// Host of the active rpc endpoint, without path or query so api keys aren't exposed.
+    rpc_endpoint: String,
+    /// True while outbound rpc calls are waiting on RPC_MAX_CALLS_PER_SEC.
+    rpc_throttled: bool,
+    /// The current round is well past its end slot without the round id advancing.
+    round_stalled: bool,
+    miners_loaded: bool,
+    /// Accounts in the last miners fetch that failed to parse as a Miner, non-zero
+    /// usually means the program layout changed.
+    skipped_miner_accounts: u64,
+}
+
+async fn get_health(
+    State(state): State<AppState>,
+) -> Json<Health> {
+    Json(Health {
+        program_id: addresses().program_id.to_string(),
+        cluster: &addresses().cluster,
+        rpc: *state.rpc_status.read().await,
+        rpc_endpoint: state.rpc_url().split(['/', '?']).next().unwrap_or_default().to_string(),
+        rpc_throttled: state.rpc_limiter.is_throttled().await,
+        round_stalled: state.board.read().await.stalled,
+        miners_loaded: state.miners_loaded_at.read().await.is_some(),
+        skipped_miner_accounts: state.rpc_metrics.skipped_miner_accounts(),
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct Version {
+    version: &'static str,
+    git_sha: &'static str,
+    /// RFC 3339, set by build.rs.
+    build_time: String,
+    program_id: String,
+    cluster: &'static str,
+}
+
+async fn get_version() -> Json<Version> {
+    let build_time = env!("BUILD_TIME")
+        .parse::<i64>()
+        .ok()
+        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_default();
+    Json(Version {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("GIT_SHA"),
+        build_time,
+        program_id: addresses().program_id.to_string(),
+        cluster: &addresses().cluster,
+    })
+}
+
+async fn get_metrics(
+    State(state): State<AppState>,
+) -> ([(header::HeaderName, &'static str); 1], String) {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.rpc_metrics.render() + &state.rpc_limiter.render(),
+    )
 }
 
 async fn get_treasury(
@@ -298,24 +906,52 @@ async fn get_treasury(
 }
 
 
+/// Returns 304 when the client's If-None-Match matches, otherwise the json body with an ETag.
+fn conditional_json<T: Serialize>(headers: &HeaderMap, etag: String, data: T) -> axum::response::Response {
+    let matches = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(',').any(|t| t.trim() == etag || t.trim() == "*"))
+        .unwrap_or(false);
+
+    let etag_header = [(header::ETAG, etag)];
+    if matches {
+        (StatusCode::NOT_MODIFIED, etag_header).into_response()
+    } else {
+        (etag_header, Json(data)).into_response()
+    }
+}
+
 async fn get_board(
     State(state): State<AppState>,
-) -> Result<Json<AppBoard>, AppError> {
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
     let r = state.board.clone();
     let lock = r.read().await;
     let data = lock.clone();
-    Ok(Json(data))
+    drop(lock);
+    Ok(conditional_json(&headers, data.etag(), data))
+}
+
+async fn get_boards(
+    State(state): State<AppState>,
+    Query(p): Query<Pagination>,
+) -> Result<Json<Vec<DbBoardSnapshot>>, AppError> {
+    let (limit, offset) = normalize_pagination(p.limit, p.offset, 100, 2000)?;
+    let boards = database::get_board_snapshots(&state.db_pool, limit, offset).await?;
+    Ok(Json(boards))
 }
 
 async fn get_round(
     State(state): State<AppState>,
-) -> Result<Json<AppRound>, AppError> {
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
     let r = state.rounds.clone();
     let lock = r.read().await;
-    let data = lock.clone();
+    let data = lock.last().cloned();
     drop(lock);
-    if let Some(d) = data.last() {
-        Ok(Json(d.clone()))
+    if let Some(d) = data {
+        Ok(conditional_json(&headers, d.etag(), d))
     } else {
         Err(anyhow!("Failed to get last round").into())
     }
@@ -330,6 +966,263 @@ async fn get_round_by_id(
     Ok(Json(round))
 }
 
+#[derive(Debug, Serialize)]
+pub struct RoundFull {
+    pub round: RoundRow,
+    pub deployments_count: usize,
+    pub deployments: Vec<GetDeployment>,
+}
+
+async fn get_round_full(
+    Path(p): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<RoundFull>, AppError> {
+    let round = database::get_round_by_id(&state.db_pool, p).await?
+        .into_iter()
+        .next()
+        .ok_or(AppError::NotFound)?;
+    let deployments = get_deployments_by_round(&state.db_pool, p).await?;
+
+    Ok(Json(RoundFull {
+        round,
+        deployments_count: deployments.len(),
+        deployments,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoundWinners {
+    pub round_id: i64,
+    pub top_miner: String,
+    /// The top miner reward was shared pro-rata across the winning square.
+    pub split: bool,
+    pub winners: Vec<RoundWinner>,
+}
+
+/// Every miner that earned ORE in the round, which `top_miner` alone can't show for split rounds.
+async fn get_round_winners(
+    Path(p): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<RoundWinners>, AppError> {
+    let round = database::get_round_by_id(&state.db_pool, p).await?
+        .into_iter()
+        .next()
+        .ok_or(AppError::NotFound)?;
+    let winners = database::get_round_winners(&state.db_pool, p).await?;
+
+    Ok(Json(RoundWinners {
+        round_id: round.id,
+        split: round.is_split,
+        top_miner: round.top_miner,
+        winners,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct WinnerInterval {
+    pub pubkey: String,
+    pub amount: i64,
+    /// `[start, end)` on the winning square, None if cumulative wasn't recorded for this round.
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub contains_sample: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WinnerProof {
+    pub round_id: i64,
+    #[serde(with = "hex_bytes")]
+    pub slot_hash: Vec<u8>,
+    pub winning_square: i64,
+    /// Sum of stored deployments on the winning square.
+    pub square_deployed: i64,
+    pub split: bool,
+    /// None when the round was split or nothing was deployed on the winning square.
+    pub sample: Option<u64>,
+    pub top_miner: String,
+    pub intervals: Vec<WinnerInterval>,
+    pub verification: &'static str,
+}
+
+const WINNER_PROOF_VERIFICATION: &str = "rng is derived from slot_hash exactly as the ore program does (Round::rng). \
+winning_square = rng % 25 and sample = reverse_bits(rng) % square_deployed (Round::top_miner_sample). \
+The top miner is the one whose [start, end) interval contains sample. \
+square_deployed is summed from stored deployments, so it only matches the on-chain value if every deployment on the square was recorded.";
+
+/// Everything needed to recompute the top miner of a round off-chain.
+async fn get_round_winner_proof(
+    Path(p): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<WinnerProof>, AppError> {
+    let row = database::get_round_by_id(&state.db_pool, p).await?
+        .into_iter()
+        .next()
+        .ok_or(AppError::NotFound)?;
+    if !(0..25).contains(&row.winning_square) {
+        return Err(AppError::BadRequest(format!("round {p} has no winning square")));
+    }
+    let ws = row.winning_square as usize;
+
+    let deployments = database::get_square_deployments(&state.db_pool, p, row.winning_square).await?;
+    let square_deployed: i64 = deployments.iter().map(|d| d.amount).sum();
+    let split = row.is_split;
+
+    let mut round: Round = bytemuck::Zeroable::zeroed();
+    round.slot_hash = row.slot_hash.as_slice().try_into().map_err(|_| anyhow!("stored slot_hash is not 32 bytes"))?;
+    round.deployed[ws] = square_deployed as u64;
+    let sample = match round.rng() {
+        Some(r) if !split && square_deployed > 0 => Some(round.top_miner_sample(r, ws)),
+        _ => None,
+    };
+
+    let intervals = deployments
+        .into_iter()
+        .map(|d| {
+            let end = d.cumulative.map(|c| c + d.amount);
+            let contains_sample = match (sample, d.cumulative, end) {
+                (Some(s), Some(start), Some(end)) => (s as i64) >= start && (s as i64) < end,
+                _ => false,
+            };
+            WinnerInterval {
+                pubkey: d.pubkey,
+                amount: d.amount,
+                start: d.cumulative,
+                end,
+                contains_sample,
+            }
+        })
+        .collect();
+
+    Ok(Json(WinnerProof {
+        round_id: row.id,
+        slot_hash: row.slot_hash,
+        winning_square: row.winning_square,
+        square_deployed,
+        split,
+        sample,
+        top_miner: row.top_miner,
+        intervals,
+        verification: WINNER_PROOF_VERIFICATION,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoundRecompute {
+    pub stored: i64,
+    /// None if the stored slot_hash can't produce an rng.
+    pub recomputed: Option<usize>,
+    #[serde(rename = "match")]
+    pub matches: bool,
+}
+
+/// Re-derives the winning square from the stored slot_hash to catch rows where they diverge.
+async fn get_round_recompute(
+    Path(p): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<RoundRecompute>, AppError> {
+    let row = database::get_round_by_id(&state.db_pool, p).await?
+        .into_iter()
+        .next()
+        .ok_or(AppError::NotFound)?;
+    let recomputed = recompute_winning_square(&row.slot_hash);
+    Ok(Json(RoundRecompute {
+        stored: row.winning_square,
+        recomputed,
+        matches: recomputed.map(|ws| ws as i64) == Some(row.winning_square),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct LongPollQuery {
+    since: i64,
+    /// Seconds to hold the request, default 30, at most 60.
+    timeout: Option<u64>,
+}
+
+/// Returns the latest stored round once one newer than `since` exists, or 204 if none
+/// is finalized before the timeout.
+async fn get_round_longpoll(
+    State(state): State<AppState>,
+    Query(q): Query<LongPollQuery>,
+) -> Result<axum::response::Response, AppError> {
+    // subscribe before checking the db so a round stored in between isn't missed
+    let mut finalized = state.round_finalized.subscribe();
+    let timeout = Duration::from_secs(q.timeout.unwrap_or(30).clamp(1, 60));
+
+    let latest = database::get_rounds(&state.db_pool, 1, 0, None).await?.into_iter().next();
+    if let Some(row) = latest.filter(|r| r.id > q.since) {
+        return Ok(Json(row).into_response());
+    }
+
+    let since = q.since;
+    // matched in one statement so the non-Send watch::Ref is dropped before the next await
+    let arrived = matches!(
+        tokio::time::timeout(timeout, finalized.wait_for(|id| *id as i64 > since)).await,
+        Ok(Ok(_))
+    );
+    if !arrived {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+    let row = database::get_rounds(&state.db_pool, 1, 0, None).await?.into_iter().next().ok_or(AppError::NotFound)?;
+    Ok(Json(row).into_response())
+}
+
+#[derive(Debug, Serialize)]
+struct ConsistencyMismatch {
+    check: &'static str,
+    expected: i64,
+    actual: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct RoundConsistency {
+    round_id: i64,
+    deployments: i64,
+    consistent: bool,
+    mismatches: Vec<ConsistencyMismatch>,
+    /// Checks that couldn't run, e.g. the winning square isn't resolved yet.
+    skipped: Vec<&'static str>,
+}
+
+/// Compares the stored deployments against the stored round, to catch deployment
+/// chunks that failed to insert. Winning square SOL allows one lamport of rounding
+/// per winning deployment since each pro-rata share is floored.
+async fn get_round_consistency(
+    Path(p): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<RoundConsistency>, AppError> {
+    let row = database::get_round_by_id(&state.db_pool, p).await?
+        .into_iter()
+        .next()
+        .ok_or(AppError::NotFound)?;
+    let sums = database::get_round_deployment_sums(&state.db_pool, p, row.winning_square).await?;
+
+    let mut mismatches = vec![];
+    if sums.total_amount != row.total_deployed {
+        mismatches.push(ConsistencyMismatch { check: "total_deployed", expected: row.total_deployed, actual: sums.total_amount });
+    }
+    let mut skipped = vec![];
+    if !(0..25).contains(&row.winning_square) {
+        skipped.push("winning_sol_earned");
+    } else if sums.winning_count > 0 {
+        let expected = sums.winning_amount - sums.winning_admin_fee + row.total_winnings;
+        if (expected - sums.winning_sol_earned).abs() > sums.winning_count {
+            mismatches.push(ConsistencyMismatch { check: "winning_sol_earned", expected, actual: sums.winning_sol_earned });
+        }
+    }
+    if sums.losing_sol_earned != 0 {
+        mismatches.push(ConsistencyMismatch { check: "losing_sol_earned", expected: 0, actual: sums.losing_sol_earned });
+    }
+
+    Ok(Json(RoundConsistency {
+        round_id: p,
+        deployments: sums.deployments,
+        consistent: mismatches.is_empty(),
+        mismatches,
+        skipped,
+    }))
+}
+
 #[derive(Debug, Deserialize)]
 struct RoundsPagination {
     limit: Option<i64>,
@@ -339,12 +1232,13 @@ struct RoundsPagination {
 
 async fn get_rounds(
     State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
     Query(p): Query<RoundsPagination>,
-) -> Result<Json<Vec<RoundRow>>, AppError> {
-    let limit = p.limit.unwrap_or(100).max(1).min(2000);
-    let offset = p.offset.unwrap_or(0).max(0);
+) -> Result<([(header::HeaderName, String); 1], Json<Vec<RoundRow>>), AppError> {
+    let (limit, offset) = normalize_pagination(p.limit, p.offset, 100, 2000)?;
     let rounds = database::get_rounds(&state.db_pool, limit, offset, p.ml).await?;
-    Ok(Json(rounds))
+    let link = pagination_links(&uri, limit, offset, Some(rounds.len()));
+    Ok(([(header::LINK, link)], Json(rounds)))
 }
 
 #[derive(Debug, Deserialize)]
@@ -358,7 +1252,7 @@ async fn v2_get_rounds(
     State(state): State<AppState>,
     Query(p): Query<V2RoundsPagination>,
 ) -> Result<Json<Vec<RoundRow>>, AppError> {
-    let limit = p.limit.unwrap_or(100).max(1).min(2000);
+    let (limit, _) = normalize_pagination(p.limit, None, 100, 2000)?;
     if let Some(rid) = p.round_id {
         let rounds = database::get_rounds_via_cursor(&state.db_pool, limit, rid, p.ml).await?;
         Ok(Json(rounds))
@@ -372,40 +1266,345 @@ async fn get_treasuries(
     State(state): State<AppState>,
     Query(p): Query<RoundsPagination>,
 ) -> Result<Json<Vec<DbTreasury>>, AppError> {
-    let limit = p.limit.unwrap_or(2000).max(1).min(2000);
-    let offset = p.offset.unwrap_or(0).max(0);
+    let (limit, offset) = normalize_pagination(p.limit, p.offset, 2000, 2000)?;
     let treasuries = database::get_treasuries(&state.db_pool, limit, offset).await?;
     Ok(Json(treasuries))
 }
 
+#[derive(Debug, Deserialize)]
+struct TreasuryDeltasQuery {
+    limit: Option<i64>,
+}
+
+async fn get_treasury_deltas(
+    State(state): State<AppState>,
+    Query(q): Query<TreasuryDeltasQuery>,
+) -> Result<Json<Vec<TreasuryDelta>>, AppError> {
+    let (limit, _) = normalize_pagination(q.limit, None, 100, 2000)?;
+    let deltas = database::get_treasury_deltas(&state.db_pool, limit).await?;
+    Ok(Json(deltas))
+}
+
+async fn get_treasury_at_round(
+    Path(round_id): Path<i64>,
+    State(state): State<AppState>,
+) -> Result<Json<DbTreasury>, AppError> {
+    database::get_treasury_at_round(&state.db_pool, round_id).await?
+        .map(Json)
+        .ok_or(AppError::NotFound)
+}
+
+#[derive(Debug, Deserialize)]
+struct StatsRoundsQuery {
+    rounds: Option<i64>,
+}
+
+async fn get_players_per_round(
+    State(state): State<AppState>,
+    Query(q): Query<StatsRoundsQuery>,
+) -> Result<Json<Vec<RoundPlayers>>, AppError> {
+    let (rounds, _) = normalize_pagination(q.rounds, None, 100, 10_000)?;
+    let rows = database::get_players_per_round(&state.db_pool, rounds).await?;
+    Ok(Json(rows))
+}
+
+async fn get_active_miners_per_round(
+    State(state): State<AppState>,
+    Query(q): Query<StatsRoundsQuery>,
+) -> Result<Json<Vec<RoundActiveMiners>>, AppError> {
+    let (rounds, _) = normalize_pagination(q.rounds, None, 100, 10_000)?;
+    let rows = database::get_active_miners_per_round(&state.db_pool, rounds).await?;
+    Ok(Json(rows))
+}
+
+#[derive(Debug, Deserialize)]
+struct DailyStatsQuery {
+    days: Option<i64>,
+}
+
+async fn get_daily_stats(
+    State(state): State<AppState>,
+    Query(q): Query<DailyStatsQuery>,
+) -> Result<Json<Vec<DailyStats>>, AppError> {
+    let (days, _) = normalize_pagination(q.days, None, 30, 365)?;
+    let rows = database::get_daily_stats(&state.db_pool, days).await?;
+    Ok(Json(rows))
+}
+
+async fn get_hourly_activity(
+    State(state): State<AppState>,
+    Query(q): Query<DailyStatsQuery>,
+) -> Result<Json<Vec<HourlyActivity>>, AppError> {
+    let (days, _) = normalize_pagination(q.days, None, 7, 365)?;
+    let rows = database::get_hourly_activity(&state.db_pool, days).await?;
+    Ok(Json(rows))
+}
+
+#[derive(Debug, Deserialize)]
+struct RoundRateQuery {
+    hours: Option<i64>,
+}
+
+async fn get_round_rate(
+    State(state): State<AppState>,
+    Query(q): Query<RoundRateQuery>,
+) -> Result<Json<RoundRate>, AppError> {
+    let (hours, _) = normalize_pagination(q.hours, None, 24, 24 * 30)?;
+    let rate = database::get_round_rate(&state.db_pool, hours).await?;
+    Ok(Json(rate))
+}
+
+const UNIQUE_MINERS_TTL: Duration = Duration::from_secs(60);
+
+async fn get_unique_miners(
+    State(state): State<AppState>,
+) -> Result<Json<UniqueMiners>, AppError> {
+    if let Some((at, cached)) = state.unique_miners_cache.read().await.as_ref()
+        && at.elapsed() < UNIQUE_MINERS_TTL
+    {
+        return Ok(Json(cached.clone()));
+    }
+    let fresh = database::get_unique_miners(&state.db_pool).await?;
+    *state.unique_miners_cache.write().await = Some((Instant::now(), fresh.clone()));
+    Ok(Json(fresh))
+}
+
+const DEPLOY_PERCENTILES_TTL: Duration = Duration::from_secs(60);
+
+async fn get_deploy_percentiles(
+    State(state): State<AppState>,
+    Query(q): Query<StatsRoundsQuery>,
+) -> Result<Json<DeployPercentiles>, AppError> {
+    let (rounds, _) = normalize_pagination(q.rounds, None, 100, 10_000)?;
+    if let Some((at, cached)) = state.deploy_percentiles_cache.read().await.get(&rounds)
+        && at.elapsed() < DEPLOY_PERCENTILES_TTL
+    {
+        return Ok(Json(cached.clone()));
+    }
+    let fresh = database::get_deploy_percentiles(&state.db_pool, rounds).await?;
+    let mut cache = state.deploy_percentiles_cache.write().await;
+    cache.retain(|_, (at, _)| at.elapsed() < DEPLOY_PERCENTILES_TTL);
+    cache.insert(rounds, (Instant::now(), fresh.clone()));
+    Ok(Json(fresh))
+}
+
+#[derive(Debug, Deserialize)]
+struct GiniQuery {
+    /// Round window, all time when omitted.
+    rounds: Option<i64>,
+    /// deployed (default) or earned.
+    by: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Gini {
+    gini: f64,
+    miners: usize,
+}
+
+/// Gini coefficient of values sorted ascending: 0 is perfect equality, 1 is one miner holding everything.
+fn gini_coefficient(sorted: &[i64]) -> f64 {
+    let n = sorted.len() as f64;
+    let total: f64 = sorted.iter().map(|v| *v as f64).sum();
+    if sorted.is_empty() || total <= 0.0 {
+        return 0.0;
+    }
+    let weighted: f64 = sorted.iter().enumerate().map(|(i, v)| (i as f64 + 1.0) * *v as f64).sum();
+    (2.0 * weighted) / (n * total) - (n + 1.0) / n
+}
+
+async fn get_gini(
+    State(state): State<AppState>,
+    Query(q): Query<GiniQuery>,
+) -> Result<Json<Gini>, AppError> {
+    let window = match q.rounds {
+        None => LeaderboardWindow::AllTime,
+        rounds => LeaderboardWindow::LastRounds(normalize_pagination(rounds, None, 100, 10_000)?.0),
+    };
+    let metric = match q.by.as_deref() {
+        None | Some("deployed") => GiniMetric::SolDeployed,
+        Some("earned") => GiniMetric::SolEarned,
+        Some(b) => return Err(AppError::BadRequest(format!("unknown by: {b}"))),
+    };
+    let totals = database::get_sorted_miner_totals(&state.db_pool, window, metric).await?;
+    Ok(Json(Gini { gini: gini_coefficient(&totals), miners: totals.len() }))
+}
+
+async fn get_top_rent_payers(
+    State(state): State<AppState>,
+    Query(p): Query<Pagination>,
+) -> Result<Json<Vec<RentPayerRow>>, AppError> {
+    let (limit, offset) = normalize_pagination(p.limit, p.offset, 25, 500)?;
+    let rows = database::get_top_rent_payers(&state.db_pool, limit, offset).await?;
+    Ok(Json(rows))
+}
+
+async fn get_admin_fees(
+    State(state): State<AppState>,
+    Query(q): Query<StatsRoundsQuery>,
+) -> Result<Json<AdminFeeTotal>, AppError> {
+    let (rounds, _) = normalize_pagination(q.rounds, None, 100, 10_000)?;
+    let total = database::get_admin_fee_total(&state.db_pool, rounds).await?;
+    Ok(Json(total))
+}
+
+async fn get_square_popularity(
+    State(state): State<AppState>,
+    Query(q): Query<StatsRoundsQuery>,
+) -> Result<Json<Vec<SquarePopularity>>, AppError> {
+    let (rounds, _) = normalize_pagination(q.rounds, None, 100, 10_000)?;
+    let squares = database::get_square_popularity(&state.db_pool, rounds).await?;
+    Ok(Json(squares))
+}
+
+/// Per-round board of SOL deployed per square, capped at 1000 rounds.
+async fn get_square_heatmap(
+    State(state): State<AppState>,
+    Query(q): Query<StatsRoundsQuery>,
+) -> Result<Json<Vec<RoundSquareDeployed>>, AppError> {
+    let (rounds, _) = normalize_pagination(q.rounds, None, 100, 1000)?;
+    let rows = database::get_square_heatmap(&state.db_pool, rounds).await?;
+    Ok(Json(rows))
+}
+
+#[derive(Debug, Serialize)]
+struct SquareEdge {
+    square_id: i64,
+    win_pct: f64,
+    /// Share of all SOL deployed in the window that went to this square.
+    deploy_share_pct: f64,
+    /// win_pct minus the fair 4% (1/25).
+    edge: f64,
+}
+
+/// Win rate against deployment share per square over the last `rounds` rounds.
+async fn get_square_edge(
+    State(state): State<AppState>,
+    Query(q): Query<StatsRoundsQuery>,
+) -> Result<Json<Vec<SquareEdge>>, AppError> {
+    let (rounds, _) = normalize_pagination(q.rounds, None, 100, 10_000)?;
+    let squares = database::get_square_popularity(&state.db_pool, rounds).await?;
+
+    let total_wins: i64 = squares.iter().map(|s| s.wins).sum();
+    let total_deployed: i64 = squares.iter().map(|s| s.total_deployed).sum();
+    let pct = |part: i64, whole: i64| if whole > 0 { part as f64 * 100.0 / whole as f64 } else { 0.0 };
+    let edges = squares
+        .into_iter()
+        .map(|s| {
+            let win_pct = pct(s.wins, total_wins);
+            SquareEdge {
+                square_id: s.square_id,
+                win_pct,
+                deploy_share_pct: pct(s.total_deployed, total_deployed),
+                edge: win_pct - 4.0,
+            }
+        })
+        .collect();
+    Ok(Json(edges))
+}
+
+#[derive(Debug, Deserialize)]
+struct NetFlowQuery {
+    /// A number of rounds, or `all` for all time.
+    rounds: Option<String>,
+}
+
+async fn get_net_flow(
+    State(state): State<AppState>,
+    Query(q): Query<NetFlowQuery>,
+) -> Result<Json<NetFlow>, AppError> {
+    let rounds = match q.rounds.as_deref() {
+        Some("all") => None,
+        other => {
+            let rounds = other
+                .map(|r| r.parse::<i64>().map_err(|_| AppError::BadRequest(format!("rounds must be a number or all, got {r}"))))
+                .transpose()?;
+            Some(normalize_pagination(rounds, None, 100, 10_000)?.0)
+        }
+    };
+    let flow = database::get_net_flow(&state.db_pool, rounds).await?;
+    Ok(Json(flow))
+}
+
+#[derive(Debug, Deserialize)]
+struct MinerHistoryQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// Snapshot id to page back from, exclusive. Replaces `offset` when set.
+    cursor: Option<i64>,
+    /// Unix seconds, inclusive
+    from: Option<i64>,
+    /// Unix seconds, inclusive
+    to: Option<i64>,
+}
+
+/// Snapshot history, newest first. A full page sets `x-next-cursor` to the last id,
+/// pass it back as `?cursor=` for the next page.
 async fn get_miner_history(
     State(state): State<AppState>,
-    Path(pubkey): Path<String>,
-    Query(p): Query<RoundsPagination>,
-) -> Result<Json<Vec<DbMinerSnapshot>>, AppError> {
-    let limit = p.limit.unwrap_or(1200).max(1).min(2000);
-    let offset = p.offset.unwrap_or(0).max(0);
-    let miners_history = database::get_miner_snapshots(&state.db_pool, pubkey, limit, offset).await?;
-    Ok(Json(miners_history))
+    MinerPubkey(pubkey): MinerPubkey,
+    Query(p): Query<MinerHistoryQuery>,
+) -> Result<(HeaderMap, Json<Vec<DbMinerSnapshot>>), AppError> {
+    if p.cursor.is_some() && p.offset.is_some() {
+        return Err(AppError::BadRequest("cursor and offset are mutually exclusive".into()));
+    }
+    let (limit, offset) = normalize_pagination(p.limit, p.offset, 1200, 2000)?;
+    let range = if p.from.is_some() || p.to.is_some() {
+        let from = p.from.unwrap_or(0);
+        let to = p.to.unwrap_or(i64::MAX);
+        if from > to {
+            return Err(AppError::BadRequest("from must be <= to".into()));
+        }
+        Some((from, to))
+    } else {
+        None
+    };
+    let miners_history = match p.cursor {
+        Some(cursor) => database::get_miner_snapshots_via_cursor(&state.db_pool, pubkey, limit, cursor, range).await?,
+        None => database::get_miner_snapshots(&state.db_pool, pubkey, limit, offset, range).await?,
+    };
+    let mut headers = HeaderMap::new();
+    if let Some(last) = miners_history.last().filter(|_| miners_history.len() as i64 == limit) {
+        headers.insert("x-next-cursor", HeaderValue::from(last.id));
+    }
+    Ok((headers, Json(miners_history)))
+}
+
+#[derive(Debug, Deserialize)]
+struct SeriesQuery {
+    bucket: Option<String>,
+}
+
+async fn get_miner_series(
+    State(state): State<AppState>,
+    MinerPubkey(pubkey): MinerPubkey,
+    Query(q): Query<SeriesQuery>,
+) -> Result<Json<Vec<SnapshotSeriesPoint>>, AppError> {
+    let bucket = match q.bucket.as_deref() {
+        None | Some("hour") => SeriesBucket::Hour,
+        Some("day") => SeriesBucket::Day,
+        Some(b) => return Err(AppError::BadRequest(format!("unknown bucket: {b}"))),
+    };
+    let series = database::get_miner_snapshot_series(&state.db_pool, pubkey, bucket).await?;
+    Ok(Json(series))
 }
 
 async fn get_miner_rounds(
     State(state): State<AppState>,
-    Path(pubkey): Path<String>,
+    MinerPubkey(pubkey): MinerPubkey,
     Query(p): Query<RoundsPagination>,
 ) -> Result<Json<Vec<RoundRow>>, AppError> {
-    let limit = p.limit.unwrap_or(10).max(1).min(100);
-    let offset = p.offset.unwrap_or(0).max(0);
+    let (limit, offset) = normalize_pagination(p.limit, p.offset, 10, 100)?;
     let rounds = database::get_miner_rounds(&state.db_pool, pubkey, limit, offset).await?;
     Ok(Json(rounds))
 }
 
 async fn get_miner_rounds_v2(
     State(state): State<AppState>,
-    Path(pubkey): Path<String>,
+    MinerPubkey(pubkey): MinerPubkey,
     Query(p): Query<V2RoundsPagination>,
 ) -> Result<Json<Vec<RoundRow>>, AppError> {
-    let limit = p.limit.unwrap_or(100).max(1).min(100);
+    let (limit, _) = normalize_pagination(p.limit, None, 100, 100)?;
     if let Some(rid) = p.round_id {
         let rounds = database::get_miner_rounds_via_cursor(&state.db_pool, pubkey, limit, rid).await?;
         Ok(Json(rounds))
@@ -482,158 +1681,479 @@ pub async fn get_deployments(
                         entry.ore_earned += d.ore_earned as u64;
                     }
 
-                    // turn the map into a vec
-                    let squished: Vec<GetDeploymentSquished> = by_pubkey.into_values().collect();
-                    Ok(Json(squished))
-                } else {
-                    let deployments = get_deployments_by_round(&state.db_pool, p.round_id as i64).await?;
+                    // turn the map into a vec
+                    let squished: Vec<GetDeploymentSquished> = by_pubkey.into_values().collect();
+                    Ok(Json(squished))
+                } else {
+                    let deployments = get_deployments_by_round(&state.db_pool, p.round_id as i64).await?;
+
+                    // group + squish
+                    let mut by_pubkey: HashMap<String, GetDeploymentSquished> = HashMap::new();
+
+                    for d in deployments {
+                        // make sure there's an entry for this pubkey
+                        let entry = by_pubkey.entry(d.pubkey.clone()).or_insert_with(|| GetDeploymentSquished {
+                            round_id: d.round_id as u64,
+                            pubkey: d.pubkey.clone(),
+                            deployments: [0u64; 25],
+                            sol_deployed: 0,
+                            sol_earned: 0,
+                            ore_earned: 0,
+                        });
+
+                        // place this deployment's amount into the correct slot
+                        // assuming square_id is 0..24
+                        let idx = d.square_id as usize;
+                        if idx < 25 {
+                            entry.deployments[idx] = d.amount as u64;
+                        }
+                        // accumulate totals
+                        entry.sol_deployed += d.amount as u64;
+                        entry.sol_earned += d.sol_earned as u64;
+                        entry.ore_earned += d.ore_earned as u64;
+                    }
+
+                    // turn the map into a vec
+                    let squished: Vec<GetDeploymentSquished> = by_pubkey.into_values().collect();
+                    let mut w = state.deployments_cache.write().await;
+                    if w.item.len() < 10 {
+                        w.item.insert(p.round_id, (squished.clone(), SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_else(|_| Duration::from_secs(0)).as_secs()));
+                    } else {
+                        tracing::warn!("Deployments cache max length reached, clearing cache...");
+                        w.item = HashMap::new();
+                        w.item.insert(p.round_id, (squished.clone(), SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_else(|_| Duration::from_secs(0)).as_secs()));
+                    }
+                    drop(w);
+
+                    Ok(Json(squished))
+                }
+            }
+            Err(e) => {
+               return Err(AppError::Sqlx(e))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Pagination {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LeaderboardQuery {
+    metric: Option<String>,
+    window: Option<String>,
+    min_rounds: Option<i64>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    direction: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LeaderboardPagination {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    direction: Option<String>,
+}
+
+/// `metric=net_sol|ore|net_ore`, `window=all|<N rounds>`. Defaults to net sol over the last 60 rounds.
+async fn get_leaderboard(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(q): Query<LeaderboardQuery>,
+) -> Result<axum::response::Response, AppError> {
+    let metric = parse_leaderboard_metric(q.metric.as_deref())?;
+    let window = parse_leaderboard_window(q.window.as_deref())?;
+    let pagination = LeaderboardPagination { limit: q.limit, offset: q.offset, direction: q.direction };
+    leaderboard(&state, &uri, metric, window, q.min_rounds, pagination).await
+}
+
+fn parse_leaderboard_metric(metric: Option<&str>) -> Result<LeaderboardMetric, AppError> {
+    match metric {
+        None | Some("net_sol") => Ok(LeaderboardMetric::NetSol),
+        Some("ore") => Ok(LeaderboardMetric::Ore),
+        Some("net_ore") => Ok(LeaderboardMetric::NetOre),
+        Some(m) => Err(AppError::BadRequest(format!("unknown metric: {m}"))),
+    }
+}
+
+fn parse_leaderboard_window(window: Option<&str>) -> Result<LeaderboardWindow, AppError> {
+    match window {
+        None => Ok(LeaderboardWindow::LastRounds(60)),
+        Some("all") => Ok(LeaderboardWindow::AllTime),
+        Some(n) => match n.parse::<i64>() {
+            Ok(n) if n > 0 => Ok(LeaderboardWindow::LastRounds(n.min(100_000))),
+            _ => Err(AppError::BadRequest(format!("invalid window: {n}"))),
+        },
+    }
+}
+
+/// All-time boards only count miners with 100+ rounds unless told otherwise.
+fn leaderboard_min_rounds(window: LeaderboardWindow, min_rounds: Option<i64>) -> i64 {
+    min_rounds.unwrap_or(match window {
+        LeaderboardWindow::AllTime => 100,
+        LeaderboardWindow::LastRounds(_) => 1,
+    }).max(1)
+}
+
+async fn leaderboard(
+    state: &AppState,
+    uri: &Uri,
+    metric: LeaderboardMetric,
+    window: LeaderboardWindow,
+    min_rounds: Option<i64>,
+    p: LeaderboardPagination,
+) -> Result<axum::response::Response, AppError> {
+    let direction = match p.direction.as_deref() {
+        None => None,
+        Some("up") => Some(SolBalanceDirection::Up),
+        Some("down") => Some(SolBalanceDirection::Down),
+        Some("flat") => Some(SolBalanceDirection::Flat),
+        Some(d) => return Err(AppError::BadRequest(format!("unknown direction: {d}"))),
+    };
+    let (limit, offset) = normalize_pagination(p.limit, p.offset, LEADERBOARD_DEFAULT_LIMIT, 2000)?;
+    let min_rounds = leaderboard_min_rounds(window, min_rounds);
+    let key = (metric, window, min_rounds, limit);
+    if offset == 0 && direction.is_none() && default_leaderboard_pages().contains(&key) {
+        let rows = match cached_leaderboard_page(state, key).await {
+            Some(rows) => rows,
+            None => load_leaderboard_page(state, key).await?,
+        };
+        let link = pagination_links(uri, limit, offset, Some(rows.len()));
+        return Ok(([(header::LINK, link)], Json(rows)).into_response());
+    }
+    if limit > LEADERBOARD_STREAM_THRESHOLD {
+        let rows = database::stream_leaderboard(state.read_pool.clone(), metric, window, min_rounds, direction, limit, offset);
+        let link = pagination_links(uri, limit, offset, None);
+        return Ok(([(header::LINK, link)], json_array_stream(rows)).into_response());
+    }
+    let rows = database::get_leaderboard(&state.db_pool, metric, window, min_rounds, direction, limit, offset).await?;
+    let link = pagination_links(uri, limit, offset, Some(rows.len()));
+    Ok(([(header::LINK, link)], Json(rows)).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct MoversQuery {
+    window: Option<String>,
+    hours: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct LeaderboardMover {
+    pubkey: String,
+    rank_now: i64,
+    rank_before: i64,
+    /// Positions gained since `rank_before`, negative when the miner dropped.
+    delta: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct LeaderboardMovers {
+    window: String,
+    /// When the ranks being compared against were recorded.
+    compared_at: i64,
+    gainers: Vec<LeaderboardMover>,
+    losers: Vec<LeaderboardMover>,
+}
+
+/// Net sol rank changes against the ranks recorded `hours` ago (default 24). Only
+/// windows listed in `MOVERS_WINDOWS` are recorded, and only miners ranked in both
+/// the current and the recorded top `MOVERS_TOP` are compared.
+async fn get_leaderboard_movers(
+    State(state): State<AppState>,
+    Query(q): Query<MoversQuery>,
+) -> Result<Json<LeaderboardMovers>, AppError> {
+    let window = parse_leaderboard_window(q.window.as_deref())?;
+    if !movers_windows_from_env().contains(&window) {
+        return Err(AppError::BadRequest(format!("window {} is not tracked for movers", window.key())));
+    }
+    let retention_hours = movers_retention_hours_from_env();
+    let hours = q.hours.unwrap_or(24);
+    if !(1..=retention_hours).contains(&hours) {
+        return Err(AppError::BadRequest(format!("hours must be between 1 and {retention_hours}, got {hours}")));
+    }
+    let (limit, _) = normalize_pagination(q.limit, None, 10, 100)?;
+    let limit = limit as usize;
+
+    let at = chrono::Utc::now().timestamp() - hours * 3600;
+    let before = database::get_leaderboard_ranks_before(&state.db_pool, window, at).await?;
+    let Some(compared_at) = before.first().map(|r| r.taken_at) else {
+        return Err(AppError::NotFound);
+    };
+    let before: HashMap<String, i64> = before.into_iter().map(|r| (r.pubkey, r.rank)).collect();
+
+    let min_rounds = leaderboard_min_rounds(window, None);
+    let now = database::get_leaderboard(&state.db_pool, LeaderboardMetric::NetSol, window, min_rounds, None, movers_top_from_env(), 0).await?;
+
+    let (mut gainers, mut losers): (Vec<_>, Vec<_>) = now
+        .into_iter()
+        .filter_map(|r| {
+            let rank_before = *before.get(&r.pubkey)?;
+            Some(LeaderboardMover { delta: rank_before - r.rank, pubkey: r.pubkey, rank_now: r.rank, rank_before })
+        })
+        .filter(|m| m.delta != 0)
+        .partition(|m| m.delta > 0);
+    gainers.sort_by_key(|m| std::cmp::Reverse(m.delta));
+    gainers.truncate(limit);
+    losers.sort_by_key(|m| m.delta);
+    losers.truncate(limit);
+
+    Ok(Json(LeaderboardMovers { window: window.key(), compared_at, gainers, losers }))
+}
+
+const LEADERBOARD_DEFAULT_LIMIT: i64 = 100;
 
-                    // group + squish
-                    let mut by_pubkey: HashMap<String, GetDeploymentSquished> = HashMap::new();
+/// Cached first pages older than this are recomputed in the background on the next request.
+const LEADERBOARD_CACHE_TTL: Duration = Duration::from_secs(120);
 
-                    for d in deployments {
-                        // make sure there's an entry for this pubkey
-                        let entry = by_pubkey.entry(d.pubkey.clone()).or_insert_with(|| GetDeploymentSquished {
-                            round_id: d.round_id as u64,
-                            pubkey: d.pubkey.clone(),
-                            deployments: [0u64; 25],
-                            sol_deployed: 0,
-                            sol_earned: 0,
-                            ore_earned: 0,
-                        });
+/// The only pages that are cached, filled by `leaderboard_warm_system`: net sol and
+/// ore boards over the default window and all time, first page at the default limit.
+fn default_leaderboard_pages() -> Vec<LeaderboardCacheKey> {
+    let mut keys = Vec::new();
+    for window in [LeaderboardWindow::LastRounds(60), LeaderboardWindow::AllTime] {
+        for metric in [LeaderboardMetric::NetSol, LeaderboardMetric::Ore] {
+            keys.push((metric, window, leaderboard_min_rounds(window, None), LEADERBOARD_DEFAULT_LIMIT));
+        }
+    }
+    keys
+}
 
-                        // place this deployment's amount into the correct slot
-                        // assuming square_id is 0..24
-                        let idx = d.square_id as usize;
-                        if idx < 25 {
-                            entry.deployments[idx] = d.amount as u64;
-                        }
-                        // accumulate totals
-                        entry.sol_deployed += d.amount as u64;
-                        entry.sol_earned += d.sol_earned as u64;
-                        entry.ore_earned += d.ore_earned as u64;
-                    }
+/// Returns the cached page even when it's past the ttl, and then starts one background
+/// refresh. The entry's timestamp is bumped first so concurrent requests don't start their
+/// own, which also means a failed refresh is only retried after another ttl.
+async fn cached_leaderboard_page(state: &AppState, key: LeaderboardCacheKey) -> Option<Vec<MinerLeaderboardRow>> {
+    let (at, rows) = state.leaderboard_cache.read().await
+        .get(&key)
+        .map(|(at, rows)| (*at, rows.clone()))?;
+    if at.elapsed() >= LEADERBOARD_CACHE_TTL {
+        let mut cache = state.leaderboard_cache.write().await;
+        if let Some(entry) = cache.get_mut(&key).filter(|(at, _)| at.elapsed() >= LEADERBOARD_CACHE_TTL) {
+            entry.0 = Instant::now();
+            let state = state.clone();
+            tokio::spawn(async move {
+                if let Err(e) = load_leaderboard_page(&state, key).await {
+                    tracing::error!("Failed to refresh leaderboard {:?}: {:?}", key, e);
+                }
+            });
+        }
+    }
+    Some(rows)
+}
 
-                    // turn the map into a vec
-                    let squished: Vec<GetDeploymentSquished> = by_pubkey.into_values().collect();
-                    let mut w = state.deployments_cache.write().await;
-                    if w.item.len() < 10 {
-                        w.item.insert(p.round_id, (squished.clone(), SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_else(|_| Duration::from_secs(0)).as_secs()));
-                    } else {
-                        tracing::warn!("Deployments cache max length reached, clearing cache...");
-                        w.item = HashMap::new();
-                        w.item.insert(p.round_id, (squished.clone(), SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_else(|_| Duration::from_secs(0)).as_secs()));
-                    }
-                    drop(w);
+/// Queries a first leaderboard page and stores it in the cache.
+async fn load_leaderboard_page(state: &AppState, key: LeaderboardCacheKey) -> anyhow::Result<Vec<MinerLeaderboardRow>> {
+    let (metric, window, min_rounds, limit) = key;
+    let rows = database::get_leaderboard(&state.db_pool, metric, window, min_rounds, None, limit, 0).await?;
+    state.leaderboard_cache.write().await.insert(key, (Instant::now(), rows.clone()));
+    Ok(rows)
+}
 
-                    Ok(Json(squished))
+/// Leaderboard pages above this many rows have their JSON streamed instead of buffered.
+const LEADERBOARD_STREAM_THRESHOLD: i64 = 500;
+
+/// Serializes rows into a JSON array body as they arrive. The status is already sent
+/// by the time a row fails, so a database error mid-stream aborts the response.
+fn json_array_stream<T, S>(rows: S) -> axum::response::Response
+where
+    T: Serialize + Send + 'static,
+    S: Stream<Item = Result<T, sqlx::Error>> + Send + 'static,
+{
+    // the body is polled after the handler returns, outside the request's bigint scope
+    let bigint = bigint_as_string();
+    let body = async_stream::stream! {
+        let mut rows = Box::pin(rows);
+        let mut first = true;
+        yield Ok::<_, anyhow::Error>(axum::body::Bytes::from_static(b"["));
+        while let Some(row) = rows.next().await {
+            let row = match row {
+                Ok(row) => row,
+                Err(e) => {
+                    tracing::error!("leaderboard stream failed: {e:?}");
+                    yield Err(e.into());
+                    return;
                 }
+            };
+            let mut buf = if first { Vec::new() } else { vec![b','] };
+            first = false;
+            if let Err(e) = with_bigint_mode(bigint, || serde_json::to_writer(&mut buf, &row)) {
+                tracing::error!("leaderboard stream failed: {e:?}");
+                yield Err(e.into());
+                return;
             }
-            Err(e) => {
-               return Err(AppError::Sqlx(e))
+            yield Ok(buf.into());
+        }
+        yield Ok(axum::body::Bytes::from_static(b"]"));
+    };
+    ([(header::CONTENT_TYPE, "application/json")], Body::from_stream(body)).into_response()
+}
+
+/// Round-by-round history as a CSV download, streamed from the read pool as sqlite
+/// produces the rows so a long history is never buffered.
+async fn get_miner_history_csv(
+    State(state): State<AppState>,
+    MinerPubkey(pubkey): MinerPubkey,
+) -> axum::response::Response {
+    let disposition = format!("attachment; filename=\"{pubkey}-history.csv\"");
+    let rows = database::stream_miner_round_history(state.read_pool.clone(), pubkey);
+    let body = async_stream::stream! {
+        let mut rows = Box::pin(rows);
+        yield Ok::<_, anyhow::Error>(axum::body::Bytes::from_static(
+            b"round_id,deployed,sol_earned,ore_earned,won_round,net_sol_round,created_at\n",
+        ));
+        while let Some(row) = rows.next().await {
+            match row {
+                Ok(r) => yield Ok(format!(
+                    "{},{},{},{},{},{},{}\n",
+                    r.round_id, r.deployed, r.sol_earned, r.ore_earned, r.won_round, r.net_sol_round, r.created_at
+                ).into()),
+                Err(e) => {
+                    tracing::error!("miner history csv stream failed: {e:?}");
+                    yield Err(e.into());
+                    return;
+                }
             }
         }
-    }
+    };
+    (
+        [(header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+async fn get_refinement_leaderboard(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(p): Query<Pagination>,
+) -> Result<([(header::HeaderName, String); 1], Json<Vec<RefinementLeaderboardRow>>), AppError> {
+    let (limit, offset) = normalize_pagination(p.limit, p.offset, 100, 2000)?;
+    let rows = database::get_refinement_leaderboard(&state.db_pool, limit, offset).await?;
+    let link = pagination_links(&uri, limit, offset, Some(rows.len()));
+    Ok(([(header::LINK, link)], Json(rows)))
 }
 
 #[derive(Debug, Deserialize)]
-struct Pagination {
-    limit: Option<i64>,
-    offset: Option<i64>,
+struct RankQuery {
+    metric: Option<String>,
+    window: Option<String>,
+    min_rounds: Option<i64>,
 }
 
-async fn get_miner_totals(
+async fn get_miner_rank(
     State(state): State<AppState>,
-    Query(p): Query<Pagination>,
-) -> Result<Json<Vec<MinerTotalsRow>>, AppError> {
-    let limit = p.limit.unwrap_or(100).clamp(1, 2000);
-    let offset = p.offset.unwrap_or(0).max(0);
-    let rows = database::get_miner_totals_all_time(&state.db_pool, limit, offset).await?;
-    Ok(Json(rows))
+    MinerPubkey(pubkey): MinerPubkey,
+    Query(q): Query<RankQuery>,
+) -> Result<Json<MinerLeaderboardRow>, AppError> {
+    let metric = parse_leaderboard_metric(q.metric.as_deref())?;
+    let window = parse_leaderboard_window(q.window.as_deref())?;
+    let min_rounds = leaderboard_min_rounds(window, q.min_rounds);
+    let row = database::get_leaderboard_rank(&state.db_pool, pubkey, metric, window, min_rounds).await?;
+    row.map(Json).ok_or(AppError::NotFound)
 }
 
-async fn get_leaderboard_all_time(
+async fn get_miner_luck(
     State(state): State<AppState>,
-    Query(p): Query<Pagination>,
-) -> Result<Json<Vec<MinerTotalsRow>>, AppError> {
-    let limit = p.limit.unwrap_or(100).clamp(1, 2000);
-    let offset = p.offset.unwrap_or(0).max(0);
-    let rows = database::get_miner_totals_all_time_v2(&state.db_pool, limit, offset).await?;
-    Ok(Json(rows))
+    MinerPubkey(pubkey): MinerPubkey,
+) -> Result<Json<MinerLuck>, AppError> {
+    let luck = database::get_miner_luck(&state.db_pool, pubkey).await?;
+    if luck.rounds_played == 0 {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(luck))
 }
 
-async fn get_leaderboard(
+#[derive(Debug, Deserialize)]
+struct NearbyQuery {
+    metric: Option<String>,
+    window: Option<String>,
+    min_rounds: Option<i64>,
+    radius: Option<i64>,
+}
+
+async fn get_miner_nearby(
     State(state): State<AppState>,
-    Query(p): Query<Pagination>,
+    MinerPubkey(pubkey): MinerPubkey,
+    Query(q): Query<NearbyQuery>,
 ) -> Result<Json<Vec<MinerLeaderboardRow>>, AppError> {
-    let limit = p.limit.unwrap_or(100).clamp(1, 2000);
-    let offset = p.offset.unwrap_or(0).max(0);
-    let rounds = 60;
-    let rows = database::get_leaderboard_last_n_rounds(&state.db_pool, rounds, limit, offset).await?;
+    let metric = parse_leaderboard_metric(q.metric.as_deref())?;
+    let window = parse_leaderboard_window(q.window.as_deref())?;
+    let min_rounds = leaderboard_min_rounds(window, q.min_rounds);
+    let radius = q.radius.unwrap_or(5).clamp(1, 50);
+
+    let row = database::get_leaderboard_rank(&state.db_pool, pubkey, metric, window, min_rounds).await?
+        .ok_or(AppError::NotFound)?;
+
+    // top-ranked miners just get a shorter slice above them
+    let offset = (row.rank - radius - 1).max(0);
+    let limit = 2 * radius + 1;
+    let rows = database::get_leaderboard(&state.db_pool, metric, window, min_rounds, None, limit, offset).await?;
     Ok(Json(rows))
 }
 
-async fn get_leaderboard_latest_rounds(
+// Fixed leaderboard routes kept for compatibility, all thin shims over `leaderboard`.
+
+async fn get_miner_totals(
     State(state): State<AppState>,
-    Query(p): Query<Pagination>,
-) -> Result<Json<Vec<MinerLeaderboardRow>>, AppError> {
-    let limit = p.limit.unwrap_or(100).clamp(1, 2000);
-    let offset = p.offset.unwrap_or(0).max(0);
-    let rounds = 60;
-    let rows = database::get_leaderboard_last_n_rounds_v2(&state.db_pool, rounds, limit, offset).await?;
-    Ok(Json(rows))
+    OriginalUri(uri): OriginalUri,
+    Query(p): Query<LeaderboardPagination>,
+) -> Result<axum::response::Response, AppError> {
+    leaderboard(&state, &uri, LeaderboardMetric::NetSol, LeaderboardWindow::AllTime, None, p).await
 }
 
-#[derive(Debug, Deserialize)]
-struct OreLeaderboardQuery {
-    limit: Option<i64>,
-    offset: Option<i64>,
-    //rounds: Option<i64>, // if present, use "Last X rounds"; else All Time
+async fn get_leaderboard_all_time(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(p): Query<LeaderboardPagination>,
+) -> Result<axum::response::Response, AppError> {
+    leaderboard(&state, &uri, LeaderboardMetric::NetSol, LeaderboardWindow::AllTime, None, p).await
+}
+
+async fn get_leaderboard_latest_rounds(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Query(p): Query<LeaderboardPagination>,
+) -> Result<axum::response::Response, AppError> {
+    leaderboard(&state, &uri, LeaderboardMetric::NetSol, LeaderboardWindow::LastRounds(60), None, p).await
 }
 
 async fn get_miner_totals_ore(
     State(state): State<AppState>,
-    Query(q): Query<OreLeaderboardQuery>,
-) -> Result<Json<Vec<MinerOreLeaderboardRow>>, AppError> {
-    let limit  = q.limit.unwrap_or(100).clamp(1, 2000);
-    let offset = q.offset.unwrap_or(0).max(0);
-    let rows =  database::get_ore_leaderboard_all_time(&state.db_pool, limit, offset).await?;
-    Ok(Json(rows))
+    OriginalUri(uri): OriginalUri,
+    Query(p): Query<LeaderboardPagination>,
+) -> Result<axum::response::Response, AppError> {
+    leaderboard(&state, &uri, LeaderboardMetric::Ore, LeaderboardWindow::AllTime, None, p).await
 }
 
 async fn get_leaderboard_all_time_ore(
     State(state): State<AppState>,
-    Query(q): Query<OreLeaderboardQuery>,
-) -> Result<Json<Vec<MinerOreLeaderboardRow>>, AppError> {
-    let limit  = q.limit.unwrap_or(100).clamp(1, 2000);
-    let offset = q.offset.unwrap_or(0).max(0);
-    let rows =  database::get_ore_leaderboard_all_time_v2(&state.db_pool, limit, offset).await?;
-    Ok(Json(rows))
+    OriginalUri(uri): OriginalUri,
+    Query(p): Query<LeaderboardPagination>,
+) -> Result<axum::response::Response, AppError> {
+    leaderboard(&state, &uri, LeaderboardMetric::Ore, LeaderboardWindow::AllTime, None, p).await
 }
 
 async fn get_leaderboard_ore(
     State(state): State<AppState>,
-    Query(p): Query<Pagination>,
-) -> Result<Json<Vec<MinerOreLeaderboardRow>>, AppError> {
-    let limit = p.limit.unwrap_or(100).clamp(1, 2000);
-    let offset = p.offset.unwrap_or(0).max(0);
-    let rows = database::get_ore_leaderboard_last_n_rounds(&state.db_pool, 60, limit, offset).await?;
-    Ok(Json(rows))
+    OriginalUri(uri): OriginalUri,
+    Query(p): Query<LeaderboardPagination>,
+) -> Result<axum::response::Response, AppError> {
+    leaderboard(&state, &uri, LeaderboardMetric::Ore, LeaderboardWindow::LastRounds(60), None, p).await
 }
 
 async fn get_leaderboard_latest_rounds_ore(
     State(state): State<AppState>,
-    Query(p): Query<Pagination>,
-) -> Result<Json<Vec<MinerOreLeaderboardRow>>, AppError> {
-    let limit = p.limit.unwrap_or(100).clamp(1, 2000);
-    let offset = p.offset.unwrap_or(0).max(0);
-    let rows = database::get_ore_leaderboard_last_n_rounds_v2(&state.db_pool, 60, limit, offset).await?;
-    Ok(Json(rows))
+    OriginalUri(uri): OriginalUri,
+    Query(p): Query<LeaderboardPagination>,
+) -> Result<axum::response::Response, AppError> {
+    leaderboard(&state, &uri, LeaderboardMetric::Ore, LeaderboardWindow::LastRounds(60), None, p).await
 }
 
 async fn get_miner_stats(
     State(state): State<AppState>,
-    Path(pubkey): Path<String>,
+    MinerPubkey(pubkey): MinerPubkey,
     Query(p): Query<RoundsPagination>,
 ) -> Result<Json<Vec<MinerTotalsRow>>, AppError> {
     let miner_stats = database::get_miner_stats(&state.db_pool, pubkey).await?;
@@ -644,16 +2164,55 @@ async fn get_miner_stats(
     }
 }
 
+const MAX_COMPARE_PUBKEYS: usize = 10;
+
+#[derive(Debug, Deserialize)]
+struct CompareQuery {
+    pubkeys: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MinerCompareEntry {
+    pub pubkey: String,
+    pub totals: Option<MinerTotalsRow>,
+}
+
+async fn get_miners_compare(
+    State(state): State<AppState>,
+    Query(q): Query<CompareQuery>,
+) -> Result<Json<Vec<MinerCompareEntry>>, AppError> {
+    // repeats are dropped, the first occurrence keeps its place
+    let mut seen = HashSet::new();
+    let pubkeys = q.pubkeys
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| Pubkey::from_str(p).map(|p| p.to_string()).map_err(|_| AppError::InvalidPubkey(p.to_string())))
+        .filter(|p| p.as_ref().map_or(true, |p| seen.insert(p.clone())))
+        .collect::<Result<Vec<String>, AppError>>()?;
+
+    if pubkeys.len() > MAX_COMPARE_PUBKEYS {
+        return Err(AppError::BadRequest(format!("at most {} pubkeys can be compared", MAX_COMPARE_PUBKEYS)));
+    }
+
+    let rows = database::get_miner_stats_many(&state.db_pool, &pubkeys).await?;
+    let by_pubkey: HashMap<String, MinerTotalsRow> = rows.into_iter().map(|r| (r.pubkey.clone(), r)).collect();
+
+    // keep the caller's ordering, with None for pubkeys that have no totals
+    let entries = pubkeys
+        .into_iter()
+        .map(|pubkey| {
+            let totals = by_pubkey.get(&pubkey).cloned();
+            MinerCompareEntry { pubkey, totals }
+        })
+        .collect();
+    Ok(Json(entries))
+}
+
 async fn get_miner_latest(
     State(state): State<AppState>,
-    Path(pubkey): Path<String>,
+    MinerPubkey(pubkey): MinerPubkey,
 ) -> Result<Json<Option<AppMiner>>, AppError> {
-    let pubkey = if let Ok(p) = Pubkey::from_str(&pubkey) {
-        p.to_string()
-    } else {
-        return Ok(Json(None))
-    };
-
     let miners = state.miners.clone();
     let reader = miners.read().await;
     let miners = reader.clone();
@@ -670,14 +2229,103 @@ async fn get_miner_latest(
 
 async fn get_miner_snapshot(
     State(state): State<AppState>,
-    Path(pubkey): Path<String>,
+    MinerPubkey(pubkey): MinerPubkey,
 ) -> Result<Json<Option<DbMinerSnapshot>>, AppError> {
-    if let Ok(p) = Pubkey::from_str(&pubkey) {
-        let earnings = database::get_snapshot_24h_ago(&state.db_pool, p.to_string()).await?;
-        return Ok(Json(earnings))
+    let earnings = database::get_snapshot_24h_ago(&state.db_pool, pubkey).await?;
+    Ok(Json(earnings))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MinerDeltaValues {
+    pub unclaimed_ore: i64,
+    pub refined_ore: i64,
+    pub lifetime_sol: i64,
+    pub lifetime_ore: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MinerDelta24h {
+    pub pubkey: String,
+    pub has_baseline: bool,
+    pub current: MinerDeltaValues,
+    pub delta: Option<MinerDeltaValues>,
+}
+
+async fn get_miner_24h_delta(
+    State(state): State<AppState>,
+    MinerPubkey(pubkey): MinerPubkey,
+) -> Result<Json<Option<MinerDelta24h>>, AppError> {
+    let reader = state.miners.read().await;
+    let miner = reader.iter().find(|m| m.authority == pubkey).cloned();
+    drop(reader);
+
+    // Miner isn't loaded in memory yet (or doesn't exist)
+    let miner = if let Some(m) = miner {
+        m
     } else {
         return Ok(Json(None))
     };
+
+    let baseline = database::get_snapshot_24h_ago(&state.db_pool, pubkey).await?;
+    Ok(Json(Some(miner_delta_24h(&miner, baseline.as_ref()))))
+}
+
+fn miner_delta_24h(miner: &AppMiner, baseline: Option<&DbMinerSnapshot>) -> MinerDelta24h {
+    let current = MinerDeltaValues {
+        unclaimed_ore: miner.rewards_ore as i64,
+        refined_ore: miner.refined_ore as i64,
+        lifetime_sol: miner.lifetime_rewards_sol as i64,
+        lifetime_ore: miner.lifetime_rewards_ore as i64,
+    };
+
+    let delta = baseline.map(|b| MinerDeltaValues {
+        unclaimed_ore: current.unclaimed_ore - b.unclaimed_ore,
+        refined_ore: current.refined_ore - b.refined_ore,
+        lifetime_sol: current.lifetime_sol - b.lifetime_sol,
+        lifetime_ore: current.lifetime_ore - b.lifetime_ore,
+    });
+
+    MinerDelta24h {
+        pubkey: miner.authority.clone(),
+        has_baseline: delta.is_some(),
+        current,
+        delta,
+    }
+}
+
+const MAX_BATCH_24H_PUBKEYS: usize = 200;
+
+/// Batch version of `/miner/{pubkey}/24h`. Keeps input order, null for miners that aren't loaded.
+async fn post_miners_24h_delta(
+    State(state): State<AppState>,
+    Json(pubkeys): Json<Vec<String>>,
+) -> Result<Json<Vec<Option<MinerDelta24h>>>, AppError> {
+    if pubkeys.len() > MAX_BATCH_24H_PUBKEYS {
+        return Err(AppError::BadRequest(format!("at most {} pubkeys per request", MAX_BATCH_24H_PUBKEYS)));
+    }
+    let pubkeys = pubkeys
+        .iter()
+        .map(|p| Pubkey::from_str(p).map(|p| p.to_string()).map_err(|_| AppError::InvalidPubkey(p.clone())))
+        .collect::<Result<Vec<String>, AppError>>()?;
+
+    let reader = state.miners.read().await;
+    let miners: HashMap<String, AppMiner> = reader
+        .iter()
+        .filter(|m| pubkeys.contains(&m.authority))
+        .map(|m| (m.authority.clone(), m.clone()))
+        .collect();
+    drop(reader);
+
+    let baselines: HashMap<String, DbMinerSnapshot> = database::get_snapshots_24h_ago(&state.db_pool, &pubkeys).await?
+        .into_iter()
+        .map(|s| (s.pubkey.clone(), s))
+        .collect();
+
+    let deltas = pubkeys
+        .iter()
+        .map(|p| miners.get(p).map(|m| miner_delta_24h(m, baselines.get(p))))
+        .collect();
+    Ok(Json(deltas))
 }
 
 async fn get_available_pubkeys(
@@ -698,6 +2346,173 @@ async fn get_live_round(
     Ok(Json(round))
 }
 
+#[derive(Debug, Deserialize)]
+struct SimulateRequest {
+    square_id: usize,
+    amount: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulateResponse {
+    pub round_id: u64,
+    pub square_id: usize,
+    pub amount: u64,
+    /// SOL on the square including the simulated deployment.
+    pub square_deployed: u64,
+    /// SOL returned if the square wins.
+    pub sol_if_win: u64,
+    /// ORE if the square wins and the top miner reward is split.
+    pub ore_if_split: u64,
+    /// ORE if the square wins and this deployment is picked as top miner.
+    pub ore_if_top_miner: u64,
+    /// Chance of being picked as top miner when the square wins and the reward isn't split.
+    pub top_miner_chance: f64,
+    /// Extra ORE if the square wins and the motherlode is hit.
+    pub motherlode_if_hit: u64,
+}
+
+/// Expected rewards for deploying `amount` on `square_id` now, assuming nobody deploys after.
+/// Winnings use the vaulted share and top miner reward of the last finished round.
+async fn post_simulate(
+    State(state): State<AppState>,
+    Json(req): Json<SimulateRequest>,
+) -> Result<Json<SimulateResponse>, AppError> {
+    if req.square_id >= 25 {
+        return Err(AppError::BadRequest(format!("square_id must be < 25, got {}", req.square_id)));
+    }
+    if req.amount == 0 {
+        return Err(AppError::BadRequest("amount must be > 0".into()));
+    }
+
+    let live = state.live_round.read().await.clone();
+    let last = state.rounds.read().await.last().cloned();
+    let motherlode = state.treasury.read().await.motherlode;
+
+    let cumulative = live.deployed[req.square_id];
+    let params = hypothetical_params(&live, last.as_ref(), req.square_id, req.square_id, req.amount);
+    let denom = params.denom;
+    let split = compute_deployment_reward(&params, req.square_id, req.amount, cumulative);
+    let top = compute_deployment_reward(
+        &RoundRewardParams { is_split: false, top_sample: Some(cumulative), ..params },
+        req.square_id, req.amount, cumulative,
+    );
+    let ml = compute_deployment_reward(
+        &RoundRewardParams { is_split: false, motherlode, ..params },
+        req.square_id, req.amount, cumulative,
+    );
+
+    Ok(Json(SimulateResponse {
+        round_id: live.id,
+        square_id: req.square_id,
+        amount: req.amount,
+        square_deployed: denom,
+        sol_if_win: split.sol,
+        ore_if_split: split.ore,
+        ore_if_top_miner: top.ore,
+        top_miner_chance: req.amount as f64 / denom as f64,
+        motherlode_if_hit: ml.ore,
+    }))
+}
+
+/// Share of losing SOL the program puts in the vault, in basis points. Used when there's
+/// no finished round to take the actual share from.
+const DEFAULT_VAULT_BPS: u64 = 1_000;
+
+/// Split-reward params for the live round if `winning_square` wins after `amount` is
+/// deployed on `square_id` and nobody else deploys. Winnings use the vaulted share and
+/// top miner reward of the last finished round, since the live round's aren't known yet.
+fn hypothetical_params(live: &AppRound, last: Option<&AppRound>, winning_square: usize, square_id: usize, amount: u64) -> RoundRewardParams {
+    let mut denom = live.deployed[winning_square];
+    if winning_square == square_id {
+        denom = denom.saturating_add(amount);
+    }
+    let losing = live.total_deployed.saturating_add(amount).saturating_sub(denom);
+    // vaulted share as a (numerator, denominator) pair so the math stays in integers
+    let ((vaulted, of), top_miner_reward) = match last {
+        Some(r) if r.total_vaulted + r.total_winnings > 0 => (
+            (r.total_vaulted, r.total_vaulted + r.total_winnings),
+            r.top_miner_reward,
+        ),
+        Some(r) => ((DEFAULT_VAULT_BPS, 10_000), r.top_miner_reward),
+        None => ((DEFAULT_VAULT_BPS, 10_000), 0),
+    };
+    let vault = (losing as u128 * vaulted as u128 / of as u128) as u64;
+    let total_winnings = losing - vault;
+
+    RoundRewardParams {
+        winning_square,
+        denom,
+        total_winnings,
+        top_miner_reward,
+        motherlode: 0,
+        is_split: true,
+        top_sample: None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EvQuery {
+    square_id: usize,
+    amount: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EvResponse {
+    pub round_id: u64,
+    pub square_id: usize,
+    pub amount: u64,
+    /// Expected SOL back, including the deployment itself when it wins.
+    pub sol_ev: f64,
+    /// `sol_ev` minus the amount deployed.
+    pub net_sol_ev: f64,
+    /// Expected ORE from the top miner reward, excluding the motherlode.
+    pub ore_ev: f64,
+    /// Expected extra ORE given the motherlode is hit this round.
+    pub motherlode_ev_if_hit: f64,
+}
+
+/// Expected value of deploying `amount` on `square_id` now, assuming nobody deploys after
+/// and every square is equally likely (1/25) to win. Averages `compute_deployment_reward`
+/// over all 25 winners. The top miner reward is taken pro-rata, which is also its
+/// expectation when it goes to a single sampled miner.
+async fn get_live_round_ev(
+    State(state): State<AppState>,
+    Query(q): Query<EvQuery>,
+) -> Result<Json<EvResponse>, AppError> {
+    if q.square_id >= 25 {
+        return Err(AppError::BadRequest(format!("square_id must be < 25, got {}", q.square_id)));
+    }
+    if q.amount == 0 {
+        return Err(AppError::BadRequest("amount must be > 0".into()));
+    }
+
+    let live = state.live_round.read().await.clone();
+    let last = state.rounds.read().await.last().cloned();
+    let motherlode = state.treasury.read().await.motherlode;
+
+    let cumulative = live.deployed[q.square_id];
+    let (mut sol, mut ore, mut ml) = (0u128, 0u128, 0u128);
+    for winning_square in 0..25 {
+        let params = hypothetical_params(&live, last.as_ref(), winning_square, q.square_id, q.amount);
+        let reward = compute_deployment_reward(&params, q.square_id, q.amount, cumulative);
+        sol += reward.sol as u128;
+        ore += reward.ore as u128;
+        let hit = compute_deployment_reward(&RoundRewardParams { motherlode, top_miner_reward: 0, ..params }, q.square_id, q.amount, cumulative);
+        ml += hit.ore as u128;
+    }
+    let sol_ev = sol as f64 / 25.0;
+
+    Ok(Json(EvResponse {
+        round_id: live.id,
+        square_id: q.square_id,
+        amount: q.amount,
+        sol_ev,
+        net_sol_ev: sol_ev - q.amount as f64,
+        ore_ev: ore as f64 / 25.0,
+        motherlode_ev_if_hit: ml as f64 / 25.0,
+    }))
+}
+
 async fn get_live_deployments(
     State(state): State<AppState>,
 ) -> Result<Json<Vec<AppLiveDeployment>>, AppError> {
@@ -774,32 +2589,229 @@ async fn sse_deployments_handler(
     Sse::new(stream).keep_alive(sse::KeepAlive::default())
 }
 
+/// Most miners one socket can follow at once.
+const WS_MAX_SUBSCRIPTIONS: usize = 50;
+
+#[derive(Debug, Deserialize)]
+struct WsRequest {
+    subscribe: Option<String>,
+    unsubscribe: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum WsResponse {
+    Subscribed(String),
+    Unsubscribed(String),
+    Error(String),
+    Miner(Box<AppMiner>),
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(app_state): State<AppState>,
+) -> axum::response::Response {
+    ws.on_upgrade(move |socket| ws_miner_updates(socket, app_state))
+}
+
+/// Clients send `{"subscribe": "<pubkey>"}` / `{"unsubscribe": "<pubkey>"}` and get the
+/// miner pushed whenever the poll loop sees its account change.
+async fn ws_miner_updates(mut socket: WebSocket, app_state: AppState) {
+    let mut rx = app_state.miner_updates.subscribe();
+    let mut subscribed: HashSet<String> = HashSet::new();
+
+    loop {
+        let reply = tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    match serde_json::from_str::<WsRequest>(text.as_str()) {
+                        Ok(WsRequest { subscribe: Some(pubkey), .. }) => {
+                            if Pubkey::from_str(&pubkey).is_err() {
+                                WsResponse::Error(format!("invalid pubkey: {pubkey}"))
+                            } else if subscribed.len() >= WS_MAX_SUBSCRIPTIONS && !subscribed.contains(&pubkey) {
+                                WsResponse::Error(format!("at most {WS_MAX_SUBSCRIPTIONS} subscriptions per connection"))
+                            } else {
+                                subscribed.insert(pubkey.clone());
+                                WsResponse::Subscribed(pubkey)
+                            }
+                        },
+                        Ok(WsRequest { unsubscribe: Some(pubkey), .. }) => {
+                            subscribed.remove(&pubkey);
+                            WsResponse::Unsubscribed(pubkey)
+                        },
+                        _ => WsResponse::Error("expected {\"subscribe\": pubkey} or {\"unsubscribe\": pubkey}".to_string()),
+                    }
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            update = rx.recv() => match update {
+                Ok(miner) if subscribed.contains(&miner.authority) => WsResponse::Miner(Box::new(miner)),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!("ws client lagged, skipped {} miner updates", n);
+                    continue;
+                },
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+
+        let Ok(json) = serde_json::to_string(&reply) else { continue };
+        if socket.send(Message::Text(json.into())).await.is_err() {
+            break;
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MinerTotalsDiff {
+    pub pubkey: String,
+    pub before: Option<MinerTotalsRow>,
+    pub after: Option<MinerTotalsRow>,
+}
+
+/// Admin endpoints that write to the database are refused under READ_ONLY.
+fn reject_if_read_only(state: &AppState) -> Result<(), AppError> {
+    if state.read_only {
+        return Err(AppError::Disabled("database writes are disabled (READ_ONLY)".into()));
+    }
+    Ok(())
+}
+
+async fn admin_finalize_round(
+    State(state): State<AppState>,
+    Path(round_id): Path<i64>,
+) -> Result<Json<Vec<MinerTotalsDiff>>, AppError> {
+    reject_if_read_only(&state)?;
+    let before = database::get_miner_totals_for_round(&state.db_pool, round_id).await?;
+    database::finalize_round_idempotent(&state.db_pool, round_id).await?;
+    let after = database::get_miner_totals_for_round(&state.db_pool, round_id).await?;
+
+    let mut by_pubkey: HashMap<String, MinerTotalsDiff> = HashMap::new();
+    for row in before {
+        by_pubkey.insert(row.pubkey.clone(), MinerTotalsDiff { pubkey: row.pubkey.clone(), before: Some(row), after: None });
+    }
+    for row in after {
+        let entry = by_pubkey.entry(row.pubkey.clone()).or_insert_with(|| MinerTotalsDiff {
+            pubkey: row.pubkey.clone(),
+            before: None,
+            after: None,
+        });
+        entry.after = Some(row);
+    }
+
+    // only report pubkeys whose totals actually changed
+    let mut diffs: Vec<MinerTotalsDiff> = by_pubkey.into_values().filter(|d| d.before != d.after).collect();
+    diffs.sort_by(|a, b| a.pubkey.cmp(&b.pubkey));
+    tracing::info!("Finalized round {} via admin, {} miner totals changed", round_id, diffs.len());
+    Ok(Json(diffs))
+}
+
+#[derive(Debug, Deserialize)]
+struct GapsQuery {
+    backfill: Option<bool>,
+}
+
+async fn admin_get_gaps(
+    State(state): State<AppState>,
+    Query(q): Query<GapsQuery>,
+) -> Result<Json<Vec<RoundGap>>, AppError> {
+    let backfill = q.backfill.unwrap_or(false);
+    if backfill {
+        reject_if_read_only(&state)?;
+        if !state.poller_enabled {
+            return Err(AppError::Disabled("backfilling needs the rpc, the poller is disabled on this instance".into()));
+        }
+    }
+    let gaps = database::get_round_gaps(&state.db_pool).await?;
+    if backfill && !gaps.is_empty() {
+        backfill_gaps(state.clone(), gaps.clone());
+    }
+    Ok(Json(gaps))
+}
+
+async fn admin_refetch_round(
+    State(state): State<AppState>,
+    Path(round_id): Path<u64>,
+) -> Result<Json<RoundRow>, AppError> {
+    reject_if_read_only(&state)?;
+    if !state.poller_enabled {
+        return Err(AppError::Disabled("refetching needs the rpc, the poller is disabled on this instance".into()));
+    }
+    let row = refetch_round(&state, round_id).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(row))
+}
+
+async fn admin_backup(
+    State(state): State<AppState>,
+) -> Result<Json<Backup>, AppError> {
+    Ok(Json(backup_database(&state).await?))
+}
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("not found")]
     NotFound,
+    #[error("unauthorized")]
+    Unauthorized,
+    #[error("bad request: {0}")]
+    BadRequest(String),
+    #[error("invalid pubkey: {0}")]
+    InvalidPubkey(String),
+    /// The feature is turned off by this instance's configuration.
+    #[error("disabled: {0}")]
+    Disabled(String),
     #[error(transparent)]
     Sqlx(#[from] sqlx::Error),
     #[error(transparent)]
     Anyhow(#[from] anyhow::Error),
 }
 
+impl AppError {
+    /// Stable machine readable code clients can branch on.
+    fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound => "NOT_FOUND",
+            AppError::Unauthorized => "UNAUTHORIZED",
+            AppError::BadRequest(_) => "BAD_REQUEST",
+            AppError::InvalidPubkey(_) => "INVALID_PUBKEY",
+            AppError::Disabled(_) => "DISABLED",
+            AppError::Sqlx(_) | AppError::Anyhow(_) => "INTERNAL",
+        }
+    }
+}
+
 impl axum::response::IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
         use axum::{http::StatusCode, Json};
         #[derive(Serialize)]
-        struct ErrBody { error: String }
-        match self {
-            AppError::NotFound => (StatusCode::NOT_FOUND, Json(ErrBody { error: "not found".into() })).into_response(),
+        struct ErrBody { code: &'static str, error: String }
+        let code = self.code();
+        let (status, error) = match self {
+            AppError::NotFound => (StatusCode::NOT_FOUND, "not found".to_string()),
+            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "unauthorized".to_string()),
+            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            e @ AppError::InvalidPubkey(_) => (StatusCode::BAD_REQUEST, e.to_string()),
+            AppError::Disabled(msg) => (StatusCode::NOT_IMPLEMENTED, msg),
             other => {
                 tracing::error!("internal error: {other:#}");
-                (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrBody { error: "internal server error".into() })).into_response()
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal server error".to_string())
             }
-        }
+        };
+        (status, Json(ErrBody { code, error })).into_response()
     }
 }
 
 
+/// Boolean env var, accepting true/false/1/0 in any case. Unset or unparsable gives `default`.
+fn env_flag(key: &str, default: bool) -> bool {
+    match env::var(key).unwrap_or_default().to_lowercase().as_str() {
+        "true" | "1" => true,
+        "false" | "0" => false,
+        _ => default,
+    }
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c().await.expect("install Ctrl+C handler");
@@ -825,3 +2837,158 @@ async fn shutdown_signal() {
     tracing::info!("shutting down");
 }
 
+#[cfg(test)]
+mod tests {
+    use bytemuck::Zeroable;
+    use ore_api::{consts::SPLIT_ADDRESS, state::Round};
+
+    use super::*;
+
+    /// A split round won by square 7, finished the way the program does it: a tenth of
+    /// the losing SOL is vaulted and the rest paid out as winnings.
+    fn finished_round(deployed: [u64; 25], top_miner_reward: u64) -> Round {
+        let mut round = Round::zeroed();
+        round.id = 2;
+        round.deployed = deployed;
+        round.slot_hash[0] = 7;
+        round.top_miner = SPLIT_ADDRESS;
+        round.top_miner_reward = top_miner_reward;
+        round.total_deployed = deployed.iter().sum();
+        let losing = round.total_deployed - deployed[7];
+        round.total_vaulted = losing / 10;
+        round.total_winnings = losing - round.total_vaulted;
+        round
+    }
+
+    #[test]
+    fn simulated_rewards_match_the_finalizer() {
+        let amount = 2_500_000_001;
+        let mut deployed = [1_234_567_891; 25];
+        deployed[7] = 3_000_000_007;
+        let finished = finished_round(deployed, 100_000_000_000);
+
+        // the live round is the finished one before our deployment landed on square 7
+        let mut live = AppRound::from(finished);
+        live.deployed[7] -= amount;
+        live.total_deployed -= amount;
+        live.slot_hash = [0; 32];
+        let last = AppRound::from(finished_round([1_000_000_000; 25], 100_000_000_000));
+
+        let finalized = RoundRewardParams::from_round(&finished).unwrap();
+        assert_eq!(finalized.winning_square, 7);
+        for last in [Some(&last), None] {
+            let simulated = hypothetical_params(&live, last, 7, 7, amount);
+            assert_eq!(simulated.denom, finalized.denom);
+            assert_eq!(simulated.total_winnings, finalized.total_winnings);
+            assert_eq!(
+                compute_deployment_reward(&simulated, 7, amount, live.deployed[7]).sol,
+                compute_deployment_reward(&finalized, 7, amount, live.deployed[7]).sol,
+            );
+        }
+
+        let simulated = hypothetical_params(&live, Some(&last), 7, 7, amount);
+        assert_eq!(
+            compute_deployment_reward(&simulated, 7, amount, live.deployed[7]),
+            compute_deployment_reward(&finalized, 7, amount, live.deployed[7]),
+        );
+    }
+
+    #[tokio::test]
+    async fn stale_leaderboard_pages_are_served_while_refreshing() {
+        let state = AppState::for_tests(database::memory_pool().await);
+        let key = default_leaderboard_pages()[0];
+        let stale = MinerLeaderboardRow {
+            rank: 1,
+            pubkey: "stale".to_string(),
+            rounds_played: 1,
+            rounds_won: 0,
+            total_sol_deployed: 0,
+            total_sol_earned: 0,
+            total_ore_earned: 0,
+            net_sol_change: 0,
+            sol_balance_direction: "even".to_string(),
+            refined_ore: 0,
+            net_ore: 0,
+        };
+        let computed_at = Instant::now().checked_sub(LEADERBOARD_CACHE_TTL * 2).unwrap();
+        state.leaderboard_cache.write().await.insert(key, (computed_at, vec![stale]));
+
+        let rows = cached_leaderboard_page(&state, key).await.unwrap();
+        assert_eq!(rows[0].pubkey, "stale");
+
+        // The database is empty, so the background refresh leaves an empty page.
+        for _ in 0..100 {
+            if state.leaderboard_cache.read().await[&key].1.is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("stale leaderboard page was never refreshed");
+    }
+
+    #[tokio::test]
+    async fn deprecated_aliases_keep_pagination_links() {
+        use tower::ServiceExt;
+
+        let state = AppState::for_tests(database::memory_pool().await);
+        for id in [1, 2] {
+            let mut round = Round::zeroed();
+            round.id = id;
+            database::insert_round(&state.db_pool, &None, &RoundRow::from(round)).await.unwrap();
+        }
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/rounds?limit=1").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let links: Vec<&str> = response.headers().get_all(header::LINK).iter().map(|v| v.to_str().unwrap()).collect();
+        assert!(links.iter().any(|l| l.contains("rel=\"successor-version\"")), "{links:?}");
+        assert!(links.iter().any(|l| l.contains("rel=\"first\"") && l.contains("rel=\"next\"")), "{links:?}");
+    }
+
+    #[tokio::test]
+    async fn large_leaderboard_pages_stream_from_the_read_pool() {
+        use tower::ServiceExt;
+
+        let state = AppState::for_tests(database::memory_pool().await);
+        for (pubkey, net) in [("a", 5), ("b", 9), ("c", -3)] {
+            sqlx::query("INSERT INTO miner_totals VALUES (?, 100, 1, 10, 10, 0, ?)")
+                .bind(pubkey)
+                .bind(net)
+                .execute(&state.db_pool)
+                .await
+                .unwrap();
+        }
+
+        let response = router(state)
+            .oneshot(Request::builder().uri("/v1/leaderboard?window=all&limit=600").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let rows: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        let pubkeys: Vec<&str> = rows.iter().map(|r| r["pubkey"].as_str().unwrap()).collect();
+        assert_eq!(pubkeys, ["b", "a", "c"]);
+    }
+
+    #[tokio::test]
+    async fn compare_rejects_invalid_pubkeys_and_drops_repeats() {
+        use tower::ServiceExt;
+
+        let app = router(AppState::for_tests(database::memory_pool().await));
+        let get = |uri: String| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let response = app.clone().oneshot(get("/v1/miners/compare?pubkeys=not-a-pubkey".to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let a = Pubkey::new_unique().to_string();
+        let b = Pubkey::new_unique().to_string();
+        let response = app.oneshot(get(format!("/v1/miners/compare?pubkeys={a},{b},{a}"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let entries: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        let pubkeys: Vec<&str> = entries.iter().map(|e| e["pubkey"].as_str().unwrap()).collect();
+        assert_eq!(pubkeys, [a.as_str(), b.as_str()]);
+    }
+}
@@ -0,0 +1,323 @@
+use std::{env, sync::atomic::{AtomicU64, Ordering}, time::{Duration, Instant}};
+
+use serde::Serialize;
+
+use crate::{app_state::{AppBoard, AppRound, AppState}, database::{self, CurrentMinerFilter, LeaderboardMetric, LeaderboardWindow}, default_leaderboard_pages, leaderboard_min_rounds, load_leaderboard_page, parse_leaderboard_window};
+
+fn env_u64(key: &str, default: u64) -> u64 {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(default)
+}
+
+/// Computes the default leaderboard pages once at startup so the first requests don't pay
+/// for the all-time aggregation. Later refreshes happen lazily, see `cached_leaderboard_page`.
+/// Pages that fail are retried every `LEADERBOARD_WARM_RETRY_SECS`, and /ready reports
+/// loading until all of them are cached.
+pub async fn leaderboard_warm_system(app_state: AppState) {
+    let retry_secs = env_u64("LEADERBOARD_WARM_RETRY_SECS", 30).max(1);
+
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let mut pending = default_leaderboard_pages();
+        loop {
+            let mut failed = Vec::new();
+            for key in pending {
+                if let Err(e) = load_leaderboard_page(&app_state, key).await {
+                    tracing::error!("Failed to warm leaderboard {:?}: {:?}", key, e);
+                    failed.push(key);
+                }
+            }
+            if failed.is_empty() {
+                break;
+            }
+            pending = failed;
+            tokio::time::sleep(Duration::from_secs(retry_secs)).await;
+        }
+        app_state.leaderboard_warmed.store(true, Ordering::Relaxed);
+        tracing::info!("Leaderboard cache warmed in {} ms", started.elapsed().as_millis());
+    });
+}
+
+/// Leaderboard windows whose ranks are recorded for /leaderboard/movers, from
+/// `MOVERS_WINDOWS` (comma separated, default `60,all`). Invalid entries are dropped.
+pub fn movers_windows_from_env() -> Vec<LeaderboardWindow> {
+    env::var("MOVERS_WINDOWS")
+        .unwrap_or_else(|_| "60,all".to_string())
+        .split(',')
+        .map(str::trim)
+        .filter(|w| !w.is_empty())
+        .filter_map(|w| parse_leaderboard_window(Some(w)).ok())
+        .collect()
+}
+
+/// How many ranks of each movers window are recorded and compared, from `MOVERS_TOP`.
+pub fn movers_top_from_env() -> i64 {
+    env_u64("MOVERS_TOP", 1000).clamp(1, 10_000) as i64
+}
+
+/// How many hours of recorded ranks are kept, from `MOVERS_RETENTION_HOURS` (default a week).
+pub fn movers_retention_hours_from_env() -> i64 {
+    env_u64("MOVERS_RETENTION_HOURS", 24 * 7).max(1) as i64
+}
+
+/// Records the top `MOVERS_TOP` net sol ranks of each movers window every
+/// `MOVERS_SNAPSHOT_INTERVAL_SECS`, keeping `MOVERS_RETENTION_HOURS` of history.
+pub async fn leaderboard_rank_system(app_state: AppState) {
+    let windows = movers_windows_from_env();
+    let interval_secs = env_u64("MOVERS_SNAPSHOT_INTERVAL_SECS", 3600).max(60);
+    let top = movers_top_from_env();
+    let retention_hours = movers_retention_hours_from_env();
+
+    tokio::spawn(async move {
+        loop {
+            let now = chrono::Utc::now().timestamp();
+            for window in windows.iter().copied() {
+                let min_rounds = leaderboard_min_rounds(window, None);
+                let rows = match database::get_leaderboard(&app_state.db_pool, LeaderboardMetric::NetSol, window, min_rounds, None, top, 0).await {
+                    Ok(rows) => rows,
+                    Err(e) => {
+                        tracing::error!("Failed to load leaderboard {} for rank snapshot: {:?}", window.key(), e);
+                        continue;
+                    }
+                };
+                if let Err(e) = database::insert_leaderboard_ranks(&app_state.db_pool, window, now, &rows).await {
+                    tracing::error!("Failed to store leaderboard ranks for {}: {:?}", window.key(), e);
+                }
+            }
+            match database::prune_leaderboard_ranks(&app_state.db_pool, now - retention_hours * 3600).await {
+                Ok(pruned) if pruned > 0 => {
+                    tracing::info!("Pruned {} leaderboard ranks older than {} hours", pruned, retention_hours);
+                },
+                Ok(_) => {},
+                Err(e) => {
+                    tracing::error!("Failed to prune leaderboard ranks: {:?}", e);
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+        }
+    });
+}
+
+/// Periodically prunes miner snapshots older than `SNAPSHOT_RETENTION_DAYS`.
+/// Disabled unless the env var is set.
+pub async fn snapshot_retention_system(app_state: AppState) {
+    let retention_days = if let Ok(Ok(days)) = env::var("SNAPSHOT_RETENTION_DAYS").map(|v| v.parse::<i64>()) {
+        days
+    } else {
+        tracing::info!("SNAPSHOT_RETENTION_DAYS not set, snapshot pruning disabled");
+        return;
+    };
+    let keep_daily = env::var("SNAPSHOT_KEEP_DAILY").map(|v| v != "false").unwrap_or(true);
+    let interval_secs = env_u64("SNAPSHOT_PRUNE_INTERVAL_SECS", 3600);
+    let batch = env_u64("SNAPSHOT_PRUNE_BATCH", 50_000) as i64;
+
+    tokio::spawn(async move {
+        loop {
+            let cutoff = chrono::Utc::now().timestamp() - retention_days * 86400;
+            match database::prune_miner_snapshots(&app_state.db_pool, cutoff, keep_daily, batch).await {
+                Ok(pruned) => {
+                    tracing::info!("Pruned {} miner snapshots older than {} days", pruned, retention_days);
+                },
+                Err(e) => {
+                    tracing::error!("Failed to prune miner snapshots: {:?}", e);
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+        }
+    });
+}
+
+/// Re-runs `PRAGMA optimize` every `OPTIMIZE_INTERVAL_SECS` (default 6h) so planner
+/// stats keep up with table growth. The startup run in main covers the first interval.
+/// Set the interval to 0 to disable.
+pub async fn optimize_system(app_state: AppState) {
+    let interval_secs = env_u64("OPTIMIZE_INTERVAL_SECS", 6 * 3600);
+    if interval_secs == 0 {
+        tracing::info!("OPTIMIZE_INTERVAL_SECS is 0, periodic optimize disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+            let start = Instant::now();
+            match database::optimize(&app_state.db_pool).await {
+                Ok(()) => {
+                    tracing::info!("PRAGMA optimize took {} ms", start.elapsed().as_millis());
+                },
+                Err(e) => {
+                    tracing::error!("Failed to run PRAGMA optimize: {:?}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Truncates the wal every `WAL_CHECKPOINT_INTERVAL_SECS` (default 10m) so continuous
+/// inserts don't leave a large `-wal` file between automatic checkpoints. Set the
+/// interval to 0 to disable.
+pub async fn wal_checkpoint_system(app_state: AppState) {
+    let interval_secs = env_u64("WAL_CHECKPOINT_INTERVAL_SECS", 600);
+    if interval_secs == 0 {
+        tracing::info!("WAL_CHECKPOINT_INTERVAL_SECS is 0, periodic wal checkpoint disabled");
+        return;
+    }
+    let busy_timeout_ms = env_u64("WAL_CHECKPOINT_BUSY_TIMEOUT_MS", 2000);
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+            let start = Instant::now();
+            match database::wal_checkpoint(&app_state.db_pool, busy_timeout_ms).await {
+                Ok(r) if r.busy != 0 => {
+                    tracing::warn!(
+                        "WAL checkpoint busy after {} ms, moved {}/{} pages, retrying next interval",
+                        start.elapsed().as_millis(),
+                        r.checkpointed,
+                        r.log
+                    );
+                },
+                Ok(r) => {
+                    tracing::info!(
+                        "WAL checkpoint moved {}/{} pages in {} ms",
+                        r.checkpointed,
+                        r.log,
+                        start.elapsed().as_millis()
+                    );
+                },
+                Err(e) => {
+                    tracing::error!("Failed to checkpoint WAL: {:?}", e);
+                }
+            }
+        }
+    });
+}
+
+#[derive(Debug, Serialize)]
+pub struct Backup {
+    pub path: String,
+    pub size_bytes: u64,
+}
+
+/// Bumped for every backup so two taken within the same microsecond don't share a name.
+static BACKUP_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// File name prefix of backups taken by `backup_system`, the only ones it prunes.
+const SCHEDULED_BACKUP_PREFIX: &str = "scheduled-";
+
+fn backup_dir() -> String {
+    env::var("BACKUP_DIR").unwrap_or_else(|_| "data/backups".to_string())
+}
+
+/// Copies the database into `BACKUP_DIR` (default `data/backups`) under a timestamped name.
+pub async fn backup_database(app_state: &AppState) -> anyhow::Result<Backup> {
+    take_backup(app_state, "app-").await
+}
+
+async fn take_backup(app_state: &AppState, prefix: &str) -> anyhow::Result<Backup> {
+    let dir = backup_dir();
+    tokio::fs::create_dir_all(&dir).await?;
+    let name = format!(
+        "{}{}-{}.db",
+        prefix,
+        chrono::Utc::now().format("%Y%m%dT%H%M%S%.6fZ"),
+        BACKUP_SEQ.fetch_add(1, Ordering::Relaxed),
+    );
+    let path = std::path::Path::new(&dir).join(name).to_string_lossy().to_string();
+
+    let start = Instant::now();
+    database::vacuum_into(&app_state.db_pool, &path).await?;
+    let size_bytes = tokio::fs::metadata(&path).await?.len();
+    tracing::info!("Backed up database to {} ({} bytes) in {} ms", path, size_bytes, start.elapsed().as_millis());
+
+    Ok(Backup { path, size_bytes })
+}
+
+/// Deletes all but the newest `keep` scheduled backups. Names sort by timestamp.
+async fn prune_scheduled_backups(keep: usize) -> std::io::Result<usize> {
+    let mut names = Vec::new();
+    let mut entries = tokio::fs::read_dir(backup_dir()).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with(SCHEDULED_BACKUP_PREFIX) && name.ends_with(".db") {
+            names.push(entry.path());
+        }
+    }
+    names.sort();
+    let excess = names.len().saturating_sub(keep);
+    for path in &names[..excess] {
+        tokio::fs::remove_file(path).await?;
+    }
+    Ok(excess)
+}
+
+/// Takes a backup every `BACKUP_INTERVAL_SECS`. Disabled unless the env var is set.
+/// Only the newest `BACKUP_RETENTION` (default 7, 0 keeps all) scheduled backups are
+/// kept; backups taken through the admin route are never pruned.
+pub async fn backup_system(app_state: AppState) {
+    let interval_secs = env_u64("BACKUP_INTERVAL_SECS", 0);
+    if interval_secs == 0 {
+        tracing::info!("BACKUP_INTERVAL_SECS not set, scheduled backups disabled");
+        return;
+    }
+    let retention = env_u64("BACKUP_RETENTION", 7) as usize;
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+            if let Err(e) = take_backup(&app_state, SCHEDULED_BACKUP_PREFIX).await {
+                tracing::error!("Failed to back up database: {:?}", e);
+                continue;
+            }
+            if retention == 0 {
+                continue;
+            }
+            match prune_scheduled_backups(retention).await {
+                Ok(0) => {},
+                Ok(n) => tracing::info!("Pruned {} old scheduled backups", n),
+                Err(e) => tracing::error!("Failed to prune scheduled backups: {:?}", e),
+            }
+        }
+    });
+}
+
+/// Loads treasury, board, the last finished round and miners from the latest rows the
+/// indexer wrote, for instances running with `ENABLE_POLLER=false`.
+pub async fn refresh_state_from_db(app_state: &AppState) -> Result<(), sqlx::Error> {
+    if let Some(treasury) = database::get_treasuries(&app_state.db_pool, 1, 0).await?.into_iter().next() {
+        *app_state.treasury.write().await = treasury.into();
+    }
+    if let Some(board) = database::get_board_snapshots(&app_state.db_pool, 1, 0).await?.into_iter().next() {
+        *app_state.board.write().await = AppBoard::from_snapshot(&board, app_state.slot_time_ms);
+    }
+    if let Some(round) = database::get_rounds(&app_state.db_pool, 1, 0, None).await?.into_iter().next() {
+        let known = app_state.rounds.read().await.last().map(|r| r.id);
+        if known != Some(round.id as u64) {
+            let (deployed, count) = database::get_round_squares(&app_state.db_pool, round.id).await?;
+            app_state.rounds.write().await.push(AppRound::from_row(&round, deployed, count));
+        }
+    }
+    let miners = database::get_current_miners(&app_state.db_pool, CurrentMinerFilter::default(), None, -1, 0).await?;
+    if !miners.is_empty() {
+        *app_state.miners.write().await = miners;
+        *app_state.miners_loaded_at.write().await = Some(chrono::Utc::now().timestamp());
+    }
+    Ok(())
+}
+
+/// Re-reads state from the db every `DB_REFRESH_SECS` (default 10) when there is no
+/// poller keeping it current.
+pub async fn db_refresh_system(app_state: AppState) {
+    let interval_secs = env_u64("DB_REFRESH_SECS", 10).max(1);
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+            if let Err(e) = refresh_state_from_db(&app_state).await {
+                tracing::error!("Failed to refresh state from the database: {:?}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,135 @@
+use std::{collections::BTreeMap, fmt::Write, sync::{atomic::{AtomicU64, Ordering}, Mutex}, time::Instant};
+
+/// Upper bounds (seconds) of the rpc latency histogram buckets.
+const BUCKETS_SECS: [f64; 10] = [0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+#[derive(Default, Clone)]
+struct MethodStats {
+    /// Non-cumulative counts per bucket, the last slot is +Inf.
+    buckets: [u64; BUCKETS_SECS.len() + 1],
+    count: u64,
+    sum_secs: f64,
+    errors: u64,
+}
+
+impl MethodStats {
+    fn record(&mut self, secs: f64, ok: bool) {
+        let i = BUCKETS_SECS.iter().position(|b| secs <= *b).unwrap_or(BUCKETS_SECS.len());
+        self.buckets[i] += 1;
+        self.count += 1;
+        self.sum_secs += secs;
+        if !ok {
+            self.errors += 1;
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Since startup, rendered by /metrics.
+    total: BTreeMap<&'static str, MethodStats>,
+    /// Since the last `log_summary`.
+    window: BTreeMap<&'static str, MethodStats>,
+}
+
+/// Latency and outcome of rpc calls keyed by method.
+#[derive(Default)]
+pub struct RpcMetrics {
+    inner: Mutex<Inner>,
+    /// getProgramAccounts queries the last miners fetch needed, 1 when unpartitioned.
+    miner_fetch_partitions: AtomicU64,
+    /// Accounts in the last miners fetch that didn't parse as a Miner.
+    skipped_miner_accounts: AtomicU64,
+    /// Data size of a Miner account as read at startup, 0 if it couldn't be read.
+    miner_account_size: AtomicU64,
+}
+
+impl RpcMetrics {
+    pub fn record(&self, method: &'static str, secs: f64, ok: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.total.entry(method).or_default().record(secs, ok);
+        inner.window.entry(method).or_default().record(secs, ok);
+    }
+
+    /// Awaits `fut` and records how long it took and whether it returned Ok.
+    pub async fn time<T, E>(
+        &self,
+        method: &'static str,
+        fut: impl std::future::Future<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        let start = Instant::now();
+        let res = fut.await;
+        self.record(method, start.elapsed().as_secs_f64(), res.is_ok());
+        res
+    }
+
+    pub fn set_miner_fetch_partitions(&self, partitions: u64) {
+        self.miner_fetch_partitions.store(partitions, Ordering::Relaxed);
+    }
+
+    pub fn set_skipped_miner_accounts(&self, skipped: u64) {
+        self.skipped_miner_accounts.store(skipped, Ordering::Relaxed);
+    }
+
+    pub fn set_miner_account_size(&self, size: u64) {
+        self.miner_account_size.store(size, Ordering::Relaxed);
+    }
+
+    pub fn skipped_miner_accounts(&self) -> u64 {
+        self.skipped_miner_accounts.load(Ordering::Relaxed)
+    }
+
+    /// Prometheus text exposition of everything recorded since startup.
+    pub fn render(&self) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        out.push_str("# HELP rpc_request_duration_seconds Latency of rpc calls by method.\n");
+        out.push_str("# TYPE rpc_request_duration_seconds histogram\n");
+        for (method, s) in inner.total.iter() {
+            let mut cumulative = 0;
+            for (i, le) in BUCKETS_SECS.iter().enumerate() {
+                cumulative += s.buckets[i];
+                let _ = writeln!(out, "rpc_request_duration_seconds_bucket{{method=\"{}\",le=\"{}\"}} {}", method, le, cumulative);
+            }
+            let _ = writeln!(out, "rpc_request_duration_seconds_bucket{{method=\"{}\",le=\"+Inf\"}} {}", method, s.count);
+            let _ = writeln!(out, "rpc_request_duration_seconds_sum{{method=\"{}\"}} {}", method, s.sum_secs);
+            let _ = writeln!(out, "rpc_request_duration_seconds_count{{method=\"{}\"}} {}", method, s.count);
+        }
+
+        out.push_str("# HELP rpc_request_errors_total Failed rpc calls by method.\n");
+        out.push_str("# TYPE rpc_request_errors_total counter\n");
+        for (method, s) in inner.total.iter() {
+            let _ = writeln!(out, "rpc_request_errors_total{{method=\"{}\"}} {}", method, s.errors);
+        }
+
+        out.push_str("# HELP miner_fetch_partitions getProgramAccounts queries used by the last miners fetch.\n");
+        out.push_str("# TYPE miner_fetch_partitions gauge\n");
+        let _ = writeln!(out, "miner_fetch_partitions {}", self.miner_fetch_partitions.load(Ordering::Relaxed));
+        out.push_str("# HELP skipped_miner_accounts Accounts in the last miners fetch that failed to parse as a Miner.\n");
+        out.push_str("# TYPE skipped_miner_accounts gauge\n");
+        let _ = writeln!(out, "skipped_miner_accounts {}", self.skipped_miner_accounts());
+        out.push_str("# HELP miner_account_size_bytes Data size of a Miner account as read at startup, 0 if unknown.\n");
+        out.push_str("# TYPE miner_account_size_bytes gauge\n");
+        let _ = writeln!(out, "miner_account_size_bytes {}", self.miner_account_size.load(Ordering::Relaxed));
+
+        out
+    }
+
+    /// Logs the average latency per method since the last call and starts a new window.
+    pub fn log_summary(&self) {
+        let window = std::mem::take(&mut self.inner.lock().unwrap().window);
+        for (method, s) in window.iter() {
+            if s.count == 0 {
+                continue;
+            }
+            tracing::info!(
+                "rpc {}: {} calls, avg {:.0} ms, {} errors",
+                method,
+                s.count,
+                s.sum_secs * 1000.0 / s.count as f64,
+                s.errors
+            );
+        }
+    }
+}
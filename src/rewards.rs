@@ -0,0 +1,161 @@
+use ore_api::{consts::SPLIT_ADDRESS, state::Round};
+
+/// Round-level inputs of the reward formula, the same for every deployment in the round.
+#[derive(Debug, Clone, Copy)]
+pub struct RoundRewardParams {
+    pub winning_square: usize,
+    /// Total deployed on the winning square, the denominator of every pro-rata share.
+    pub denom: u64,
+    pub total_winnings: u64,
+    pub top_miner_reward: u64,
+    /// Non-zero only if the motherlode was hit this round.
+    pub motherlode: u64,
+    /// Every miner on the winning square shares top_miner_reward pro-rata.
+    pub is_split: bool,
+    /// Winner-takes-all sample, None when split or nothing was deployed on the winning square.
+    pub top_sample: Option<u64>,
+}
+
+impl RoundRewardParams {
+    /// None until the round has an rng.
+    pub fn from_round(round: &Round) -> Option<Self> {
+        let r = round.rng()?;
+        let winning_square = round.winning_square(r);
+        let denom = round.deployed[winning_square];
+        let is_split = round.top_miner == SPLIT_ADDRESS;
+        let top_sample = (!is_split && denom > 0).then(|| round.top_miner_sample(r, winning_square));
+        Some(RoundRewardParams {
+            winning_square,
+            denom,
+            total_winnings: round.total_winnings,
+            top_miner_reward: round.top_miner_reward,
+            motherlode: round.motherlode,
+            is_split,
+            top_sample,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeploymentReward {
+    pub sol: u64,
+    pub ore: u64,
+    /// Taken from the refund of a winning deployment, zero otherwise.
+    pub admin_fee: u64,
+    /// The deployment's cumulative interval covered the top miner sample.
+    pub top_miner: bool,
+}
+
+/// Rewards for one deployment of `amount` on `square_index`, where `cumulative` is the
+/// SOL already on that square before it. Losing squares earn nothing.
+pub fn compute_deployment_reward(params: &RoundRewardParams, square_index: usize, amount: u64, cumulative: u64) -> DeploymentReward {
+    let denom = params.denom;
+    if square_index != params.winning_square || denom == 0 {
+        return DeploymentReward::default();
+    }
+    let pro_rata = |total: u64| ((total as u128 * amount as u128) / denom as u128) as u64;
+
+    // SOL: the deployment back minus the admin fee (max(1, amount/100)), plus its share of the winnings
+    let admin_fee = (amount / 100).max(1);
+    let sol = amount.saturating_sub(admin_fee).saturating_add(pro_rata(params.total_winnings));
+
+    // ORE: top miner reward split pro-rata, or all of it if the interval covers the sample
+    let mut ore: u64 = 0;
+    let mut top_miner = false;
+    if params.is_split {
+        ore = ore.saturating_add(pro_rata(params.top_miner_reward));
+    } else if let Some(sample) = params.top_sample {
+        let end = cumulative.saturating_add(amount);
+        if sample >= cumulative && sample < end {
+            ore = ore.saturating_add(params.top_miner_reward);
+            top_miner = true;
+        }
+    }
+
+    if params.motherlode > 0 {
+        ore = ore.saturating_add(pro_rata(params.motherlode));
+    }
+
+    DeploymentReward { sol, ore, admin_fee, top_miner }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE_ORE: u64 = 100_000_000_000;
+
+    /// Square 3 won with 4 SOL on it and 10 SOL of winnings to share.
+    fn params() -> RoundRewardParams {
+        RoundRewardParams {
+            winning_square: 3,
+            denom: 4_000_000_000,
+            total_winnings: 10_000_000_000,
+            top_miner_reward: ONE_ORE,
+            motherlode: 0,
+            is_split: true,
+            top_sample: None,
+        }
+    }
+
+    #[test]
+    fn non_winning_square_earns_nothing() {
+        let reward = compute_deployment_reward(&params(), 4, 1_000_000_000, 0);
+        assert_eq!(reward, DeploymentReward::default());
+    }
+
+    #[test]
+    fn split_shares_winnings_and_ore_pro_rata() {
+        // a quarter of the winning square
+        let reward = compute_deployment_reward(&params(), 3, 1_000_000_000, 0);
+        assert_eq!(reward.admin_fee, 10_000_000);
+        assert_eq!(reward.sol, 1_000_000_000 - 10_000_000 + 2_500_000_000);
+        assert_eq!(reward.ore, ONE_ORE / 4);
+        assert!(!reward.top_miner);
+    }
+
+    #[test]
+    fn winner_takes_all_pays_the_sampled_deployment() {
+        let params = RoundRewardParams { is_split: false, top_sample: Some(1_500_000_000), ..params() };
+        // covers [1, 2) SOL of the square's cumulative
+        let reward = compute_deployment_reward(&params, 3, 1_000_000_000, 1_000_000_000);
+        assert_eq!(reward.ore, ONE_ORE);
+        assert!(reward.top_miner);
+        assert_eq!(reward.sol, 1_000_000_000 - 10_000_000 + 2_500_000_000);
+    }
+
+    #[test]
+    fn winner_takes_all_miss_earns_no_ore() {
+        let params = RoundRewardParams { is_split: false, top_sample: Some(2_000_000_000), ..params() };
+        // the interval end is exclusive
+        let reward = compute_deployment_reward(&params, 3, 1_000_000_000, 1_000_000_000);
+        assert_eq!(reward.ore, 0);
+        assert!(!reward.top_miner);
+        assert_eq!(reward.sol, 1_000_000_000 - 10_000_000 + 2_500_000_000);
+    }
+
+    #[test]
+    fn motherlode_is_shared_pro_rata_on_top() {
+        let params = RoundRewardParams { motherlode: 40 * ONE_ORE, ..params() };
+        let reward = compute_deployment_reward(&params, 3, 1_000_000_000, 0);
+        assert_eq!(reward.ore, ONE_ORE / 4 + 10 * ONE_ORE);
+
+        let params = RoundRewardParams { is_split: false, top_sample: Some(0), ..params };
+        let reward = compute_deployment_reward(&params, 3, 1_000_000_000, 0);
+        assert_eq!(reward.ore, ONE_ORE + 10 * ONE_ORE);
+    }
+
+    #[test]
+    fn empty_winning_square_earns_nothing() {
+        let params = RoundRewardParams { denom: 0, ..params() };
+        let reward = compute_deployment_reward(&params, 3, 1_000_000_000, 0);
+        assert_eq!(reward, DeploymentReward::default());
+    }
+
+    #[test]
+    fn admin_fee_is_at_least_one_lamport() {
+        let reward = compute_deployment_reward(&params(), 3, 50, 0);
+        assert_eq!(reward.admin_fee, 1);
+        assert_eq!(reward.sol, 49 + 125);
+    }
+}
@@ -1,16 +1,16 @@
 
-use std::{env, str::FromStr, time::Duration};
+use std::{collections::{hash_map::RandomState, HashMap}, env, future::Future, hash::{BuildHasher, Hasher}, str::FromStr, sync::{atomic::{AtomicU64, Ordering}, OnceLock}, time::Duration};
 
-use ore_api::{consts::{SPLIT_ADDRESS, TREASURY_ADDRESS}, state::{round_pda, Board, Miner, Round, Treasury}};
+use ore_api::state::{Board, Miner, Round, Treasury};
 use serde::Deserialize;
-use solana_account_decoder_client_types::UiAccountEncoding;
-use solana_client::{nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient}, rpc_config::RpcAccountInfoConfig, rpc_filter::RpcFilterType};
-use solana_sdk::{commitment_config::{CommitmentConfig, CommitmentLevel}, slot_hashes::SlotHashes};
-use steel::{AccountDeserialize, Numeric, Pubkey};
-use tokio::time::Instant;
+use solana_account_decoder_client_types::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{client_error::{ClientError, ClientErrorKind, Result as ClientResult}, nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient}, rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig}, rpc_filter::{Memcmp, RpcFilterType}, rpc_request::RpcError};
+use solana_sdk::{account::Account, commitment_config::{CommitmentConfig, CommitmentLevel}, slot_hashes::SlotHashes};
+use steel::{AccountDeserialize, Discriminator, Numeric, Pubkey};
+use tokio::{sync::{watch, Mutex}, task::JoinHandle, time::Instant};
 use tokio_stream::StreamExt;
 
-use crate::{app_state::{AppLiveDeployment, AppMiner, AppRound, AppState, AppWinningSquare}, database::{self, insert_deployments, insert_miner_snapshots, insert_round, insert_treasury, CreateDeployment, CreateMinerSnapshot, CreateTreasury, RoundRow}, entropy_api::ORE_VAR_ADDRESS, BOARD_ADDRESS};
+use crate::{chain::ChainSource, cluster::addresses, rewards::{compute_deployment_reward, RoundRewardParams}, app_state::{AppBoard, AppLiveDeployment, AppMiner, AppRound, AppState, AppWinningSquare, RpcStatus}, database::{self, insert_board_snapshot, insert_deployments, insert_miner_snapshots, insert_round, insert_treasury, CreateBoardSnapshot, CreateDeployment, CreateMinerSnapshot, CreateTreasury, RoundGap, RoundRow}};
 
 pub struct MinerSnapshot {
     round_id: u64,
@@ -27,471 +27,1104 @@ struct EntropyApiSeed {
     seed: Vec<u8>,
 }
 
-pub async fn update_data_system(connection: RpcClient, app_state: AppState) {
-    tracing::info!("Starting update_data_system");
-    let db_pool = app_state.db_pool.clone();
+/// Commitment used for every RPC call and subscription, from `RPC_COMMITMENT`
+/// (confirmed/finalized/processed, default confirmed).
+///
+/// Finalized is the safest for round finalization but lags confirmed by a few
+/// dozen slots, which delays round processing after the board resets.
+/// Processed is lowest latency but can observe rounds that later get rolled back.
+pub fn commitment_from_env() -> CommitmentConfig {
+    let commitment = match env::var("RPC_COMMITMENT").unwrap_or_default().to_lowercase().as_str() {
+        "finalized" => CommitmentLevel::Finalized,
+        "processed" => CommitmentLevel::Processed,
+        "" | "confirmed" => CommitmentLevel::Confirmed,
+        other => {
+            tracing::warn!("Unknown RPC_COMMITMENT {:?}, using confirmed", other);
+            CommitmentLevel::Confirmed
+        }
+    };
+    CommitmentConfig { commitment }
+}
+
+/// Per-request http timeout for rpc calls, from `RPC_TIMEOUT_SECS` (default 20), so a hung
+/// node fails the call instead of blocking the poll loop.
+pub fn rpc_timeout_from_env() -> Duration {
+    let secs = env::var("RPC_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(20);
+    Duration::from_secs(secs)
+}
 
-    let entropy_seed_api = env::var("ENTROPY_SEED_API").expect("ENTROPY_SEED_API must be set");
+/// Rpc client for `url` (without the scheme) with the given timeout and commitment.
+pub fn connect(url: &str, timeout: Duration, commitment: CommitmentConfig) -> RpcClient {
+    RpcClient::new_with_timeout_and_commitment("https://".to_string() + url, timeout, commitment)
+}
 
-    tokio::spawn(async move {
-        let mut board_snapshot = false;
-        let mut miners_snapshot = MinerSnapshot {
-            round_id: 0,
-            miners: vec![],
-            completed: false,
-        };
-        let mut emitted_winning_square = false;
+fn is_timeout(e: &ClientError) -> bool {
+    matches!(e.kind(), ClientErrorKind::Reqwest(r) if r.is_timeout())
+}
+
+/// Endpoints from the comma separated `RPC_URLS`, falling back to the single `RPC_URL`.
+pub fn rpc_urls_from_env() -> Vec<String> {
+    let urls: Vec<String> = env::var("RPC_URLS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|u| u.trim().to_string())
+        .filter(|u| !u.is_empty())
+        .collect();
+    if !urls.is_empty() {
+        return urls;
+    }
+    vec![env::var("RPC_URL").expect("RPC_URL or RPC_URLS must be set")]
+}
+
+/// Token bucket shared by every outbound rpc call, refilled at `RPC_MAX_CALLS_PER_SEC`
+/// with a burst of one second's worth of calls. Unset or 0 disables limiting.
+pub struct RpcRateLimiter {
+    rate: f64,
+    /// Available tokens and when they were last refilled.
+    bucket: Mutex<(f64, Instant)>,
+    throttled_calls: AtomicU64,
+    throttled_micros: AtomicU64,
+}
+
+impl RpcRateLimiter {
+    pub fn from_env() -> Self {
+        let rate = env::var("RPC_MAX_CALLS_PER_SEC")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|r| *r > 0.0)
+            .unwrap_or(0.0);
+        if rate > 0.0 {
+            tracing::info!("Limiting RPC calls to {} per second", rate);
+        }
+        Self {
+            rate,
+            bucket: Mutex::new((rate, Instant::now())),
+            throttled_calls: AtomicU64::new(0),
+            throttled_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Waits until a token is available and takes it.
+    pub async fn acquire(&self) {
+        if self.rate <= 0.0 {
+            return;
+        }
+        let mut waited = Duration::ZERO;
         loop {
-            let treasury = if let Ok(treasury) = connection.get_account_data(&TREASURY_ADDRESS).await {
-                if let Ok(treasury) = Treasury::try_from_bytes(&treasury) {
-                    treasury.clone()
-                } else {
-                    tracing::error!("Failed to parse Treasury account");
-                    tokio::time::sleep(Duration::from_secs(2)).await;
-                    continue
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+                let now = Instant::now();
+                bucket.0 = (bucket.0 + now.duration_since(bucket.1).as_secs_f64() * self.rate).min(self.rate.max(1.0));
+                bucket.1 = now;
+                if bucket.0 >= 1.0 {
+                    bucket.0 -= 1.0;
+                    break;
                 }
-            } else {
-                tracing::error!("Failed to load treasury account data");
-                tokio::time::sleep(Duration::from_secs(2)).await;
-                continue
+                Duration::from_secs_f64((1.0 - bucket.0) / self.rate)
             };
+            tokio::time::sleep(wait).await;
+            waited += wait;
+        }
+        if !waited.is_zero() {
+            self.throttled_calls.fetch_add(1, Ordering::Relaxed);
+            self.throttled_micros.fetch_add(waited.as_micros() as u64, Ordering::Relaxed);
+        }
+    }
 
-            // update treasury
-            let r = app_state.treasury.clone();
-            let mut l = r.write().await;
-            *l = treasury.into();
-            drop(l);
+    /// True when the next call would have to wait for a token.
+    pub async fn is_throttled(&self) -> bool {
+        if self.rate <= 0.0 {
+            return false;
+        }
+        let bucket = self.bucket.lock().await;
+        bucket.0 + bucket.1.elapsed().as_secs_f64() * self.rate < 1.0
+    }
 
-            tokio::time::sleep(Duration::from_secs(1)).await;
+    /// Prometheus text for the limiter, appended to /metrics.
+    pub fn render(&self) -> String {
+        format!(
+            "# HELP rpc_rate_limit_per_second Configured RPC_MAX_CALLS_PER_SEC, 0 when unlimited.\n\
+             # TYPE rpc_rate_limit_per_second gauge\n\
+             rpc_rate_limit_per_second {}\n\
+             # HELP rpc_throttled_calls_total Rpc calls that had to wait for a token.\n\
+             # TYPE rpc_throttled_calls_total counter\n\
+             rpc_throttled_calls_total {}\n\
+             # HELP rpc_throttled_seconds_total Time spent waiting for tokens.\n\
+             # TYPE rpc_throttled_seconds_total counter\n\
+             rpc_throttled_seconds_total {}\n",
+            self.rate,
+            self.throttled_calls.load(Ordering::Relaxed),
+            self.throttled_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+        )
+    }
+}
 
-            let board = if let Ok(board) = connection.get_account_data(&BOARD_ADDRESS).await {
-                if let Ok(board) = Board::try_from_bytes(&board) {
-                    board.clone()
-                } else {
-                    tracing::error!("Failed to parse Board account");
-                    tokio::time::sleep(Duration::from_secs(2)).await;
-                    continue;
-                }
+/// Every outbound rpc call goes through here: waits on the rate limiter, then records
+/// latency. The call itself isn't sent until `fut` is first polled.
+async fn rpc_call<T>(
+    app_state: &AppState,
+    method: &'static str,
+    fut: impl Future<Output = ClientResult<T>>,
+) -> ClientResult<T> {
+    app_state.rpc_limiter.acquire().await;
+    let res = app_state.rpc_metrics.time(method, fut).await;
+    if matches!(&res, Err(e) if is_timeout(e)) {
+        tracing::warn!("RPC {} timed out after {}s on {}", method, app_state.rpc_timeout.as_secs(), app_state.rpc_url());
+    }
+    res
+}
+
+/// Builds a fresh rpc client for the active endpoint with the configured timeout and commitment.
+pub fn new_rpc_client(app_state: &AppState) -> RpcClient {
+    connect(app_state.rpc_url(), app_state.rpc_timeout, app_state.commitment)
+}
+
+/// Spawns the poll loop. It checks `shutdown` between cycles, so a cycle's inserts always
+/// finish before the task exits; await the returned handle to wait for that.
+pub async fn update_data_system(connection: RpcClient, app_state: AppState, shutdown: watch::Receiver<bool>) -> JoinHandle<()> {
+    tracing::info!("Starting update_data_system");
+    let config = PollConfig::from_env();
+
+    tokio::spawn(async move {
+        // one client per endpoint, main already built the first
+        let mut clients = vec![connection];
+        clients.extend(app_state.rpc_urls.iter().skip(1).map(|url| {
+            connect(url, app_state.rpc_timeout, app_state.commitment)
+        }));
+        let rebuild_state = app_state.clone();
+        poll_loop(clients, move || new_rpc_client(&rebuild_state), app_state, config, shutdown).await;
+    })
+}
+
+/// Poll loop settings, read from the environment once at startup.
+struct PollConfig {
+    entropy_seed_api: String,
+    reconnect_after: u32,
+    metrics_log_every: u64,
+    stall_slots: i64,
+}
+
+impl PollConfig {
+    fn from_env() -> Self {
+        let entropy_seed_api = env::var("ENTROPY_SEED_API").expect("ENTROPY_SEED_API must be set");
+        // consecutive failed polls before the client is probed and, if still failing, rebuilt
+        let reconnect_after: u32 = env::var("RPC_RECONNECT_AFTER")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5);
+        // poll cycles between rpc latency log lines
+        let metrics_log_every: u64 = env::var("RPC_METRICS_LOG_EVERY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(100);
+        // slots past end_slot with the same round id before the round is reported as stalled
+        let stall_slots: i64 = env::var("ROUND_STALL_SLOTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(150);
+
+        PollConfig { entropy_seed_api, reconnect_after, metrics_log_every, stall_slots }
+    }
+}
+
+/// Polls treasury, board and rounds from `clients[active]`, failing over to the next
+/// client (or one from `rebuild` when there's only one) after repeated failures.
+async fn poll_loop<C: ChainSource>(
+    mut clients: Vec<C>,
+    rebuild: impl Fn() -> C,
+    app_state: AppState,
+    config: PollConfig,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let db_pool = app_state.db_pool.clone();
+    let db_2_pool = app_state.db_2_pool.clone();
+    // READ_ONLY keeps the in-memory board/round/miners live but persists nothing
+    let read_only = app_state.read_only;
+    let PollConfig { entropy_seed_api, reconnect_after, metrics_log_every, stall_slots } = config;
+
+    let mut active = 0;
+    let mut board_snapshot = false;
+    let mut miners_snapshot = resume_miner_snapshot(&app_state).await;
+    let mut emitted_winning_square = false;
+    let mut last_board_round_id: u64 = 0;
+    let mut rpc_failures: u32 = 0;
+    let mut cycles: u64 = 0;
+    loop {
+        if *shutdown.borrow() {
+            tracing::info!("Poller stopped for shutdown");
+            break;
+        }
+        let connection = &clients[active];
+        cycles += 1;
+        if metrics_log_every > 0 && cycles.is_multiple_of(metrics_log_every) {
+            app_state.rpc_metrics.log_summary();
+        }
+
+        let treasury = if let Ok(treasury) = rpc_call(&app_state, "get_account_data", connection.get_account_data(&addresses().treasury)).await {
+            if let Ok(treasury) = Treasury::try_from_bytes(&treasury) {
+                treasury.clone()
             } else {
-                tracing::error!("Failed to load board account data");
-                tokio::time::sleep(Duration::from_secs(2)).await;
-                continue;
-            };
+                tracing::error!("Failed to parse Treasury account");
+                tokio::time::sleep(jittered(Duration::from_secs(2))).await;
+                continue
+            }
+        } else {
+            tracing::error!("Failed to load treasury account data");
+            rpc_failures += 1;
+            if rpc_failures >= reconnect_after {
+                *app_state.rpc_status.write().await = RpcStatus::Reconnecting;
+                if clients.len() > 1 {
+                    active = (active + 1) % clients.len();
+                    app_state.rpc_endpoint.store(active, Ordering::Relaxed);
+                    tracing::warn!("RPC failed {} polls in a row, failing over to endpoint {}", rpc_failures, active);
+                } else if rpc_call(&app_state, "get_slot", connection.get_slot()).await.is_err() {
+                    tracing::warn!("RPC failed {} polls in a row, rebuilding client", rpc_failures);
+                    clients[active] = rebuild();
+                }
+                rpc_failures = 0;
+            }
+            tokio::time::sleep(jittered(Duration::from_secs(2))).await;
+            continue
+        };
+
+        rpc_failures = 0;
+        if *app_state.rpc_status.read().await != RpcStatus::Ok {
+            tracing::info!("RPC recovered");
+            *app_state.rpc_status.write().await = RpcStatus::Ok;
+        }
 
-            // update board
-            let r = app_state.board.clone();
-            let mut l = r.write().await;
-            *l = board.into();
-            drop(l);
+        // update treasury
+        let r = app_state.treasury.clone();
+        let mut l = r.write().await;
+        *l = treasury.into();
+        drop(l);
 
-            let last_deployable_slot = board.end_slot;
-            let current_slot = if let Ok(current_slot) = connection.get_slot().await {
-                current_slot
+        tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+
+        let board = if let Ok(board) = rpc_call(&app_state, "get_account_data", connection.get_account_data(&addresses().board)).await {
+            if let Ok(board) = Board::try_from_bytes(&board) {
+                board.clone()
             } else {
-                tracing::error!("Failed to get slot from rpc");
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                tracing::error!("Failed to parse Board account");
+                tokio::time::sleep(jittered(Duration::from_secs(2))).await;
                 continue;
-            };
+            }
+        } else {
+            tracing::error!("Failed to load board account data");
+            tokio::time::sleep(jittered(Duration::from_secs(2))).await;
+            continue;
+        };
 
-            let slots_left_in_round = last_deployable_slot as i64 - current_slot as i64;
+        // record board history once per round
+        if !read_only && board.round_id != last_board_round_id {
+            if let Err(e) = insert_board_snapshot(&db_pool, &db_2_pool, &CreateBoardSnapshot::from(board)).await {
+                tracing::error!("Failed to insert board snapshot: {:?}", e);
+            } else {
+                last_board_round_id = board.round_id;
+            }
+        }
 
-            println!("Slots left for round: {}", slots_left_in_round);
-            tokio::time::sleep(Duration::from_secs(1)).await;
+        let last_deployable_slot = board.end_slot;
+        let current_slot = if let Ok(current_slot) = rpc_call(&app_state, "get_slot", connection.get_slot()).await {
+            current_slot
+        } else {
+            tracing::error!("Failed to get slot from rpc");
+            tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+            continue;
+        };
 
-            if slots_left_in_round <= 0 {
-                if !board_snapshot {
-                    tracing::info!("Updating data");
-                    let round = if let Ok(round) = connection.get_account_data(&round_pda(board.round_id).0).await {
-                        if let Ok(round) = Round::try_from_bytes(&round) {
-                            round.clone()
-                        } else {
-                            tracing::error!("Failed to parse Round account");
-                            tokio::time::sleep(Duration::from_secs(1)).await;
-                            continue;
-                        }
+        let slots_left_in_round = last_deployable_slot as i64 - current_slot as i64;
+
+        // update board
+        let mut app_board = AppBoard::new(board, current_slot, app_state.slot_time_ms);
+        app_board.block_height = rpc_call(&app_state, "get_block_height", connection.get_block_height()).await.ok();
+        app_board.stalled = board.end_slot != u64::MAX && slots_left_in_round < -stall_slots;
+        if app_board.stalled && !app_state.board.read().await.stalled {
+            tracing::warn!(
+                "Round {} is {} slots past its end slot without advancing",
+                board.round_id,
+                -slots_left_in_round
+            );
+        }
+        let r = app_state.board.clone();
+        let mut l = r.write().await;
+        *l = app_board;
+        drop(l);
+
+        println!("Slots left for round: {}", slots_left_in_round);
+        tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+
+        if slots_left_in_round <= 0 {
+            if !board_snapshot {
+                tracing::info!("Updating data");
+                let round = if let Ok(round) = rpc_call(&app_state, "get_account_data", connection.get_account_data(&addresses().round(board.round_id))).await {
+                    if let Ok(round) = Round::try_from_bytes(&round) {
+                        round.clone()
                     } else {
-                        tracing::error!("Failed to load round account data");
-                        tokio::time::sleep(Duration::from_secs(1)).await;
-                        continue
-                    };
-
-                    let mut miners: Vec<AppMiner> = vec![];
-                    if let Ok(miners_data_raw) = connection.get_program_accounts_with_config(
-                        &ore_api::id(),
-                        solana_client::rpc_config::RpcProgramAccountsConfig { 
-                            filters: Some(vec![RpcFilterType::DataSize(size_of::<Miner>() as u64 + 8)]),
-                            account_config: solana_client::rpc_config::RpcAccountInfoConfig {
-                                encoding: Some(UiAccountEncoding::Base64),
-                                data_slice: None,
-                                commitment: Some(CommitmentConfig { commitment: CommitmentLevel::Confirmed }),
-                                min_context_slot: None,
-                            },
-                            with_context: None,
-                            sort_results: None
-                        } 
-                    ).await {
-                        for miner_data in miners_data_raw {
-                            if let Ok(miner) = Miner::try_from_bytes(&miner_data.1.data) {
-                                let mut miner = *miner;
-                                miner.refined_ore = infer_refined_ore(&miner, &treasury);
-                                miners.push(miner.clone().into());
-                            }
-                        }
+                        tracing::error!("Failed to parse Round account");
+                        tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+                        continue;
                     }
+                } else {
+                    tracing::error!("Failed to load round account data");
+                    tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+                    continue
+                };
 
-                    if miners.len() > 0 {
-                        miners_snapshot.round_id = round.id;
-                        miners_snapshot.miners = miners.clone();
-                        miners_snapshot.completed = false;
-                        miners.sort_by(|a, b| b.rewards_ore.partial_cmp(&a.rewards_ore).unwrap());
-
-                        tracing::info!("Setting miners snapshot completed to false");
-                        
-                    } else {
-                        miners_snapshot.round_id = round.id;
-                        miners_snapshot.miners = vec![];
-                        miners_snapshot.completed = true;
-                        tracing::info!("Setting miners snapshot completed to true");
+                let mut miners: Vec<AppMiner> = vec![];
+                if let Ok((miners_data_raw, partitions)) = get_miner_accounts(app_state.commitment, |config| {
+                    rpc_call(&app_state, "get_program_accounts", connection.get_program_accounts_with_config(&addresses().program_id, config))
+                }).await {
+                    app_state.rpc_metrics.set_miner_fetch_partitions(partitions);
+                    let skipped;
+                    (miners, skipped) = parse_miner_accounts(&miners_data_raw, &treasury);
+                    app_state.rpc_metrics.set_skipped_miner_accounts(skipped);
+                    if miners_data_raw.is_empty() && !app_state.miners.read().await.is_empty() {
+                        tracing::warn!(
+                            "Miners fetch returned no accounts of {} bytes after previously loading miners, the Miner layout may have changed",
+                            size_of::<Miner>() + 8
+                        );
                     }
-                    board_snapshot = true;
                 }
-                if !emitted_winning_square {
-                    println!("Checking and Emitting for winning square");
-                    if let Ok(res) = reqwest::get(&entropy_seed_api).await {
-                        if let Ok(d) = res.json::<EntropyApiSeed>().await {
-                            let  entropy_var = if let Ok(v) = connection.get_account_data(&ORE_VAR_ADDRESS).await {
-                                if let Ok(ovar) = crate::entropy_api::Var::try_from_bytes(&v) {
-                                    ovar.clone()
-                                } else {
-                                    tracing::error!("Failed to parse Var account");
-                                    tokio::time::sleep(Duration::from_secs(1)).await;
-                                    continue
-                                }
+
+                if miners.len() > 0 {
+                    miners_snapshot.round_id = round.id;
+                    miners_snapshot.miners = miners.clone();
+                    miners_snapshot.completed = false;
+                    miners.sort_by(|a, b| b.rewards_ore.partial_cmp(&a.rewards_ore).unwrap());
+
+                    tracing::info!("Setting miners snapshot completed to false");
+
+                } else {
+                    miners_snapshot.round_id = round.id;
+                    miners_snapshot.miners = vec![];
+                    miners_snapshot.completed = true;
+                    tracing::info!("Setting miners snapshot completed to true");
+                }
+                save_snapshot_state(&app_state, miners_snapshot.round_id, miners_snapshot.completed).await;
+                board_snapshot = true;
+            }
+            if !emitted_winning_square {
+                println!("Checking and Emitting for winning square");
+                if let Ok(res) = reqwest::get(&entropy_seed_api).await {
+                    if let Ok(d) = res.json::<EntropyApiSeed>().await {
+                        let  entropy_var = if let Ok(v) = rpc_call(&app_state, "get_account_data", connection.get_account_data(&addresses().ore_var)).await {
+                            if let Ok(ovar) = crate::entropy_api::Var::try_from_bytes(&v) {
+                                ovar.clone()
                             } else {
-                                tracing::error!("Failed to load var account data");
-                                tokio::time::sleep(Duration::from_secs(1)).await;
-                                continue
-                            };
-                            if d.commit != entropy_var.commit {
-                                tracing::info!("Missmatching commits..trying again..");
-                                tokio::time::sleep(Duration::from_secs(2)).await;
+                                tracing::error!("Failed to parse Var account");
+                                tokio::time::sleep(jittered(Duration::from_secs(1))).await;
                                 continue
                             }
-                            tokio::time::sleep(Duration::from_millis(200)).await;
-                            match connection.get_account_data(&Pubkey::from_str("SysvarS1otHashes111111111111111111111111111").unwrap()).await {
-                                Ok(data) => {
-                                    let slot_hashes =
-                                        bincode::deserialize::<SlotHashes>(&data).unwrap();
-                                    if let Some(slot_hash) = slot_hashes.get(&entropy_var.end_at) {
-                                        let s_hash =
-                                            solana_program::keccak::hashv(&[&slot_hash.to_bytes(), &d.seed, &entropy_var.samples.to_le_bytes()])
-                                                .to_bytes();
-                                        tokio::time::sleep(Duration::from_millis(200)).await;
-                                        let mut round = if let Ok(round) = connection.get_account_data(&round_pda(board.round_id).0).await {
-                                            if let Ok(round) = Round::try_from_bytes(&round) {
-                                                round.clone()
-                                            } else {
-                                                tracing::error!("Failed to parse Round account");
-                                                tokio::time::sleep(Duration::from_secs(1)).await;
-                                                continue;
-                                            }
+                        } else {
+                            tracing::error!("Failed to load var account data");
+                            tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+                            continue
+                        };
+                        if d.commit != entropy_var.commit {
+                            tracing::info!("Missmatching commits..trying again..");
+                            tokio::time::sleep(jittered(Duration::from_secs(2))).await;
+                            continue
+                        }
+                        tokio::time::sleep(jittered(Duration::from_millis(200))).await;
+                        match rpc_call(&app_state, "get_account_data", connection.get_account_data(&Pubkey::from_str("SysvarS1otHashes111111111111111111111111111").unwrap())).await {
+                            Ok(data) => {
+                                let slot_hashes =
+                                    bincode::deserialize::<SlotHashes>(&data).unwrap();
+                                if let Some(slot_hash) = slot_hashes.get(&entropy_var.end_at) {
+                                    let s_hash =
+                                        solana_program::keccak::hashv(&[&slot_hash.to_bytes(), &d.seed, &entropy_var.samples.to_le_bytes()])
+                                            .to_bytes();
+                                    tokio::time::sleep(jittered(Duration::from_millis(200))).await;
+                                    let mut round = if let Ok(round) = rpc_call(&app_state, "get_account_data", connection.get_account_data(&addresses().round(board.round_id))).await {
+                                        if let Ok(round) = Round::try_from_bytes(&round) {
+                                            round.clone()
                                         } else {
-                                            tracing::error!("Failed to load round account data");
-                                            tokio::time::sleep(Duration::from_secs(1)).await;
-                                            continue
-                                        };
-
-                                        round.slot_hash = s_hash;
-
-                                        if let Some(r) = round.rng() {
-                                            let wsquare = round.winning_square(r);
-                                            tracing::info!("WINNING SQUARE: {}", wsquare);
-                                            if let Err(_) = app_state.live_data_broadcaster.send(crate::app_state::LiveBroadcastData::WinningSquare(
-                                                AppWinningSquare {
-                                                    round_id: round.id,
-                                                    winning_square: wsquare,
-                                                }
-                                            )) {
-                                                tracing::error!("Failed to broadcast live round data");
-                                            }
-                                            emitted_winning_square = true;
+                                            tracing::error!("Failed to parse Round account");
+                                            tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+                                            continue;
                                         }
                                     } else {
-                                        println!("\nFailed to get slothash\n");
-                                        tokio::time::sleep(Duration::from_secs(1)).await;
-                                        continue;
+                                        tracing::error!("Failed to load round account data");
+                                        tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+                                        continue
                                     };
-                                },
-                                Err(_e) => {
-                                    println!("Failed to get slothash for slot {}", board.end_slot);
-                                    tokio::time::sleep(Duration::from_secs(1)).await;
+
+                                    round.slot_hash = s_hash;
+
+                                    if let Some(r) = round.rng() {
+                                        let wsquare = round.winning_square(r);
+                                        tracing::info!("WINNING SQUARE: {}", wsquare);
+                                        if let Err(_) = app_state.live_data_broadcaster.send(crate::app_state::LiveBroadcastData::WinningSquare(
+                                            AppWinningSquare {
+                                                round_id: round.id,
+                                                winning_square: wsquare,
+                                            }
+                                        )) {
+                                            tracing::error!("Failed to broadcast live round data");
+                                        }
+                                        emitted_winning_square = true;
+                                    }
+                                } else {
+                                    println!("\nFailed to get slothash\n");
+                                    tokio::time::sleep(jittered(Duration::from_secs(1))).await;
                                     continue;
-                                }
+                                };
+                            },
+                            Err(_e) => {
+                                println!("Failed to get slothash for slot {}", board.end_slot);
+                                tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+                                continue;
                             }
                         }
-
-                        tokio::time::sleep(Duration::from_millis(400)).await;
-                        continue;
-                    } else {
-                        tracing::error!("Failed to get entropy seed api data");
-                        tokio::time::sleep(Duration::from_secs(2)).await;
-                        continue;
                     }
+
+                    tokio::time::sleep(jittered(Duration::from_millis(400))).await;
+                    continue;
+                } else {
+                    tracing::error!("Failed to get entropy seed api data");
+                    tokio::time::sleep(jittered(Duration::from_secs(2))).await;
+                    continue;
                 }
-                tokio::time::sleep(Duration::from_secs(5)).await;
-            } else if slots_left_in_round > 0 {
-                let now = Instant::now();
-                board_snapshot = false;
-                emitted_winning_square = false;
-                tracing::info!("Checking miner snapshot status: {}", miners_snapshot.completed);
-                if !miners_snapshot.completed {
-                    let r_now = Instant::now();
-                    tracing::info!("Performing snapshot and updating round");
-                    // load previous round
-                    let round_id = board.round_id - 1;
-                    let mut round = if let Ok(round) = connection.get_account_data(&round_pda(round_id).0).await {
-                        if let Ok(round) = Round::try_from_bytes(&round) {
-                            round.clone()
-                        } else {
-                            tracing::error!("Failed to parse Round account");
-                            tokio::time::sleep(Duration::from_secs(1)).await;
-                            continue;
-                        }
-                    } else {
-                        tracing::error!("Failed to load round account data");
-                        tokio::time::sleep(Duration::from_secs(1)).await;
-                        continue
-                    };
+            }
+            tokio::time::sleep(jittered(Duration::from_secs(5))).await;
+        } else if slots_left_in_round > 0 {
+            let now = Instant::now();
+            board_snapshot = false;
+            emitted_winning_square = false;
+            tracing::info!("Checking miner snapshot status: {}", miners_snapshot.completed);
+            if !miners_snapshot.completed {
+                let round_id = board.round_id - 1;
+                if finalize_round(connection, &app_state, &mut miners_snapshot, treasury, round_id).await != FinalizeOutcome::Finalized {
+                    continue;
+                }
+            }
 
 
-                    if round.slot_hash == [0; 32] {
-                        tracing::error!("Round slot hash should not be 0's");
-                        tokio::time::sleep(Duration::from_secs(1)).await;
-                        continue;
-                    } else if round.slot_hash == [u8::MAX; 32] {
-                        tracing::error!("Round reset failed");
-                        tokio::time::sleep(Duration::from_secs(1)).await;
-                        tracing::error!("");
-                        // Update miners
-                        let r = app_state.miners.clone();
-                        let mut l = r.write().await;
-                        *l = miners_snapshot.miners.clone();
-                        drop(l);
-                        miners_snapshot.completed = true;
-
-                        let mut db_snapshot: Vec<CreateMinerSnapshot> = vec![];
-
-                        for m in miners_snapshot.miners.iter() {
-                            let m = m.clone();
-                            db_snapshot.push(m.into());
-                        }
 
-                        // insert miners
-                        if let Err(e) = insert_miner_snapshots(&db_pool, &db_snapshot).await {
-                            tracing::error!("Failed to insert miners snapshot: {:?}", e);
-                        }
+            let elapsed = now.elapsed().as_millis();
+            let sleep_time = ((slots_left_in_round as u64  * app_state.slot_time_ms) as u128).saturating_sub(elapsed) as u64;
+            println!("Sleeping until round is over in {} ms", sleep_time);
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(sleep_time)) => {},
+                _ = shutdown.changed() => {},
+            }
+        } else {
+            board_snapshot = false;
+            println!("Sleeping for 5 seconds");
+            tokio::select! {
+                _ = tokio::time::sleep(jittered(Duration::from_secs(5))) => {},
+                _ = shutdown.changed() => {},
+            }
+        }
 
-                        // update round
-                        let r = app_state.rounds.clone();
-                        let mut l = r.write().await;
-                        l.push(round.into());
-                        drop(l);
 
-                        // insert round
-                        if let Err(e) = insert_round(&db_pool, &RoundRow::from(round)).await {
-                            tracing::error!("Failed to insert round: {:?}", e);
-                        }
+    }
+}
 
-                        // insert treasury
-                        if let Err(e) = insert_treasury(&db_pool, &CreateTreasury::from(treasury)).await {
-                            tracing::error!("Failed to insert treasury: {:?}", e);
-                        }
-                        miners_snapshot.completed = true;
-                        continue;
-                    } else {
-                        // process round data
-                        if let Some(_r) = round.rng() {
-                            let (winning_square_opt, top_sample_opt, denom_opt) = if let Some(r) = round.rng() {
-                                let winning_square = round.winning_square(r) as usize;
-
-                                // Total deployed on winning square (denominator for pro-rata shares)
-                                let denom = round.deployed[winning_square];
-                                if denom == 0 {
-                                    // Degenerate case: nothing deployed on the winning square → no rewards
-                                    (Some(winning_square), None, Some(denom))
-                                } else {
-                                    // If split, every miner on the winning square shares top_miner_reward pro-rata.
-                                    // If not split, one miner (whose cumulative range contains top_sample) takes it all.
-                                    let top_sample = if round.top_miner == SPLIT_ADDRESS {
-                                        None
-                                    } else {
-                                        Some(round.top_miner_sample(r, winning_square))
-                                    };
-                                    (Some(winning_square), top_sample, Some(denom))
-                                }
-                            } else {
-                                (None, None, None)
-                            };
-
-                            let mut deployments: Vec<CreateDeployment> = Vec::new();
-
-                            // Convenience captures
-                            let winning_square = winning_square_opt;
-                            let denom = denom_opt.unwrap_or(0);
-                            let is_split = round.top_miner == SPLIT_ADDRESS;
-                            let motherlode_amt = round.motherlode; // you already set this earlier if did_hit_motherlode
-                            let total_winnings = round.total_winnings;
-                            let top_sample = top_sample_opt; // same for all miners if not split
-
-                            for miner in miners_snapshot.miners.iter() {
-                                if miner.round_id == round.id {
-                                     for (square_index, amount) in miner.deployed.iter().enumerate() {
-                                         if *amount == 0 {
-                                             continue;
-                                         }
-
-                                         // Defaults for non-winning squares (or missing RNG)
-                                         let mut sol_earned_u64: u64 = 0;
-                                         let mut ore_earned_u64: u64 = 0;
-
-                                         // Only compute rewards on the winning square and when we had RNG
-                                         if let Some(ws) = winning_square {
-                                             if square_index == ws && denom > 0 {
-                                                 // ---- SOL rewards ----
-                                                 // Base = original_deployment - admin_fee (admin_fee = max(1, original/100))
-                                                 let original = *amount as u64;
-                                                 let admin_fee = (original / 100).max(1);
-                                                 let mut rewards_sol = original.saturating_sub(admin_fee);
-
-                                                 // Pro-rata share of round.total_winnings
-                                                 let share = ((total_winnings as u128 * original as u128) / denom as u128) as u64;
-                                                 rewards_sol = rewards_sol.saturating_add(share);
-
-                                                 sol_earned_u64 = rewards_sol;
-
-                                                 // ---- ORE rewards ----
-                                                 // Top miner reward: split evenly pro-rata if split, else winner-takes-all by sample
-                                                 if is_split {
-                                                     let split_share = ((round.top_miner_reward as u128 * original as u128)
-                                                         / denom as u128) as u64;
-                                                     ore_earned_u64 = ore_earned_u64.saturating_add(split_share);
-                                                 } else if let Some(sample) = top_sample {
-                                                     // Check if this miner's cumulative interval covers the sample
-                                                     let start = miner.cumulative[ws];
-                                                     let end = start.saturating_add(original);
-                                                     if sample >= start && sample < end {
-                                                         ore_earned_u64 = ore_earned_u64.saturating_add(round.top_miner_reward);
-                                                         round.top_miner = Pubkey::from_str(&miner.authority).unwrap();
-                                                     }
-                                                 }
-
-                                                 // Motherlode reward (if any)
-                                                 if motherlode_amt > 0 {
-                                                     let ml_share = ((motherlode_amt as u128 * original as u128)
-                                                         / denom as u128) as u64;
-                                                     ore_earned_u64 = ore_earned_u64.saturating_add(ml_share);
-                                                 }
-                                             }
-                                         }
-
-                                         let deployment = CreateDeployment {
-                                             round_id: miner.round_id as i64,
-                                             pubkey: miner.authority.to_string(),
-                                             square_id: square_index as i64,
-                                             amount: *amount as i64,
-                                             sol_earned: sol_earned_u64 as i64,
-                                             ore_earned: ore_earned_u64 as i64,
-                                             unclaimed_ore: miner.rewards_ore as i64,
-                                             created_at: chrono::Utc::now().to_rfc3339(),
-                                         };
-
-                                         deployments.push(deployment);
-                                     }
-                                }
+/// What a finalize attempt did, so the poll loop knows whether to retry right away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinalizeOutcome {
+    /// Deployments, miners, round and treasury were stored for the round.
+    Finalized,
+    /// The round's reset failed on chain, it was stored without deployments.
+    ResetFailed,
+    /// The round couldn't be loaded or has no rng yet, try again next poll.
+    Retry,
+}
 
-                            }
-                            let n = Instant::now();
-                            if let Err(e) = insert_deployments(&db_pool, &deployments).await {
-                                tracing::error!("Failed to insert deployments: {:?}", e);
-                            }
-                            tracing::info!("Inserted deployments in {} ms", n.elapsed().as_millis());
+/// Stores the finished round `round_id` against the miners snapshot taken when it ended:
+/// computes every deployment's reward, then writes deployments, miners, the round and
+/// the treasury and marks the snapshot completed.
+async fn finalize_round<C: ChainSource>(
+    connection: &C,
+    app_state: &AppState,
+    miners_snapshot: &mut MinerSnapshot,
+    treasury: Treasury,
+    round_id: u64,
+) -> FinalizeOutcome {
+    let db_pool = &app_state.db_pool;
+    let db_2_pool = &app_state.db_2_pool;
+    let read_only = app_state.read_only;
+
+    let r_now = Instant::now();
+    tracing::info!("Performing snapshot and updating round");
+    // load previous round
+    let mut round = if let Ok(round) = rpc_call(app_state, "get_account_data", connection.get_account_data(&addresses().round(round_id))).await {
+        if let Ok(round) = Round::try_from_bytes(&round) {
+            round.clone()
+        } else {
+            tracing::error!("Failed to parse Round account");
+            tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+            return FinalizeOutcome::Retry;
+        }
+    } else {
+        tracing::error!("Failed to load round account data");
+        tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+        return FinalizeOutcome::Retry;
+    };
+
+
+    if round.slot_hash == [0; 32] {
+        tracing::error!("Round slot hash should not be 0's");
+        tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+        return FinalizeOutcome::Retry;
+    } else if round.slot_hash == [u8::MAX; 32] {
+        tracing::error!("Round reset failed");
+        tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+        tracing::error!("");
+        // Update miners
+        let r = app_state.miners.clone();
+        let mut l = r.write().await;
+        broadcast_miner_changes(app_state, &l, &miners_snapshot.miners);
+        *l = miners_snapshot.miners.clone();
+        drop(l);
+        *app_state.miners_loaded_at.write().await = Some(chrono::Utc::now().timestamp());
+        miners_snapshot.completed = true;
+
+        if !read_only {
+            if let Err(e) = database::upsert_current_miners(db_pool, &miners_snapshot.miners).await {
+                tracing::error!("Failed to upsert current miners: {:?}", e);
+            }
 
+            let mut db_snapshot: Vec<CreateMinerSnapshot> = vec![];
 
-                        } else {
-                            tracing::error!("Failed to get round rng.");
-                            tokio::time::sleep(Duration::from_secs(1)).await;
-                            continue
-                        }
-                        
-                        // Update miners
-                        let r = app_state.miners.clone();
-                        let mut l = r.write().await;
-                        *l = miners_snapshot.miners.clone();
-                        drop(l);
-
-                        let mut db_snapshot: Vec<CreateMinerSnapshot> = vec![];
-
-                        for m in miners_snapshot.miners.iter() {
-                            let m = m.clone();
-                            db_snapshot.push(m.into());
-                        }
+            for m in miners_snapshot.miners.iter() {
+                let m = m.clone();
+                db_snapshot.push(m.into());
+            }
 
-                        // insert miners
-                        if let Err(e) = insert_miner_snapshots(&db_pool, &db_snapshot).await {
-                            tracing::error!("Failed to insert miners snapshot: {:?}", e);
-                        }
+            // insert miners
+            if let Err(e) = insert_miner_snapshots(db_pool, db_2_pool, &db_snapshot).await {
+                tracing::error!("Failed to insert miners snapshot: {:?}", e);
+            }
+        }
 
+        // update round
+        let r = app_state.rounds.clone();
+        let mut l = r.write().await;
+        l.push(round.into());
+        drop(l);
+
+        if !read_only {
+            // insert round
+            let players = miners_snapshot.miners.iter().filter(|m| m.round_id == round.id && m.total_deployed > 0).count();
+            if let Err(e) = insert_round(db_pool, db_2_pool, &RoundRow::with_players(round, players)).await {
+                tracing::error!("Failed to insert round: {:?}", e);
+            }
 
-                        // update round
-                        let n = Instant::now();
-                        tracing::info!("\n----------------\nUpdating round.");
-                        let r = app_state.rounds.clone();
-                        let mut l = r.write().await;
-                        l.push(round.into());
-                        drop(l);
-                        tracing::info!("Updated round in {} ms", n.elapsed().as_millis());
+            // insert treasury
+            if let Err(e) = insert_treasury(db_pool, db_2_pool, &CreateTreasury::at_round(treasury, round.id)).await {
+                tracing::error!("Failed to insert treasury: {:?}", e);
+            }
+        }
+        save_snapshot_state(app_state, round.id, true).await;
+        app_state.round_finalized.send_replace(round.id);
+        return FinalizeOutcome::ResetFailed;
+    } else {
+        // process round data
+        if let Some(params) = RoundRewardParams::from_round(&round) {
+            let mut deployments: Vec<CreateDeployment> = Vec::new();
+
+            for miner in miners_snapshot.miners.iter() {
+                if miner.round_id == round.id {
+                     for (square_index, amount) in miner.deployed.iter().enumerate() {
+                         if *amount == 0 {
+                             continue;
+                         }
+
+                         let reward = compute_deployment_reward(&params, square_index, *amount, miner.cumulative[square_index]);
+                         if reward.top_miner {
+                             round.top_miner = Pubkey::from_str(&miner.authority).unwrap();
+                         }
+
+                         let deployment = CreateDeployment {
+                             round_id: miner.round_id as i64,
+                             pubkey: miner.authority.to_string(),
+                             square_id: square_index as i64,
+                             amount: *amount as i64,
+                             sol_earned: reward.sol as i64,
+                             ore_earned: reward.ore as i64,
+                             unclaimed_ore: miner.rewards_ore as i64,
+                             cumulative: miner.cumulative[square_index] as i64,
+                             admin_fee: reward.admin_fee as i64,
+                             created_at: chrono::Utc::now().to_rfc3339(),
+                         };
+
+                         deployments.push(deployment);
+                     }
+                }
 
-                        // insert round
-                        let n = Instant::now();
-                        if let Err(e) = insert_round(&db_pool, &RoundRow::from(round)).await {
-                            tracing::error!("Failed to insert round: {:?}", e);
-                        }
-                        tracing::info!("Inserted round in {} ms", n.elapsed().as_millis());
+            }
+            if !read_only {
+                let n = Instant::now();
+                if let Err(e) = insert_deployments(db_pool, db_2_pool, &deployments).await {
+                    tracing::error!("Failed to insert deployments: {:?}", e);
+                }
+                tracing::info!("Inserted deployments in {} ms", n.elapsed().as_millis());
+            }
 
-                        // insert treasury
-                        let n = Instant::now();
-                        if let Err(e) = insert_treasury(&db_pool, &CreateTreasury::from(treasury)).await {
-                            tracing::error!("Failed to insert treasury: {:?}", e);
-                        }
-                        tracing::info!("Inserted treasury in {} ms", n.elapsed().as_millis());
 
+        } else {
+            tracing::error!("Failed to get round rng.");
+            tokio::time::sleep(jittered(Duration::from_secs(1))).await;
+            return FinalizeOutcome::Retry;
+        }
 
-                        let n = Instant::now();
-                        if let Err(e) = database::finalize_round_idempotent(&db_pool, round.id as i64).await {
-                            tracing::error!("Failed to finalize for round: {:?}", e);
-                        }
-                        tracing::info!("Finalized data in {} ms", n.elapsed().as_millis());
+        // Update miners
+        let r = app_state.miners.clone();
+        let mut l = r.write().await;
+        broadcast_miner_changes(app_state, &l, &miners_snapshot.miners);
+        *l = miners_snapshot.miners.clone();
+        drop(l);
+        *app_state.miners_loaded_at.write().await = Some(chrono::Utc::now().timestamp());
+        if !read_only {
+            if let Err(e) = database::upsert_current_miners(db_pool, &miners_snapshot.miners).await {
+                tracing::error!("Failed to upsert current miners: {:?}", e);
+            }
 
-                        tracing::info!("Successfully snapshot round and updated database in {}ms", r_now.elapsed().as_millis());
-                        miners_snapshot.completed = true;
-                    }
-                }
+            let mut db_snapshot: Vec<CreateMinerSnapshot> = vec![];
 
+            for m in miners_snapshot.miners.iter() {
+                let m = m.clone();
+                db_snapshot.push(m.into());
+            }
 
+            // insert miners
+            if let Err(e) = insert_miner_snapshots(db_pool, db_2_pool, &db_snapshot).await {
+                tracing::error!("Failed to insert miners snapshot: {:?}", e);
+            }
+        }
 
-                let elapsed = now.elapsed().as_millis();
-                let sleep_time = ((slots_left_in_round as u64  * 400) as u128 - elapsed) as u64;
-                println!("Sleeping until round is over in {} ms", sleep_time);
-                tokio::time::sleep(Duration::from_millis(sleep_time)).await;
-            } else {
-                board_snapshot = false;
-                println!("Sleeping for 5 seconds");
-                tokio::time::sleep(Duration::from_secs(5)).await;
+
+        // update round
+        let n = Instant::now();
+        tracing::info!("\n----------------\nUpdating round.");
+        let r = app_state.rounds.clone();
+        let mut l = r.write().await;
+        l.push(round.into());
+        drop(l);
+        tracing::info!("Updated round in {} ms", n.elapsed().as_millis());
+
+        if !read_only {
+            // insert round
+            let n = Instant::now();
+            let players = miners_snapshot.miners.iter().filter(|m| m.round_id == round.id && m.total_deployed > 0).count();
+            if let Err(e) = insert_round(db_pool, db_2_pool, &RoundRow::with_players(round, players)).await {
+                tracing::error!("Failed to insert round: {:?}", e);
+            }
+            tracing::info!("Inserted round in {} ms", n.elapsed().as_millis());
+
+            // insert treasury
+            let n = Instant::now();
+            if let Err(e) = insert_treasury(db_pool, db_2_pool, &CreateTreasury::at_round(treasury, round.id)).await {
+                tracing::error!("Failed to insert treasury: {:?}", e);
+            }
+            tracing::info!("Inserted treasury in {} ms", n.elapsed().as_millis());
+
+
+            let n = Instant::now();
+            if let Err(e) = database::finalize_round_idempotent(db_pool, round.id as i64).await {
+                tracing::error!("Failed to finalize for round: {:?}", e);
+            }
+            tracing::info!("Finalized data in {} ms", n.elapsed().as_millis());
+        }
+
+        tracing::info!("Successfully snapshot round and updated database in {}ms", r_now.elapsed().as_millis());
+        miners_snapshot.completed = true;
+        save_snapshot_state(app_state, round.id, true).await;
+        app_state.round_finalized.send_replace(round.id);
+    }
+    FinalizeOutcome::Finalized
+}
+
+/// Byte offset of `Miner::authority` in the account data, after the discriminator.
+const MINER_AUTHORITY_OFFSET: usize = 8;
+
+/// Prefix depth at which a failing partition is given up on, 2 bytes is 65536 partitions.
+const MAX_PARTITION_DEPTH: usize = 2;
+
+/// Queries a single miners fetch may send, including the ones that get split.
+const MAX_MINER_QUERIES: u64 = 1024;
+
+/// Whether the node refused a query because its response would be too large. Timeouts,
+/// rate limits and other failures aren't, and splitting them only multiplies the load.
+fn is_response_size_error(e: &ClientError) -> bool {
+    match e.kind() {
+        ClientErrorKind::Reqwest(r) => r.status() == Some(reqwest::StatusCode::PAYLOAD_TOO_LARGE) || r.is_decode(),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { message, .. }) => {
+            let message = message.to_lowercase();
+            ["too large", "response size", "size limit", "scan limit"].iter().any(|m| message.contains(m))
+        }
+        // a truncated body from a proxy that cut the response off
+        ClientErrorKind::SerdeJson(e) => e.is_eof(),
+        _ => false,
+    }
+}
+
+/// Fetches every Miner account. Large programs can exceed the node's response size
+/// limit, so when a query is refused for its size it is split into 256 memcmp queries
+/// on the next byte of the authority, down to `MAX_PARTITION_DEPTH` bytes, and the results
+/// are merged. Any other error, or going past `MAX_MINER_QUERIES`, fails the fetch.
+/// Returns the accounts and how many queries succeeded, 1 when no split was needed.
+pub async fn get_miner_accounts<F, Fut>(
+    commitment: CommitmentConfig,
+    fetch: F,
+) -> ClientResult<(Vec<(Pubkey, Account)>, u64)>
+where
+    F: Fn(RpcProgramAccountsConfig) -> Fut,
+    Fut: Future<Output = ClientResult<Vec<(Pubkey, Account)>>>,
+{
+    let mut accounts = vec![];
+    let mut queries = 0;
+    let mut sent = 0;
+    let mut pending: Vec<Vec<u8>> = vec![vec![]];
+    while let Some(prefix) = pending.pop() {
+        if sent >= MAX_MINER_QUERIES {
+            return Err(ClientErrorKind::Custom(format!("miners fetch gave up after {} queries", sent)).into());
+        }
+        sent += 1;
+        let mut filters = vec![RpcFilterType::DataSize(size_of::<Miner>() as u64 + 8)];
+        if !prefix.is_empty() {
+            filters.push(RpcFilterType::Memcmp(Memcmp::new_raw_bytes(MINER_AUTHORITY_OFFSET, prefix.clone())));
+        }
+        let config = RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                data_slice: None,
+                commitment: Some(commitment),
+                min_context_slot: None,
+            },
+            with_context: None,
+            sort_results: None,
+        };
+        match fetch(config).await {
+            Ok(mut part) => {
+                accounts.append(&mut part);
+                queries += 1;
             }
+            Err(e) if prefix.len() < MAX_PARTITION_DEPTH && is_response_size_error(&e) => {
+                tracing::warn!("Miners query for prefix {:02x?} failed, splitting it: {:?}", prefix, e);
+                pending.extend((0..=u8::MAX).map(|b| {
+                    let mut p = prefix.clone();
+                    p.push(b);
+                    p
+                }));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    if queries > 1 {
+        tracing::info!("Fetched {} miner accounts in {} partitions", accounts.len(), queries);
+    }
+    Ok((accounts, queries))
+}
 
+/// Data size of a Miner account as stored on chain, to catch layout drift that would
+/// make the `DataSize` filter in `get_miner_accounts` silently match nothing. Lists miner
+/// addresses by discriminator with an empty data slice, then reads the first one.
+/// None when the program has no miners.
+pub async fn observed_miner_account_size(connection: &RpcClient, commitment: CommitmentConfig) -> ClientResult<Option<usize>> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, vec![Miner::discriminator()]))]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: Some(UiDataSliceConfig { offset: 0, length: 0 }),
+            commitment: Some(commitment),
+            min_context_slot: None,
+        },
+        with_context: None,
+        sort_results: None,
+    };
+    let Some((address, _)) = connection.get_program_accounts_with_config(&addresses().program_id, config).await?.into_iter().next() else {
+        return Ok(None);
+    };
+    Ok(Some(connection.get_account_data(&address).await?.len()))
+}
 
+/// Parses fetched Miner accounts, filling in the inferred refined ORE. Accounts that
+/// don't parse (layout drift, truncated data) are counted and logged rather than dropped
+/// silently.
+pub fn parse_miner_accounts(accounts: &[(Pubkey, Account)], treasury: &Treasury) -> (Vec<AppMiner>, u64) {
+    let mut miners = Vec::with_capacity(accounts.len());
+    let mut skipped = 0;
+    for (address, account) in accounts {
+        match Miner::try_from_bytes(&account.data) {
+            Ok(miner) => {
+                let mut miner = *miner;
+                miner.refined_ore = infer_refined_ore(&miner, treasury);
+                miners.push(miner.into());
+            }
+            Err(e) => {
+                skipped += 1;
+                tracing::debug!("Failed to parse Miner account {}: {:?}", address, e);
+            }
         }
+    }
+    if skipped > 0 {
+        tracing::warn!("Skipped {} of {} miner accounts that failed to parse", skipped, accounts.len());
+    }
+    (miners, skipped)
+}
+
+/// Spreads `d` by up to ±`POLL_JITTER_PCT` percent (default 10, capped at 50) so restarts
+/// or several instances sharing a provider don't poll in lockstep.
+fn jittered(d: Duration) -> Duration {
+    static JITTER_PCT: OnceLock<u64> = OnceLock::new();
+    let pct = *JITTER_PCT.get_or_init(|| {
+        env::var("POLL_JITTER_PCT")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(10)
+            .min(50)
     });
+    if pct == 0 {
+        return d;
+    }
+    // RandomState is freshly keyed per call, good enough for jitter without pulling in rand
+    let r = RandomState::new().build_hasher().finish();
+    let factor = 1.0 + (pct as f64 / 100.0) * ((r % 2001) as f64 / 1000.0 - 1.0);
+    d.mul_f64(factor)
+}
+
+/// Re-reads a round account from chain, upserts it and re-finalizes the round, for
+/// repairing rounds stored with bad data (e.g. the winning_square 100 sentinel).
+/// Returns None if the round account no longer exists. Deployments aren't rebuilt,
+/// the miner state they came from is gone once the round is over.
+pub async fn refetch_round(app_state: &AppState, round_id: u64) -> anyhow::Result<Option<RoundRow>> {
+    let connection = new_rpc_client(app_state);
+    let account = rpc_call(
+        app_state,
+        "get_account_with_commitment",
+        connection.get_account_with_commitment(&addresses().round(round_id), app_state.commitment),
+    )
+    .await?
+    .value;
+    let Some(account) = account else {
+        return Ok(None);
+    };
+    let round = Round::try_from_bytes(&account.data)
+        .map_err(|e| anyhow::anyhow!("Failed to parse Round account {}: {:?}", round_id, e))?;
+
+    insert_round(&app_state.db_pool, &app_state.db_2_pool, &RoundRow::from(*round)).await?;
+    database::finalize_round_idempotent(&app_state.db_pool, round_id as i64).await?;
+    tracing::info!("Refetched round {} from rpc", round_id);
+
+    Ok(database::get_round_by_id(&app_state.db_pool, round_id as i64).await?.into_iter().next())
+}
+
+/// Sends every miner in `new` that differs from its entry in `old` to /ws subscribers.
+fn broadcast_miner_changes(app_state: &AppState, old: &[AppMiner], new: &[AppMiner]) {
+    if app_state.miner_updates.receiver_count() == 0 {
+        return;
+    }
+    let previous: HashMap<&str, &AppMiner> = old.iter().map(|m| (m.authority.as_str(), m)).collect();
+    for miner in new {
+        if previous.get(miner.authority.as_str()) != Some(&miner) {
+            let _ = app_state.miner_updates.send(miner.clone());
+        }
+    }
+}
+
+/// Picks up the round boundary where the last run stopped. If the previous round was
+/// already stored it's marked completed so it isn't inserted twice. If its miners were
+/// snapshot but never stored, the miners main loaded at startup stand in for the lost
+/// snapshot; miners that haven't deployed in the new round still hold that round's
+/// deployments. Anything else starts fresh as before.
+async fn resume_miner_snapshot(app_state: &AppState) -> MinerSnapshot {
+    let mut snapshot = MinerSnapshot {
+        round_id: 0,
+        miners: vec![],
+        completed: false,
+    };
+    let state = match database::get_poller_state(&app_state.db_pool).await {
+        Ok(Some(state)) => state,
+        Ok(None) => return snapshot,
+        Err(e) => {
+            tracing::error!("Failed to load poller state: {:?}", e);
+            return snapshot;
+        }
+    };
+
+    let previous_round_id = app_state.board.read().await.round_id.saturating_sub(1);
+    if state.snapshot_round_id as u64 != previous_round_id {
+        return snapshot;
+    }
+    snapshot.round_id = previous_round_id;
+    if state.snapshot_completed {
+        tracing::info!("Round {} was already stored before restart, skipping it", previous_round_id);
+        snapshot.completed = true;
+    } else {
+        tracing::warn!("Round {} was snapshot but not stored before restart, resuming from current miners", previous_round_id);
+        snapshot.miners = app_state.miners.read().await.clone();
+    }
+    snapshot
+}
+
+async fn save_snapshot_state(app_state: &AppState, round_id: u64, completed: bool) {
+    if app_state.read_only {
+        return;
+    }
+    let state = database::PollerState { snapshot_round_id: round_id as i64, snapshot_completed: completed };
+    if let Err(e) = database::save_poller_state(&app_state.db_pool, &state).await {
+        tracing::error!("Failed to save poller state: {:?}", e);
+    }
+}
+
+fn backfill_delay_ms() -> u64 {
+    env::var("BACKFILL_DELAY_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(500)
+}
+
+/// Fetches the given round accounts from RPC and upserts them, sleeping between
+/// calls so a large catch-up doesn't flood the RPC. Rounds whose account is closed are
+/// added to `unrecoverable_rounds`. Returns how many were stored.
+async fn backfill_round_ids(connection: &impl ChainSource, app_state: &AppState, round_ids: Vec<u64>) -> u64 {
+    let delay_ms = backfill_delay_ms();
+    let total = round_ids.len();
+    let mut backfilled = 0;
+    for round_id in round_ids {
+        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        let address = addresses().round(round_id);
+        let account = match rpc_call(app_state, "get_account_with_commitment", connection.get_account_with_commitment(&address, app_state.commitment)).await {
+            Ok(response) => response.value,
+            Err(e) => {
+                tracing::warn!("Failed to load round account data for {}: {:?}", round_id, e);
+                continue;
+            }
+        };
+        let Some(account) = account else {
+            tracing::warn!("Round account {} is closed, it can't be backfilled", round_id);
+            app_state.unrecoverable_rounds.write().await.insert(round_id);
+            continue;
+        };
+        let round = if let Ok(round) = Round::try_from_bytes(&account.data) {
+            round.clone()
+        } else {
+            tracing::error!("Failed to parse Round account {}", round_id);
+            continue;
+        };
+
+        if let Err(e) = insert_round(&app_state.db_pool, &app_state.db_2_pool, &RoundRow::from(round)).await {
+            tracing::error!("Failed to insert backfilled round {}: {:?}", round_id, e);
+            continue;
+        }
+        backfilled += 1;
+
+        if backfilled % 50 == 0 {
+            tracing::info!("Backfill progress: {}/{}", backfilled, total);
+        }
+    }
+    backfilled
+}
+
+/// Fetches rounds that were missed while the server was down and upserts them.
+/// Only the round accounts are recoverable; miner accounts only hold their latest
+/// round so historical deployments can't be reconstructed here. The most recent
+/// finished round (`current_round_id - 1`) is left to the poll loop which does
+/// snapshot the miners for it.
+pub async fn backfill_rounds(app_state: AppState, current_round_id: u64) {
+    tokio::spawn(async move {
+        let connection = new_rpc_client(&app_state);
+
+        let last_stored = match database::get_max_round_id(&app_state.db_pool).await {
+            Ok(Some(id)) => id as u64,
+            Ok(None) => {
+                tracing::info!("No rounds stored yet, skipping backfill");
+                return;
+            },
+            Err(e) => {
+                tracing::error!("Failed to get max round id for backfill: {:?}", e);
+                return;
+            }
+        };
+
+        let end = current_round_id.saturating_sub(1);
+        if last_stored + 1 >= end {
+            tracing::info!("Rounds are up to date, nothing to backfill");
+            return;
+        }
+
+        let round_ids: Vec<u64> = ((last_stored + 1)..end).collect();
+        let total = round_ids.len();
+        tracing::info!("Backfilling {} rounds ({}..{})", total, last_stored + 1, end);
+        let backfilled = backfill_round_ids(&connection, &app_state, round_ids).await;
+        tracing::info!("Backfilled {}/{} rounds", backfilled, total);
+    });
+}
+
+/// Round ids inside the gaps that haven't already been found closed on chain.
+async fn recoverable_gap_ids(app_state: &AppState, gaps: &[RoundGap]) -> Vec<u64> {
+    let unrecoverable = app_state.unrecoverable_rounds.read().await;
+    gaps.iter()
+        .flat_map(|g| (g.start_id as u64)..=(g.end_id as u64))
+        .filter(|id| !unrecoverable.contains(id))
+        .collect()
+}
+
+/// Fetches every recoverable round id inside the given gaps from RPC in the background.
+/// Returns false without doing anything if another gap backfill is still running.
+pub fn backfill_gaps(app_state: AppState, gaps: Vec<RoundGap>) -> bool {
+    if app_state.gap_backfill_running.swap(true, Ordering::AcqRel) {
+        tracing::info!("Gap backfill already running, not starting another");
+        return false;
+    }
+    tokio::spawn(async move {
+        let round_ids = recoverable_gap_ids(&app_state, &gaps).await;
+        let total = round_ids.len();
+        if total > 0 {
+            let connection = new_rpc_client(&app_state);
+            tracing::info!("Backfilling {} rounds across {} gaps", total, gaps.len());
+            let backfilled = backfill_round_ids(&connection, &app_state, round_ids).await;
+            tracing::info!("Backfilled {}/{} gap rounds", backfilled, total);
+        }
+        app_state.gap_backfill_running.store(false, Ordering::Release);
+    });
+    true
+}
+
+/// Periodically scans the rounds table for missing ids and backfills them.
+pub async fn watch_round_gaps(app_state: AppState) {
+    let interval_secs = env::var("GAP_SCAN_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(3600);
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval_secs)).await;
+            match database::get_round_gaps(&app_state.db_pool).await {
+                Ok(gaps) => {
+                    // closed rounds stay gaps forever, only report the ones worth fetching
+                    let recoverable = recoverable_gap_ids(&app_state, &gaps).await.len();
+                    if recoverable == 0 {
+                        continue;
+                    }
+                    tracing::warn!("Found {} gaps in rounds table, {} rounds recoverable", gaps.len(), recoverable);
+                    backfill_gaps(app_state.clone(), gaps);
+                },
+                Err(e) => {
+                    tracing::error!("Failed to scan rounds for gaps: {:?}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Recomputes a round's winning square from its slot hash the way the program does
+/// (`Round::rng` then `Round::winning_square`). None if the hash isn't 32 bytes or
+/// is one of the values `rng` rejects (unset or a failed reset).
+pub fn recompute_winning_square(slot_hash: &[u8]) -> Option<usize> {
+    let mut round: Round = bytemuck::Zeroable::zeroed();
+    round.slot_hash = slot_hash.try_into().ok()?;
+    round.rng().map(|r| round.winning_square(r))
 }
 
 pub fn infer_refined_ore(miner: &Miner, treasury: &Treasury) -> u64 {
@@ -516,17 +1149,16 @@ pub fn refinement_level_percent(refined_ore: f64, unclaimed_ore: f64) -> f64 {
     }
 }
 
-pub async fn watch_live_board(rpc_url: &str, app_state: AppState) {
-    let prefix = "ws://".to_string();
-    let url = prefix + rpc_url;
-    //let http_url = "https://".to_string() + rpc_url;
+pub async fn watch_live_board(app_state: AppState) {
     tokio::spawn(async move {
         loop {
+            // follows the poller's failover on each reconnect
+            let url = "ws://".to_string() + app_state.rpc_url();
             if let Ok(ps_client) = PubsubClient::new(&url).await {
                 let account_info_config = RpcAccountInfoConfig {
                     encoding: Some(UiAccountEncoding::Base64),
                     data_slice: None,
-                    commitment: Some(CommitmentConfig { commitment: CommitmentLevel::Confirmed }),
+                    commitment: Some(app_state.commitment),
                     min_context_slot: None,
                 };
                 let config = solana_client::rpc_config::RpcProgramAccountsConfig {
@@ -536,7 +1168,7 @@ pub async fn watch_live_board(rpc_url: &str, app_state: AppState) {
                     sort_results: None,
                 };
                 let mut live_round_id = 0;
-                if let Ok((mut accounts_stream, accounts_stream_unsub)) = ps_client.program_subscribe(&ore_api::id(), Some(config)).await {
+                if let Ok((mut accounts_stream, accounts_stream_unsub)) = ps_client.program_subscribe(&addresses().program_id, Some(config)).await {
                     while let Some(account_data) = accounts_stream.next().await {
                         let data_ctx = account_data.context;
                         if let Some(data) = account_data.value.account.data.decode() {
@@ -592,3 +1224,173 @@ pub async fn watch_live_board(rpc_url: &str, app_state: AppState) {
     ()
 }
 
+#[cfg(test)]
+mod tests {
+    use bytemuck::Zeroable;
+    use ore_api::consts::SPLIT_ADDRESS;
+
+    use super::*;
+    use crate::chain::mock::MockChainSource;
+
+    const ONE_SOL: u64 = 1_000_000_000;
+    const ONE_ORE: u64 = 100_000_000_000;
+
+    fn miner(round_id: u64, deployed: [u64; 25], cumulative: [u64; 25]) -> AppMiner {
+        let mut miner = Miner::zeroed();
+        miner.authority = Pubkey::new_unique();
+        miner.round_id = round_id;
+        miner.deployed = deployed;
+        miner.cumulative = cumulative;
+        miner.into()
+    }
+
+    #[tokio::test]
+    async fn finalize_round_stores_round_deployments_and_totals() {
+        let app_state = AppState::for_tests(database::memory_pool().await);
+
+        // a split round won by square 7: A put 1 SOL on it, B 2 SOL after A and 1 SOL on square 2
+        let mut a_deployed = [0; 25];
+        a_deployed[7] = ONE_SOL;
+        let a = miner(5, a_deployed, [0; 25]);
+        let mut b_deployed = [0; 25];
+        b_deployed[7] = 2 * ONE_SOL;
+        b_deployed[2] = ONE_SOL;
+        let mut b_cumulative = [0; 25];
+        b_cumulative[7] = ONE_SOL;
+        let b = miner(5, b_deployed, b_cumulative);
+        // played an earlier round, not part of this one
+        let idle = miner(4, a_deployed, [0; 25]);
+
+        let mut round = Round::zeroed();
+        round.id = 5;
+        round.deployed[7] = 3 * ONE_SOL;
+        round.deployed[2] = ONE_SOL;
+        round.slot_hash[0] = 7;
+        round.top_miner = SPLIT_ADDRESS;
+        round.top_miner_reward = ONE_ORE;
+        round.total_deployed = 4 * ONE_SOL;
+        round.total_vaulted = ONE_SOL / 10;
+        round.total_winnings = ONE_SOL - ONE_SOL / 10;
+
+        let mut chain = MockChainSource::default();
+        chain.insert(addresses().round(5), &round);
+        let mut treasury = Treasury::zeroed();
+        treasury.balance = 42;
+
+        let mut snapshot = MinerSnapshot { round_id: 5, miners: vec![a.clone(), b.clone(), idle], completed: false };
+        let outcome = finalize_round(&chain, &app_state, &mut snapshot, treasury, 5).await;
+        assert_eq!(outcome, FinalizeOutcome::Finalized);
+        assert!(snapshot.completed);
+
+        let pool = &app_state.db_pool;
+        let rows = database::get_round_by_id(pool, 5).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        let row = &rows[0];
+        assert_eq!(row.winning_square, 7);
+        assert_eq!(row.total_deployed, 4 * ONE_SOL as i64);
+        assert_eq!(row.total_winnings, 900_000_000);
+        assert_eq!(row.total_players, 2);
+        assert!(row.is_split);
+
+        let deployments = database::get_deployments_by_round(pool, 5).await.unwrap();
+        assert_eq!(deployments.len(), 3);
+        let find = |pubkey: &str, square: i64| {
+            deployments.iter().find(|d| d.pubkey == pubkey && d.square_id == square).unwrap()
+        };
+        // 1 SOL back minus the 1% admin fee, plus a third of the 0.9 SOL winnings
+        assert_eq!(find(&a.authority, 7).sol_earned, 1_290_000_000);
+        assert_eq!(find(&a.authority, 7).ore_earned, (ONE_ORE / 3) as i64);
+        assert_eq!(find(&b.authority, 7).sol_earned, 2_580_000_000);
+        assert_eq!(find(&b.authority, 7).ore_earned, (2 * ONE_ORE / 3) as i64);
+        assert_eq!(find(&b.authority, 2).sol_earned, 0);
+
+        let totals = database::get_miner_totals_for_round(pool, 5).await.unwrap();
+        assert_eq!(totals.len(), 2);
+        let a_totals = totals.iter().find(|t| t.pubkey == a.authority).unwrap();
+        assert_eq!((a_totals.rounds_played, a_totals.rounds_won), (1, 1));
+        assert_eq!(a_totals.net_sol_change, 290_000_000);
+        let b_totals = totals.iter().find(|t| t.pubkey == b.authority).unwrap();
+        assert_eq!(b_totals.total_sol_deployed, 3 * ONE_SOL as i64);
+        assert_eq!(b_totals.net_sol_change, -420_000_000);
+
+        let treasuries = database::get_treasuries(pool, 1, 0).await.unwrap();
+        assert_eq!(treasuries[0].round_id, Some(5));
+        assert_eq!(treasuries[0].balance, 42);
+        assert_eq!(app_state.rounds.read().await.len(), 1);
+        assert_eq!(*app_state.round_finalized.borrow(), 5);
+    }
+
+    fn rpc_error(message: &str) -> ClientError {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            code: -32600,
+            message: message.to_string(),
+            data: solana_client::rpc_request::RpcResponseErrorData::Empty,
+        })
+        .into()
+    }
+
+    #[tokio::test]
+    async fn get_miner_accounts_splits_only_oversized_queries() {
+        let calls = std::sync::atomic::AtomicU64::new(0);
+        let (accounts, queries) = get_miner_accounts(CommitmentConfig::confirmed(), |config| {
+            let n = calls.fetch_add(1, Ordering::Relaxed);
+            let split = config.filters.unwrap().len() > 1;
+            async move {
+                match (n, split) {
+                    (0, false) => Err(rpc_error("Response too large")),
+                    (_, true) => Ok(vec![(Pubkey::new_unique(), Account::default())]),
+                    _ => unreachable!(),
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(queries, 256);
+        assert_eq!(accounts.len(), 256);
+
+        let calls = std::sync::atomic::AtomicU64::new(0);
+        let res = get_miner_accounts(CommitmentConfig::confirmed(), |_| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            async { Err::<Vec<(Pubkey, Account)>, _>(rpc_error("Too many requests for a specific RPC call")) }
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn get_miner_accounts_caps_total_queries() {
+        let calls = std::sync::atomic::AtomicU64::new(0);
+        // only 2-byte prefixes fit, which would take 65536 queries
+        let res = get_miner_accounts(CommitmentConfig::confirmed(), |config| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            let prefix_len = match config.filters.unwrap().get(1) {
+                Some(RpcFilterType::Memcmp(m)) => m.bytes().unwrap().len(),
+                _ => 0,
+            };
+            async move {
+                if prefix_len < MAX_PARTITION_DEPTH {
+                    Err(rpc_error("response size limit exceeded"))
+                } else {
+                    Ok(vec![])
+                }
+            }
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), MAX_MINER_QUERIES);
+    }
+
+    #[tokio::test]
+    async fn finalize_round_retries_while_the_round_is_missing() {
+        let app_state = AppState::for_tests(database::memory_pool().await);
+        let chain = MockChainSource::default();
+        let mut snapshot = MinerSnapshot { round_id: 5, miners: vec![], completed: false };
+
+        let outcome = finalize_round(&chain, &app_state, &mut snapshot, Treasury::zeroed(), 5).await;
+        assert_eq!(outcome, FinalizeOutcome::Retry);
+        assert!(!snapshot.completed);
+        assert!(database::get_round_by_id(&app_state.db_pool, 5).await.unwrap().is_empty());
+    }
+}
+
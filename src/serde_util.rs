@@ -0,0 +1,44 @@
+use std::fmt::Display;
+
+use axum::{body::Body, http::Request, middleware::Next, response::Response};
+use serde::{Serialize, Serializer};
+
+tokio::task_local! {
+    static BIGINT_AS_STRING: bool;
+}
+
+/// True while serializing a response for a request with `?bigint=string`.
+pub fn bigint_as_string() -> bool {
+    BIGINT_AS_STRING.try_with(|v| *v).unwrap_or(false)
+}
+
+/// Runs `f` with the bigint mode set, for serialization that happens outside the
+/// request task (e.g. streamed bodies).
+pub fn with_bigint_mode<R>(enabled: bool, f: impl FnOnce() -> R) -> R {
+    BIGINT_AS_STRING.sync_scope(enabled, f)
+}
+
+/// `serialize_with` for lamport/ORE amounts that can exceed 2^53. Writes a string
+/// when the request asked for `bigint=string`, a plain number otherwise. Also used
+/// on the i64 db columns that hold u64 amounts.
+pub fn u64_as_string<T, S>(v: &T, s: S) -> Result<S::Ok, S::Error>
+where
+    T: Display + Serialize,
+    S: Serializer,
+{
+    if bigint_as_string() {
+        s.collect_str(v)
+    } else {
+        v.serialize(s)
+    }
+}
+
+/// Middleware enabling `u64_as_string` for the rest of the request when `bigint=string`.
+pub async fn bigint_mode(req: Request<Body>, next: Next) -> Response {
+    let enabled = req
+        .uri()
+        .query()
+        .map(|q| q.split('&').any(|kv| kv == "bigint=string"))
+        .unwrap_or(false);
+    BIGINT_AS_STRING.scope(enabled, next.run(req)).await
+}